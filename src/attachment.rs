@@ -0,0 +1,246 @@
+//! File attachments on issues and comments.
+//!
+//! Attachments live in the `attachments` directory of the issue or
+//! comment, one file per attachment, named after the attachment.  The
+//! file holds either the contents of the attachment, or (if the
+//! database config sets `blob_attachments = true`) a small pointer to
+//! a content-addressed blob in the `blobs` directory at the top of the
+//! issues database:
+//!
+//! ```text
+//! entomologist-blob 5716ca5987cbf97d6bb54920bea6adde242d87e6 1048576
+//! ```
+//!
+//! Blobs are named by their git object id, so a file attached to many
+//! issues is only stored once.  The git object id of an attachment
+//! stored in the issue is recorded next to it, in the `attachment_ids`
+//! directory of the issue or comment, so listing attachments doesn't
+//! need to hash them.
+//!
+//! Either way the contents are committed to the issues branch, and stay
+//! in its history (and in every clone that syncs it) even after the
+//! issue is removed, unless the history is purged.
+
+const POINTER_PREFIX: &str = "entomologist-blob ";
+
+/// The directory of an issue or comment holding the git object ids of
+/// its attachments.
+const IDS_DIR: &str = "attachment_ids";
+
+#[derive(Debug, thiserror::Error)]
+pub enum AttachmentError {
+    #[error(transparent)]
+    StdIoError(#[from] std::io::Error),
+    #[error(transparent)]
+    GitError(#[from] crate::git::GitError),
+    #[error("invalid attachment name {0:?}")]
+    InvalidName(String),
+    #[error("attachment {0} already exists")]
+    Exists(String),
+    #[error("attachment {0} not found")]
+    NotFound(String),
+    #[error("invalid blob pointer in attachment {0}")]
+    InvalidPointer(String),
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Attachment {
+    pub name: String,
+    /// Size of the contents in bytes.
+    pub size: u64,
+    /// Git object id of the contents.
+    pub hash: String,
+    /// True if the contents are stored in the blob store.
+    pub blob: bool,
+}
+
+/// Attachment names are used as filenames.
+pub fn validate_name(name: &str) -> Result<(), AttachmentError> {
+    if name.is_empty() || name == "." || name == ".." || name.contains('/') {
+        return Err(AttachmentError::InvalidName(String::from(name)));
+    }
+    Ok(())
+}
+
+/// Read the attachments in an `attachments` directory, sorted by name.
+pub(crate) fn read_attachments(dir: &std::path::Path) -> Result<Vec<Attachment>, AttachmentError> {
+    let ids_dir = dir.parent().map(|parent| parent.join(IDS_DIR));
+    let mut attachments = Vec::<Attachment>::new();
+    let mut unrecorded_paths = Vec::<std::path::PathBuf>::new();
+    for direntry in (dir.read_dir()?).flatten() {
+        let name = direntry.file_name().to_string_lossy().into_owned();
+        let path = direntry.path();
+        match read_pointer(&path, &name)? {
+            Some((hash, size)) => attachments.push(Attachment {
+                name,
+                size,
+                hash,
+                blob: true,
+            }),
+            None => {
+                let hash = match &ids_dir {
+                    Some(ids_dir) => read_id(&ids_dir.join(&name))?,
+                    None => None,
+                };
+                if hash.is_none() {
+                    unrecorded_paths.push(path);
+                }
+                attachments.push(Attachment {
+                    name,
+                    size: direntry.metadata()?.len(),
+                    hash: hash.unwrap_or_default(),
+                    blob: false,
+                });
+            }
+        }
+    }
+
+    // Attachments from before the ids were recorded.
+    if !unrecorded_paths.is_empty() {
+        let hashes = crate::git::hash_objects(dir, &unrecorded_paths, false)?;
+        let mut hashes = hashes.into_iter();
+        for attachment in attachments
+            .iter_mut()
+            .filter(|a| !a.blob && a.hash.is_empty())
+        {
+            attachment.hash = hashes.next().unwrap_or_default();
+        }
+    }
+
+    attachments.sort();
+    Ok(attachments)
+}
+
+/// Attach a file, without committing.  `parent_dir` is the directory
+/// of the issue or comment, `db_dir` is the top of the issues database.
+pub(crate) fn add_attachment(
+    parent_dir: &std::path::Path,
+    db_dir: &std::path::Path,
+    file: &std::path::Path,
+    name: &str,
+    use_blobs: bool,
+) -> Result<Attachment, AttachmentError> {
+    validate_name(name)?;
+    let mut attachment_filename = std::path::PathBuf::from(parent_dir);
+    attachment_filename.push("attachments");
+    std::fs::create_dir_all(&attachment_filename)?;
+    attachment_filename.push(name);
+    if attachment_filename.exists() {
+        return Err(AttachmentError::Exists(String::from(name)));
+    }
+
+    let size = file.metadata()?.len();
+    let Some(hash) = crate::git::hash_objects(db_dir, &[file], false)?
+        .into_iter()
+        .next()
+    else {
+        return Err(AttachmentError::NotFound(String::from(name)));
+    };
+
+    if use_blobs {
+        let blob_filename = blob_filename(db_dir, &hash);
+        if !blob_filename.exists() {
+            if let Some(blobs_dir) = blob_filename.parent() {
+                std::fs::create_dir_all(blobs_dir)?;
+            }
+            std::fs::copy(file, &blob_filename)?;
+        }
+        std::fs::write(
+            &attachment_filename,
+            format!("{POINTER_PREFIX}{hash} {size}\n"),
+        )?;
+    } else {
+        std::fs::copy(file, &attachment_filename)?;
+        let mut id_filename = std::path::PathBuf::from(parent_dir);
+        id_filename.push(IDS_DIR);
+        std::fs::create_dir_all(&id_filename)?;
+        id_filename.push(name);
+        std::fs::write(&id_filename, format!("{hash}\n"))?;
+    }
+
+    Ok(Attachment {
+        name: String::from(name),
+        size,
+        hash,
+        blob: use_blobs,
+    })
+}
+
+/// Read the contents of an attachment.
+pub(crate) fn read_contents(
+    parent_dir: &std::path::Path,
+    db_dir: &std::path::Path,
+    attachment: &Attachment,
+) -> Result<Vec<u8>, AttachmentError> {
+    let path = if attachment.blob {
+        blob_filename(db_dir, &attachment.hash)
+    } else {
+        let mut path = std::path::PathBuf::from(parent_dir);
+        path.push("attachments");
+        path.push(&attachment.name);
+        path
+    };
+    match std::fs::read(&path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(AttachmentError::NotFound(attachment.name.clone()))
+        }
+        Err(e) => Err(e.into()),
+        Ok(contents) => Ok(contents),
+    }
+}
+
+fn blob_filename(db_dir: &std::path::Path, hash: &str) -> std::path::PathBuf {
+    let mut blob_filename = std::path::PathBuf::from(db_dir);
+    blob_filename.push("blobs");
+    blob_filename.push(hash);
+    blob_filename
+}
+
+/// Read the recorded git object id of an attachment, None if there's
+/// none.
+fn read_id(path: &std::path::Path) -> Result<Option<String>, AttachmentError> {
+    match std::fs::read_to_string(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+        Ok(id) => Ok(Some(String::from(id.trim()))),
+    }
+}
+
+/// If the file is a blob pointer, return the hash and size it points to.
+fn read_pointer(
+    path: &std::path::Path,
+    name: &str,
+) -> Result<Option<(String, u64)>, AttachmentError> {
+    // Pointers are short, don't read big attachments to find out.
+    if path.metadata()?.len() > 128 {
+        return Ok(None);
+    }
+    let contents = std::fs::read(path)?;
+    let Some(pointer) = std::str::from_utf8(&contents)
+        .ok()
+        .and_then(|contents| contents.strip_prefix(POINTER_PREFIX))
+    else {
+        return Ok(None);
+    };
+    let mut tokens = pointer.split_whitespace();
+    match (
+        tokens.next(),
+        tokens.next().and_then(|size| size.parse::<u64>().ok()),
+    ) {
+        (Some(hash), Some(size)) => Ok(Some((String::from(hash), size))),
+        _ => Err(AttachmentError::InvalidPointer(String::from(name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attachment_names() {
+        assert!(validate_name("scope capture 1.png").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("..").is_err());
+        assert!(validate_name("logs/boot.log").is_err());
+    }
+}
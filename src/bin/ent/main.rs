@@ -7,10 +7,25 @@ use simple_logger;
 #[derive(Debug, clap::Parser)]
 #[command(version, about, long_about = None)]
 struct Args {
+    /// Run as if ent was started in PATH instead of the current
+    /// directory.
+    #[arg(short = 'C', value_name = "PATH", env = "ENT_REPO")]
+    repo: Option<std::path::PathBuf>,
+
     /// Branch containing issues.
     #[arg(short = 'b', long, default_value_t = String::from("entomologist-data"))]
     issues_branch: String,
 
+    /// How many seconds to wait for another ent that's changing the
+    /// issues before giving up.
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        env = "ENT_LOCK_TIMEOUT",
+        default_value_t = 30
+    )]
+    lock_timeout: u64,
+
     /// Type of behavior/output.
     #[command(subcommand)]
     command: Commands,
@@ -38,6 +53,11 @@ enum Commands {
         /// that are tagged "bug" and not tagged "docs".  Defaults to
         /// including all tags and excluding none.
         ///
+        /// "field.NAME": Comma-separated list of values of the custom
+        /// field NAME to include in the list.  The empty string includes
+        /// issues that don't have the field set.  Example:
+        /// "field.component=motor,power".
+        ///
         /// "done-time": Time range of issue completion, in the form
         /// "[START]..[END]".  Includes issues that were marked Done
         /// between START and END.  START and END are both in RFC 3339
@@ -45,36 +65,205 @@ enum Commands {
         /// is omitted, defaults to the beginning of time.  If END is
         /// omitted, defaults to the end of time.
         filter: Vec<String>,
+
+        /// Show issues as a tree, with child issues indented below
+        /// their parent.
+        #[arg(long)]
+        tree: bool,
     },
 
     /// Create a new issue.
-    New { description: Option<String> },
+    New {
+        description: Option<String>,
+
+        /// Name of the template to create the issue from.  Templates
+        /// are stored as `templates/NAME.toml` in the issues branch.
+        #[arg(long)]
+        template: Option<String>,
+
+        /// Edit the metadata of the new issue (state, assignee, tags,
+        /// dependencies, etc) together with its description.
+        #[arg(long, conflicts_with = "description")]
+        full: bool,
+    },
 
     /// Edit the description of an Issue or a Comment.
-    Edit { uuid: String },
+    Edit {
+        uuid: String,
+
+        /// Edit all the metadata of the issue (state, assignee, tags,
+        /// dependencies, etc) together with its description, as a TOML
+        /// front-matter block at the top of the description.
+        #[arg(long)]
+        full: bool,
+    },
+
+    /// Delete an issue or a comment.
+    Rm {
+        uuid: String,
+
+        /// Also rewrite the history of the issues branch, so that the
+        /// deleted issue or comment is gone from every commit.
+        #[arg(long)]
+        purge_history: bool,
+    },
+
+    /// Replace the description of an issue or a comment with
+    /// "[redacted]", keeping its metadata.
+    Redact {
+        uuid: String,
+
+        /// Also rewrite the history of the issues branch, so that the
+        /// original description is gone from every commit.
+        #[arg(long)]
+        purge_history: bool,
+    },
 
     /// Show the full description of an issue.
     Show { issue_id: String },
 
-    /// Modify the state of an issue (New, Backlog, Blocked, InProgress, Done, WontDo).
+    /// Modify the state of an issue (New, Backlog, Blocked, InProgress,
+    /// Done, WontDo, or the states declared in the workflow of the
+    /// issue database's config.toml).
     State {
         issue_id: String,
-        new_state: Option<State>,
+        new_state: Option<String>,
     },
 
     /// Create a new comment on an issue.
     Comment {
         issue_id: String,
         description: Option<String>,
+
+        /// Reply to the comment with this ID.
+        #[arg(long)]
+        reply: Option<String>,
+    },
+
+    /// Move a comment to another issue.
+    MvComment {
+        comment_id: String,
+        target_issue_id: String,
+    },
+
+    /// Merge one issue into another: move its comments, tags and
+    /// dependencies to the other issue, and mark it as a duplicate
+    /// of the other issue.
+    MergeIssues {
+        from_issue_id: String,
+        into_issue_id: String,
+    },
+
+    /// Create a new issue from a comment.  The new issue links to the
+    /// issue the comment is on.
+    Split { comment_id: String },
+
+    /// Add a reaction to a comment, for example "+1".
+    React {
+        comment_id: String,
+        reaction: String,
+
+        /// Remove the reaction instead of adding it.
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Attach a file to an issue or a comment.  The file is committed to
+    /// the issues branch, and stays in its history (and in every clone
+    /// that syncs it) even if the issue is removed, unless the history
+    /// is purged with `ent rm --purge-history`.
+    Attach {
+        /// ID of the issue or comment.
+        uuid: String,
+        file: std::path::PathBuf,
+
+        /// Name of the attachment, defaults to the name of the file.
+        #[arg(long)]
+        name: Option<String>,
+    },
+
+    /// List the attachments of an issue and its comments.
+    Attachments { issue_id: String },
+
+    /// Write the contents of an attachment to a file.
+    Extract {
+        /// ID of the issue or comment.
+        uuid: String,
+        name: String,
+
+        /// File to write, defaults to the name of the attachment in the
+        /// current directory.  "-" writes to stdout.
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
     },
 
     /// Sync entomologist data with remote.  This fetches from the remote,
     /// merges the remote entomologist data branch with the local one,
     /// and pushes the result back to the remote.
+    ///
+    /// The issues branch on a remote can have a different name, set
+    /// with `git config remote.REMOTE.entomologistBranch NAME`.
     Sync {
-        /// Name of the git remote to sync with.
-        #[arg(default_value_t = String::from("origin"))]
-        remote: String,
+        /// Name of the git remote to sync with.  Defaults to the remotes
+        /// in the `entomologist.remote` git config (set with `git
+        /// config --add entomologist.remote REMOTE`), or origin.
+        #[arg(conflicts_with = "all")]
+        remote: Option<String>,
+
+        /// Sync with all the remotes, one after the other.
+        #[arg(long)]
+        all: bool,
+
+        /// Show the changes that would be fetched and pushed, without
+        /// changing anything.
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Fetch and merge, but don't push the result, so the merge can
+        /// be reviewed locally first.
+        #[arg(long)]
+        no_push: bool,
+    },
+
+    /// Show how far the local issue branch is ahead of and behind each
+    /// remote, as of the last fetch from each.
+    Status,
+
+    /// Run an ent command on a proposal instead of on the issues
+    /// branch.  A proposal is a branch of changes to the issues for
+    /// someone with write access to review and accept, like a pull
+    /// request.  The proposal NAME is the branch
+    /// `ISSUES_BRANCH-proposals/NAME`, made from the issues branch the
+    /// first time it's used.
+    ///
+    /// For example `ent propose motor-stall new` files a new issue in
+    /// the proposal "motor-stall", and `ent propose motor-stall sync
+    /// my-fork` pushes the proposal to the remote "my-fork".
+    Propose {
+        name: String,
+
+        /// The ent command to run on the proposal, like `new` or
+        /// `comment ISSUE_ID`.  Without a command the proposal is just
+        /// made.
+        #[arg(
+            trailing_var_arg = true,
+            allow_hyphen_values = true,
+            value_name = "COMMAND"
+        )]
+        command: Vec<String>,
+    },
+
+    /// List the proposals to the issues branch that aren't accepted
+    /// yet, local ones and ones fetched from remotes, with the changes
+    /// to the issues in each.
+    Proposals,
+
+    /// Merge a proposal into the issues branch.  Run `ent sync`
+    /// afterwards to publish it.
+    Accept {
+        /// The proposal branch as listed by `ent proposals`, or the
+        /// name of a local proposal.
+        proposal: String,
     },
 
     /// Get or set the Assignee field of an Issue.
@@ -103,17 +292,537 @@ enum Commands {
         issue_id: String,
         dependency_id: Option<String>,
     },
+
+    /// Add a typed link from one issue to another, or list the links of
+    /// an issue.  Link types are duplicate-of, relates-to, supersedes,
+    /// and caused-by.
+    Link {
+        issue_id: String,
+        link_type: Option<entomologist::issue::LinkType>,
+        #[arg(requires = "link_type")]
+        target_id: Option<String>,
+    },
+
+    /// Remove a typed link from one issue to another.
+    Unlink {
+        issue_id: String,
+        link_type: entomologist::issue::LinkType,
+        target_id: String,
+    },
+
+    /// Mark an issue as a duplicate of another issue.  This links the
+    /// duplicate to the original, adds a comment, and changes the state
+    /// of the duplicate to WontDo.
+    Dup {
+        duplicate_id: String,
+        original_id: String,
+    },
+
+    /// Set custom fields of an issue, or list them.  Custom fields are
+    /// declared in the issue database's config.toml.
+    Set {
+        issue_id: String,
+
+        /// Fields to set, in the form "NAME=VALUE".  An empty VALUE
+        /// removes the field from the issue.
+        fields: Vec<String>,
+    },
+
+    /// Get, set, or clear the parent of an issue.
+    Parent {
+        issue_id: String,
+        parent_id: Option<String>,
+
+        /// Remove the parent, making the issue a top-level issue.
+        #[arg(long, conflicts_with = "parent_id")]
+        clear: bool,
+    },
+
+    /// Get, set, or clear the estimate of an issue, for example "1h30m".
+    Estimate {
+        issue_id: String,
+        estimate: Option<String>,
+
+        /// Remove the estimate.
+        #[arg(long, conflicts_with = "estimate")]
+        clear: bool,
+    },
+
+    /// Start a timer on an issue.  Timers are kept in this clone of the
+    /// repo until they're stopped.
+    Start { issue_id: String },
+
+    /// Stop the timer on an issue and log the time in the issue's
+    /// worklog.  The issue can be omitted if only one timer is running.
+    Stop {
+        issue_id: Option<String>,
+
+        /// A note about the work done.
+        #[arg(long)]
+        note: Option<String>,
+    },
+
+    /// Report on the issues.
+    Report {
+        #[command(subcommand)]
+        report: ReportCommands,
+    },
+
+    /// Write Markdown release notes listing the issues completed in a
+    /// time window (by their done-time).  SINCE and UNTIL can be dates
+    /// (YYYY-MM-DD), times (RFC 3339), or git tags.
+    Changelog {
+        /// Start of the window, inclusive.
+        #[arg(long)]
+        since: String,
+
+        /// End of the window, exclusive.  Defaults to now.
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Group the issues by tag (using the changelog sections in
+        /// config.toml) or by milestone.
+        #[arg(long, default_value_t = String::from("tag"))]
+        by: String,
+    },
+
+    /// Manage milestones, and the milestones of issues.
+    Milestone {
+        #[command(subcommand)]
+        command: MilestoneCommands,
+    },
+
+    /// Remove the lock on the issues branch left behind by an ent that
+    /// crashed on another host.  Locks left by crashed ents on this
+    /// host are removed automatically.
+    Unlock,
+
+    /// Find and clean up what crashed or killed ents left behind:
+    /// temporary worktrees, and locks on the issues branch.
+    Doctor,
+
+    /// Work with the issues of all the repos listed in a workspace
+    /// file.  Issues are shown as REPO:ID.
+    Multi {
+        /// The workspace file, a TOML file listing the repos as
+        /// `[[repos]]` tables with `name`, `path`, and optionally
+        /// `branch`.
+        #[arg(
+            short = 'w',
+            long,
+            env = "ENT_WORKSPACE",
+            default_value = "entomologist-workspace.toml"
+        )]
+        workspace: std::path::PathBuf,
+
+        #[command(subcommand)]
+        command: MultiCommands,
+    },
+}
+
+/// The COMMAND of `ent propose NAME COMMAND`.
+#[derive(Debug, clap::Parser)]
+struct ProposeArgs {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum MultiCommands {
+    /// List the issues of all the repos in the workspace.
+    List {
+        /// Filter strings, same as for `ent list`.  The default states
+        /// are the open states of each repo's workflow.
+        filter: Vec<String>,
+
+        /// Print the issues as a JSON array.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum MilestoneCommands {
+    /// Create a new milestone.
+    New {
+        /// Name of the milestone, for example "v1.2".
+        name: String,
+
+        description: Option<String>,
+
+        /// Target date of the milestone (YYYY-MM-DD).
+        #[arg(long)]
+        target: Option<String>,
+    },
+
+    /// List the milestones and their progress.
+    List,
+
+    /// Show a milestone: its progress, open blockers, and issues.
+    Show { name: String },
+
+    /// Close a milestone.
+    Close {
+        name: String,
+
+        /// Reopen the milestone instead of closing it.
+        #[arg(long)]
+        reopen: bool,
+    },
+
+    /// Get, set, or clear the milestone of an issue.
+    Set {
+        issue_id: String,
+        milestone: Option<String>,
+
+        /// Remove the issue from its milestone.
+        #[arg(long, conflicts_with = "milestone")]
+        clear: bool,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ReportCommands {
+    /// Summarize the time logged in issue worklogs.
+    Time {
+        /// Start of the report: today, yesterday, last-week,
+        /// last-month, or a date (YYYY-MM-DD).
+        #[arg(long, default_value_t = String::from("last-week"))]
+        since: String,
+
+        /// Group the time by assignee, user (who logged it), issue,
+        /// or tag.
+        #[arg(long, default_value_t = String::from("assignee"))]
+        by: String,
+    },
+}
+
+/// Apply the issue database's automatic Blocked policy (if enabled)
+/// after a change to issue states or dependencies, and report the
+/// resulting state changes.
+fn update_blocked_states(issues: &mut entomologist::IssuesMut) -> anyhow::Result<()> {
+    for (issue_id, old_state, new_state) in issues.update_blocked_states()? {
+        println!("issue {issue_id}: {old_state} -> {new_state} (automatic)");
+    }
+    Ok(())
+}
+
+/// Pick an emoji showing whether an issue is resolved or not.
+fn state_emoji(issues: &entomologist::Issues, issue: Option<&entomologist::Issue>) -> &'static str {
+    match issue {
+        None => "☠️ ",
+        Some(issue) => {
+            if !issues.config().workflow.is_closed(&issue.state) {
+                "⌛"
+            } else if issue.state == entomologist::issue::State::WontDo {
+                "❌"
+            } else {
+                "✅"
+            }
+        }
+    }
+}
+
+/// Format the one-line summary of an issue used by `ent list`.
+fn format_issue_line(issues: &entomologist::Issues, uuid: &str) -> String {
+    let issue = issues.get_issue(uuid).unwrap();
+    let comments = match issue.comments.len() {
+        0 => String::from("   "),
+        n => format!("🗨️ {n}"),
+    };
+    let blocking_dependencies = match issues.blocking_dependencies(uuid).len() {
+        0 => String::from("   "),
+        count => format!("⌛{count}"),
+    };
+    let progress = match issues.progress(uuid) {
+        Some((closed, total)) => format!(" ({closed}/{total} done)"),
+        None => String::from(""),
+    };
+    let assignee = match &issue.assignee {
+        Some(assignee) => format!(" (👉 {assignee})"),
+        None => String::from(""),
+    };
+    let tags = match &issue.tags.len() {
+        0 => String::from(""),
+        _ => {
+            // Could use `format!(" {:?}", issue.tags)`
+            // here, but that results in `["tag1", "TAG2",
+            // "i-am-also-a-tag"]` and i don't want the
+            // double-quotes around each tag.
+            let mut tags = String::from(" [");
+            let mut separator = "";
+            for tag in &issue.tags {
+                tags.push_str(separator);
+                tags.push_str(tag);
+                separator = ", ";
+            }
+            tags.push(']');
+            tags
+        }
+    };
+    format!(
+        "{}  {} {}  {}{}{}{}",
+        uuid,
+        comments,
+        blocking_dependencies,
+        issue.title(),
+        progress,
+        assignee,
+        tags
+    )
+}
+
+/// Print an issue and (recursively) its children, for `ent list --tree`.
+/// Only issues in `uuids_by_state` (the issues that passed the filter)
+/// are printed.
+fn print_issue_tree(
+    issues: &entomologist::Issues,
+    uuids_by_state: &std::collections::HashMap<
+        entomologist::issue::State,
+        Vec<&entomologist::issue::IssueHandle>,
+    >,
+    uuid: &str,
+    depth: usize,
+) {
+    let issue = issues.get_issue(uuid).unwrap();
+    println!(
+        "{}{:<10}  {}",
+        "    ".repeat(depth),
+        issues.effective_state(issue).to_string(),
+        format_issue_line(issues, uuid)
+    );
+    for child in issues.children(uuid) {
+        if uuids_by_state
+            .values()
+            .any(|these_uuids| these_uuids.contains(&&child.id))
+        {
+            print_issue_tree(issues, uuids_by_state, &child.id, depth + 1);
+        }
+    }
+}
+
+fn print_reactions(comment: &entomologist::comment::Comment) {
+    if comment.reactions.is_empty() {
+        return;
+    }
+    let reactions: Vec<String> = comment
+        .reactions
+        .iter()
+        .map(|(reaction, users)| format!("{reaction} ×{}", users.len()))
+        .collect();
+    println!("reactions: {}", reactions.join(", "));
+}
+
+fn print_attachment(attachment: &entomologist::attachment::Attachment, indent: &str) {
+    println!(
+        "{indent}attachment: {} ({} bytes, {})",
+        attachment.name, attachment.size, attachment.hash
+    );
+}
+
+/// Print a comment followed by its replies, indenting replies below
+/// the comment they reply to.
+fn print_comment_thread(
+    issue: &entomologist::Issue,
+    comment: &entomologist::comment::Comment,
+    depth: usize,
+) {
+    let indent = "    ".repeat(depth);
+    println!();
+    println!("{indent}comment: {}", comment.uuid);
+    if let Some(in_reply_to) = &comment.in_reply_to {
+        println!("{indent}in reply to: {in_reply_to}");
+    }
+    println!("{indent}author: {}", comment.author);
+    println!("{indent}creation_time: {}", comment.creation_time);
+    if !comment.reactions.is_empty() {
+        print!("{indent}");
+        print_reactions(comment);
+    }
+    for attachment in &comment.attachments {
+        print_attachment(attachment, &indent);
+    }
+    println!();
+    for line in comment.description.lines() {
+        println!("{indent}{line}");
+    }
+    for reply in issue.replies(Some(&comment.uuid)) {
+        print_comment_thread(issue, reply, depth + 1);
+    }
+}
+
+/// After deleting or redacting, either warn that the original content
+/// is still in the git history, or purge it from the history.  `path`
+/// is the deleted directory or the redacted file, relative to the top
+/// of the issues branch.
+fn purge_or_warn(
+    repo: &entomologist::Repo,
+    issues: &entomologist::IssuesMut,
+    git_ref: &str,
+    purge_history: bool,
+    path: &str,
+    replacement: Option<&str>,
+) -> anyhow::Result<()> {
+    if !purge_history {
+        eprintln!(
+            "warning: the original content is still in the git history of the {git_ref:?} branch,"
+        );
+        eprintln!("and in every clone and remote that has synced it.  Use --purge-history to");
+        eprintln!("rewrite the history of the branch in this repo.");
+        return Ok(());
+    }
+    entomologist::git::purge_from_history(repo, &issues.path(), git_ref, path, replacement)?;
+    eprintln!("rewrote the history of the {git_ref:?} branch in this repo.");
+    eprintln!("The original content stays in the repo's object database until it's");
+    eprintln!("garbage-collected, run `git gc --prune=now` to remove it now.");
+    eprintln!("warning: remotes and other clones still have the original content.  Force-push");
+    eprintln!("the branch with `git push --force REMOTE {git_ref}` and have everyone reset");
+    eprintln!("their copy of the branch to it, otherwise `ent sync` will merge the original");
+    eprintln!("history back in.");
+    Ok(())
+}
+
+/// Print a summary of changes to the issues under a heading, or nothing
+/// if there are no changes.
+fn print_changes(heading: &str, changes: &entomologist::changes::Changes) {
+    if changes.is_empty() {
+        return;
+    }
+    println!("{heading}:");
+    for issue in &changes.issues {
+        let status = match issue.status {
+            entomologist::changes::IssueStatus::Created => " (new)",
+            entomologist::changes::IssueStatus::Modified => "",
+            entomologist::changes::IssueStatus::Deleted => " (deleted)",
+        };
+        println!("{}  {}{status}", issue.id, issue.title);
+        for change in &issue.changes {
+            println!("    {change}");
+        }
+    }
+    for path in &changes.other {
+        println!("{path} changed");
+    }
+    println!();
+}
+
+/// Sync with each of the remotes in turn, and sum up what moved where.
+/// Changes fetched from one remote are pushed to the remotes synced
+/// before it in a second round, so all the remotes end up the same.
+fn handle_sync(
+    repo: &entomologist::Repo,
+    git_ref: &str,
+    remotes: &[String],
+    dry_run: bool,
+    no_push: bool,
+) -> anyhow::Result<()> {
+    let count =
+        |changes: &entomologist::changes::Changes| changes.issues.len() + changes.other.len();
+    let mut results = Vec::<(&str, anyhow::Result<(usize, usize)>)>::new();
+
+    if dry_run {
+        for remote in remotes {
+            let result = entomologist::git::sync_preview(repo, remote, git_ref);
+            if let Ok(summary) = &result {
+                print_changes(
+                    &format!("Changes to fetch from remote {remote}"),
+                    &summary.incoming,
+                );
+                print_changes(
+                    &format!("Changes to push to remote {remote}"),
+                    &summary.outgoing,
+                );
+            }
+            results.push((
+                remote,
+                result
+                    .map(|summary| (count(&summary.incoming), count(&summary.outgoing)))
+                    .map_err(|e| e.into()),
+            ));
+        }
+    } else {
+        let issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+        let options = entomologist::git::SyncOptions { push: !no_push };
+        let mut last_to_bring_changes = None;
+        for (i, remote) in remotes.iter().enumerate() {
+            let result =
+                entomologist::git::sync_with_options(&issues.path(), remote, git_ref, &options);
+            if let Ok(summary) = &result {
+                print_changes(
+                    &format!("Changes fetched from remote {remote}"),
+                    &summary.incoming,
+                );
+                let pushed_heading = match no_push {
+                    true => String::from("Local changes, not pushed"),
+                    false => format!("Changes pushed to remote {remote}"),
+                };
+                print_changes(&pushed_heading, &summary.outgoing);
+                if !summary.incoming.is_empty() {
+                    last_to_bring_changes = Some(i);
+                }
+            }
+            results.push((
+                remote,
+                result
+                    .map(|summary| (count(&summary.incoming), count(&summary.outgoing)))
+                    .map_err(|e| e.into()),
+            ));
+        }
+
+        if !no_push && let Some(last) = last_to_bring_changes {
+            for (remote, result) in results[..last].iter_mut() {
+                let Ok((_, pushed)) = result else {
+                    continue;
+                };
+                let summary = entomologist::git::sync(&issues.path(), remote, git_ref)?;
+                print_changes(
+                    &format!("Changes pushed to remote {remote}"),
+                    &summary.outgoing,
+                );
+                *pushed += count(&summary.outgoing);
+            }
+        }
+    }
+
+    let (fetched, pushed) = match (dry_run, no_push) {
+        (true, _) => ("to fetch", "to push"),
+        (false, true) => ("fetched", "not pushed"),
+        (false, false) => ("fetched", "pushed"),
+    };
+    let mut failed = 0;
+    for (remote, result) in &results {
+        match result {
+            Ok((incoming, outgoing)) => {
+                println!("{remote}: {incoming} {fetched}, {outgoing} {pushed}")
+            }
+            Err(e) => {
+                failed += 1;
+                println!("{remote}: failed: {e}");
+            }
+        }
+    }
+    if dry_run {
+        println!("dry run, nothing changed");
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "failed to sync {git_ref:?} with {failed} of {} remotes",
+            results.len()
+        ));
+    }
+    Ok(())
 }
 
 fn handle_command(
-    args: &Args,
+    command: &Commands,
+    repo: &entomologist::Repo,
     git_ref: &str,
 ) -> anyhow::Result<()> {
-    match &args.command {
-        Commands::List { filter } => {
-            let issues = entomologist::Issues::new_from_git(git_ref)?;
+    match command {
+        Commands::List { filter, tree } => {
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
             let filter = {
-                let mut f = entomologist::Filter::new();
+                let mut f = entomologist::Filter::new_for_workflow(&issues.config().workflow);
                 for filter_str in filter {
                     f.parse(filter_str)?;
                 }
@@ -125,55 +834,48 @@ fn handle_command(
                 Vec<&entomologist::issue::IssueHandle>,
             >::new();
             for (uuid, issue) in issues.iter() {
-                if !filter.include_states.contains(&issue.state) {
+                let state = issues.effective_state(issue);
+                if !filter.matches(issue, &state) {
                     continue;
                 }
-                if !filter.include_assignees.is_empty() {
-                    let assignee = match &issue.assignee {
-                        Some(assignee) => assignee,
-                        None => "",
-                    };
-                    if !filter.include_assignees.contains(assignee) {
-                        continue;
-                    }
-                }
+                uuids_by_state.entry(state).or_default().push(uuid);
+            }
 
-                if !filter.include_tags.is_empty() && !issue.has_any_tag(&filter.include_tags) {
-                    continue;
-                }
-                if !filter.exclude_tags.is_empty() && issue.has_any_tag(&filter.exclude_tags) {
-                    continue;
+            if *tree {
+                // Issues whose parent is not in the list are the roots
+                // of the trees.
+                let mut roots: Vec<&entomologist::issue::IssueHandle> = uuids_by_state
+                    .values()
+                    .flatten()
+                    .copied()
+                    .filter(|uuid| {
+                        let issue = issues.get_issue(uuid).unwrap();
+                        match &issue.parent {
+                            None => true,
+                            Some(parent) => !uuids_by_state
+                                .values()
+                                .any(|these_uuids| these_uuids.contains(&parent)),
+                        }
+                    })
+                    .collect();
+                roots.sort_by_key(|uuid| issues.get_issue(uuid).unwrap().creation_time);
+                for uuid in roots {
+                    print_issue_tree(&issues, &uuids_by_state, uuid, 0);
                 }
+                return Ok(());
+            }
 
-                if let Some(issue_done_time) = issue.done_time {
-                    if let Some(start_done_time) = filter.start_done_time
-                        && start_done_time > issue_done_time
-                    {
-                        continue;
-                    }
-                    if let Some(end_done_time) = filter.end_done_time
-                        && end_done_time < issue_done_time
-                    {
-                        continue;
-                    }
+            // Show the states in workflow order, followed by any states
+            // the workflow doesn't know about (so issues in those states
+            // don't silently disappear).
+            let workflow = &issues.config().workflow;
+            let mut states: Vec<State> = workflow.states().cloned().collect();
+            for state in uuids_by_state.keys() {
+                if !workflow.has_state(state) {
+                    states.push(state.clone());
                 }
-
-                // This issue passed all the filters, include it in list.
-                uuids_by_state
-                    .entry(issue.state.clone())
-                    .or_default()
-                    .push(uuid);
             }
-
-            use entomologist::issue::State;
-            for state in [
-                State::InProgress,
-                State::Blocked,
-                State::Backlog,
-                State::New,
-                State::Done,
-                State::WontDo,
-            ] {
+            for state in states {
                 let these_uuids = uuids_by_state.entry(state.clone()).or_default();
                 if these_uuids.is_empty() {
                     continue;
@@ -183,71 +885,41 @@ fn handle_command(
                     let b = issues.get_issue(b_id).unwrap();
                     a.creation_time.cmp(&b.creation_time)
                 });
-                println!("{state:?}:");
+                println!("{}:", workflow.label(&state));
                 for uuid in these_uuids {
-                    let issue = issues.get_issue(uuid).unwrap();
-                    let comments = match issue.comments.len() {
-                        0 => String::from("   "),
-                        n => format!("🗨️ {n}"),
-                    };
-                    let blocking_dependencies = match &issue.dependencies {
-                        None => String::from("   "),
-                        Some(dependencies) => {
-                            let mut count: usize = 0;
-                            for dep_id in dependencies {
-                                if let Some(d) = issues.get_issue(dep_id)
-                                    && d.state != entomologist::issue::State::Done
-                                    && d.state != entomologist::issue::State::WontDo
-                                {
-                                    count += 1;
-                                }
-                            }
-                            match count {
-                                0 => String::from("   "),
-                                _ => format!("⌛{count}"),
-                            }
-                        }
-                    };
-                    let assignee = match &issue.assignee {
-                        Some(assignee) => format!(" (👉 {assignee})"),
-                        None => String::from(""),
-                    };
-                    let tags = match &issue.tags.len() {
-                        0 => String::from(""),
-                        _ => {
-                            // Could use `format!(" {:?}", issue.tags)`
-                            // here, but that results in `["tag1", "TAG2",
-                            // "i-am-also-a-tag"]` and i don't want the
-                            // double-quotes around each tag.
-                            let mut tags = String::from(" [");
-                            let mut separator = "";
-                            for tag in &issue.tags {
-                                tags.push_str(separator);
-                                tags.push_str(tag);
-                                separator = ", ";
-                            }
-                            tags.push(']');
-                            tags
-                        }
-                    };
-                    println!(
-                        "{}  {} {}  {}{}{}",
-                        uuid,
-                        comments,
-                        blocking_dependencies,
-                        issue.title(),
-                        assignee,
-                        tags
-                    );
+                    println!("{}", format_issue_line(&issues, uuid));
                 }
                 println!();
             }
         }
 
-        Commands::New { description } => {
-            let issues = entomologist::IssuesMut::new_from_git(git_ref)?;
-            match entomologist::issue::Issue::new(&issues.path(), description) {
-                Err(entomologist::issue::IssueError::EmptyDescription) => {
+        Commands::New {
+            description,
+            template,
+            full: true,
+        } => {
+            assert!(description.is_none());
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            match issues.new_issue_full(template.as_deref())? {
+                None => println!("no new issue created"),
+                Some(issue) => {
+                    println!("created new issue '{}'", issue.title());
+                    println!("ID: {}", issue.id);
+                }
+            }
+            update_blocked_states(&mut issues)?;
+        }
+
+        Commands::New {
+            description,
+            template,
+            full: false,
+        } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            match issues.new_issue(description, template.as_deref()) {
+                Err(entomologist::issues_mut::Error::Issue(
+                    entomologist::issue::IssueError::EmptyDescription,
+                )) => {
                     println!("no new issue created");
                     return Ok(());
                 }
@@ -257,13 +929,22 @@ fn handle_command(
                 Ok(issue) => {
                     println!("created new issue '{}'", issue.title());
                     println!("ID: {}", issue.id);
-                    return Ok(());
                 }
             }
+            update_blocked_states(&mut issues)?;
+        }
+
+        Commands::Edit { uuid, full: true } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            if issues.edit_issue_full(uuid)? {
+                update_blocked_states(&mut issues)?;
+            } else {
+                println!("aborted issue edit");
+            }
         }
 
-        Commands::Edit { uuid } => {
-            let mut issues = entomologist::IssuesMut::new_from_git(git_ref)?;
+        Commands::Edit { uuid, full: false } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
             if let Some(issue) = issues.get_issue_mut(uuid) {
                 match issue.edit_description() {
                     Err(entomologist::issue::IssueError::EmptyDescription) => {
@@ -295,8 +976,89 @@ fn handle_command(
             ));
         }
 
+        Commands::Rm {
+            uuid,
+            purge_history,
+        } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let path = if issues.get_issue(uuid).is_some() {
+                for dependent in issues.dependents(uuid) {
+                    eprintln!(
+                        "warning: issue {} depends on removed issue {uuid}",
+                        dependent.id
+                    );
+                }
+                for child in issues.children(uuid) {
+                    eprintln!(
+                        "warning: issue {} is a child of removed issue {uuid}",
+                        child.id
+                    );
+                }
+                for (link_type, linking_id) in issues.links_to(uuid) {
+                    eprintln!("warning: issue {linking_id} {link_type} removed issue {uuid}");
+                }
+                issues.remove_issue(uuid)?;
+                println!("removed issue {uuid}");
+                uuid.clone()
+            } else {
+                let Some(issue) = issues.issue_of_comment(uuid) else {
+                    return Err(anyhow::anyhow!(
+                        "no issue or comment with uuid {} found",
+                        uuid
+                    ));
+                };
+                let issue_id = issue.id.clone();
+                let Some(issue) = issues.get_issue_mut(&issue_id) else {
+                    return Err(anyhow::anyhow!("issue {} not found", issue_id));
+                };
+                issue.remove_comment(uuid)?;
+                println!("removed comment {uuid} from issue {issue_id}");
+                // The comment may have been in other issues before, see
+                // `ent mv-comment`.
+                format!("*/comments/{uuid}")
+            };
+            purge_or_warn(repo, &issues, git_ref, *purge_history, &path, None)?;
+        }
+
+        Commands::Redact {
+            uuid,
+            purge_history,
+        } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let path = if let Some(issue) = issues.get_issue_mut(uuid) {
+                issue.redact()?;
+                println!("redacted issue {uuid}");
+                format!("{uuid}/description")
+            } else {
+                let Some(issue) = issues.issue_of_comment(uuid) else {
+                    return Err(anyhow::anyhow!(
+                        "no issue or comment with uuid {} found",
+                        uuid
+                    ));
+                };
+                let issue_id = issue.id.clone();
+                let Some(comment) = issues
+                    .get_issue_mut(&issue_id)
+                    .and_then(|issue| issue.comments.iter_mut().find(|c| c.uuid == *uuid))
+                else {
+                    return Err(anyhow::anyhow!("comment {} not found", uuid));
+                };
+                comment.redact()?;
+                println!("redacted comment {uuid} on issue {issue_id}");
+                format!("*/comments/{uuid}/description")
+            };
+            purge_or_warn(
+                repo,
+                &issues,
+                git_ref,
+                *purge_history,
+                &path,
+                Some(entomologist::issue::REDACTED_DESCRIPTION),
+            )?;
+        }
+
         Commands::Show { issue_id } => {
-            let issues = entomologist::Issues::new_from_git(git_ref)?;
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
             let Some(issue) = issues.get_issue(issue_id) else {
                 return Err(anyhow::anyhow!("issue {} not found", issue_id));
             };
@@ -315,39 +1077,98 @@ fn handle_command(
             if let Some(done_time) = &issue.done_time {
                 println!("done_time: {done_time}");
             }
-            println!("state: {:?}", issue.state);
+            let workflow = &issues.config().workflow;
+            let state = issues.effective_state(issue);
+            if state == issue.state {
+                println!("state: {}", workflow.label(&state));
+            } else {
+                println!(
+                    "state: {} (automatic, recorded state is {})",
+                    workflow.label(&state),
+                    workflow.label(&issue.state)
+                );
+            }
             if let Some(dependencies) = &issue.dependencies {
                 print!("dependencies: ");
                 let mut separator = "";
                 for dep_id in dependencies {
-                    let emoji = match issues.get_issue(dep_id) {
-                        None => "☠️ ",
-                        Some(d) => match d.state {
-                            entomologist::issue::State::New => "⌛",
-                            entomologist::issue::State::Backlog => "⌛",
-                            entomologist::issue::State::Blocked => "⌛",
-                            entomologist::issue::State::InProgress => "⌛",
-                            entomologist::issue::State::Done => "✅",
-                            entomologist::issue::State::WontDo => "❌",
-                        },
-                    };
+                    let emoji = state_emoji(&issues, issues.get_issue(dep_id));
                     print!("{separator}{emoji} {dep_id}");
                     separator = ", "
                 }
                 println!();
             }
+            let dependents = issues.dependents(issue_id);
+            if !dependents.is_empty() {
+                print!("blocks: ");
+                let mut separator = "";
+                for dependent in dependents {
+                    let emoji = state_emoji(&issues, Some(dependent));
+                    print!("{separator}{emoji} {}", dependent.id);
+                    separator = ", "
+                }
+                println!();
+            }
+            if let Some(parent) = &issue.parent {
+                println!("parent: {parent}");
+            }
+            if let Some(milestone) = &issue.milestone {
+                println!("milestone: {milestone}");
+            }
+            for link in &issue.links {
+                println!("{}: {}", link.link_type, link.target);
+            }
+            for (link_type, source) in issues.links_to(issue_id) {
+                println!("{}: {}", link_type.reverse_name(), source);
+            }
+            if let Some((closed, total)) = issues.progress(issue_id) {
+                println!("children: {closed}/{total} done");
+                for child in issues.children(issue_id) {
+                    println!(
+                        "    {:<10}  {}  {}",
+                        workflow.label(&issues.effective_state(child)),
+                        child.id,
+                        child.title()
+                    );
+                }
+            }
             if let Some(assignee) = &issue.assignee {
                 println!("assignee: {assignee}");
             }
-            println!();
-            println!("{}", issue.description);
-            for comment in &issue.comments {
-                println!();
-                println!("comment: {}", comment.uuid);
-                println!("author: {}", comment.author);
-                println!("creation_time: {}", comment.creation_time);
-                println!();
-                println!("{}", comment.description);
+            if let Some(estimate) = &issue.estimate {
+                println!(
+                    "estimate: {}",
+                    entomologist::worklog::format_duration(estimate)
+                );
+            }
+            if !issue.worklog.is_empty() {
+                println!(
+                    "logged: {}",
+                    entomologist::worklog::format_duration(&issue.logged_time())
+                );
+                for entry in &issue.worklog {
+                    print!(
+                        "    {}  {:>6}  {}",
+                        entry.start.format("%Y-%m-%d %H:%M"),
+                        entomologist::worklog::format_duration(&entry.duration),
+                        entry.user
+                    );
+                    match &entry.note {
+                        Some(note) => println!("  {note}"),
+                        None => println!(),
+                    }
+                }
+            }
+            for (name, value) in &issue.fields {
+                println!("{name}: {value}");
+            }
+            for attachment in &issue.attachments {
+                print_attachment(attachment, "");
+            }
+            println!();
+            println!("{}", issue.description);
+            for comment in issue.replies(None) {
+                print_comment_thread(issue, comment, 0);
             }
         }
 
@@ -356,25 +1177,42 @@ fn handle_command(
             new_state,
         } => match new_state {
             Some(new_state) => {
-                let mut issues = entomologist::IssuesMut::new_from_git(git_ref)?;
-                match issues.get_issue_mut(issue_id) {
-                    Some(issue) => {
-                        let current_state = issue.state.clone();
-                        issue.set_state(new_state.clone())?;
-                        println!("issue: {issue_id}");
-                        println!("state: {current_state} -> {new_state}");
-                    }
-                    None => {
-                        return Err(anyhow::anyhow!("issue {} not found", issue_id));
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+                let Some(issue) = issues.get_issue(issue_id) else {
+                    return Err(anyhow::anyhow!("issue {} not found", issue_id));
+                };
+                let current_state = issue.state.clone();
+                let new_state = State::parse(new_state, &issues.config().workflow)?;
+                // The dependents that are blocked now, to report the
+                // ones this state change (and the automatic state
+                // changes that follow it) unblocks.
+                let blocked: Vec<String> = issues
+                    .dependents(issue_id)
+                    .into_iter()
+                    .filter(|dependent| issues.is_blocked(dependent))
+                    .map(|dependent| dependent.id.clone())
+                    .collect();
+                issues.set_state(issue_id, new_state.clone())?;
+                println!("issue: {issue_id}");
+                println!("state: {current_state} -> {new_state}");
+                update_blocked_states(&mut issues)?;
+                let workflow = &issues.config().workflow;
+                if !workflow.is_closed(&current_state) && workflow.is_closed(&new_state) {
+                    for dependent_id in &blocked {
+                        if let Some(dependent) = issues.get_issue(dependent_id)
+                            && !issues.is_blocked(dependent)
+                        {
+                            println!("unblocked: {}  {}", dependent.id, dependent.title());
+                        }
                     }
                 }
             }
             None => {
-                let issues = entomologist::Issues::new_from_git(git_ref)?;
+                let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
                 match issues.get_issue(issue_id) {
                     Some(issue) => {
                         println!("issue: {issue_id}");
-                        println!("state: {}", issue.state);
+                        println!("state: {}", issues.effective_state(issue));
                     }
                     None => {
                         return Err(anyhow::anyhow!("issue {} not found", issue_id));
@@ -386,12 +1224,17 @@ fn handle_command(
         Commands::Comment {
             issue_id,
             description,
+            reply,
         } => {
-            let mut issues = entomologist::IssuesMut::new_from_git(git_ref)?;
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
             let Some(issue) = issues.get_issue_mut(issue_id) else {
                 return Err(anyhow::anyhow!("issue {} not found", issue_id));
             };
-            match issue.add_comment(description) {
+            let result = match reply {
+                Some(in_reply_to) => issue.add_reply(in_reply_to, description),
+                None => issue.add_comment(description),
+            };
+            match result {
                 Err(entomologist::issue::IssueError::CommentError(
                     entomologist::comment::CommentError::EmptyDescription,
                 )) => {
@@ -410,10 +1253,186 @@ fn handle_command(
             }
         }
 
-        Commands::Sync { remote } => {
-            let issues = entomologist::IssuesMut::new_from_git(git_ref)?;
-            entomologist::git::sync(&issues.path(), remote, git_ref)?;
-            println!("synced {git_ref:?} with {remote:?}");
+        Commands::MvComment {
+            comment_id,
+            target_issue_id,
+        } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            issues.move_comment(comment_id, target_issue_id)?;
+            println!("moved comment {comment_id} to issue {target_issue_id}");
+        }
+
+        Commands::MergeIssues {
+            from_issue_id,
+            into_issue_id,
+        } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let skipped = issues.merge_issues(from_issue_id, into_issue_id)?;
+            for dep in skipped {
+                eprintln!(
+                    "warning: not adding dependency {dep} to issue {into_issue_id}, it would create a cycle"
+                );
+            }
+            println!("merged issue {from_issue_id} into issue {into_issue_id}");
+            update_blocked_states(&mut issues)?;
+        }
+
+        Commands::Split { comment_id } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let new_id = issues.split_comment(comment_id)?;
+            let Some(issue) = issues.get_issue(&new_id) else {
+                return Err(anyhow::anyhow!("issue {} not found", new_id));
+            };
+            println!("created new issue '{}'", issue.title());
+            println!("ID: {new_id}");
+        }
+
+        Commands::React {
+            comment_id,
+            reaction,
+            remove,
+        } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let user = entomologist::git::get_user_name_email(&issues.path())?;
+            for (_, issue) in issues.iter_mut() {
+                for comment in issue.comments.iter_mut() {
+                    if comment.uuid == *comment_id {
+                        if *remove {
+                            comment.remove_reaction(reaction, &user)?;
+                        } else {
+                            comment.add_reaction(reaction, &user)?;
+                        }
+                        print_reactions(comment);
+                        return Ok(());
+                    }
+                }
+            }
+            return Err(anyhow::anyhow!("comment {} not found", comment_id));
+        }
+
+        Commands::Attach { uuid, file, name } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let attachment = issues.attach(uuid, file, name.as_deref())?;
+            print_attachment(&attachment, "");
+        }
+
+        Commands::Attachments { issue_id } => {
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+            let Some(issue) = issues.get_issue(issue_id) else {
+                return Err(anyhow::anyhow!("issue {} not found", issue_id));
+            };
+            for attachment in &issue.attachments {
+                print_attachment(attachment, "");
+            }
+            for comment in &issue.comments {
+                if !comment.attachments.is_empty() {
+                    println!("comment {}:", comment.uuid);
+                    for attachment in &comment.attachments {
+                        print_attachment(attachment, "    ");
+                    }
+                }
+            }
+        }
+
+        Commands::Extract { uuid, name, output } => {
+            let issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let contents = issues.read_attachment(uuid, name)?;
+            match output {
+                Some(output) if output.as_os_str() == "-" => {
+                    std::io::Write::write_all(&mut std::io::stdout(), &contents)?;
+                }
+                _ => {
+                    let output = match output {
+                        Some(output) => output.clone(),
+                        None => std::path::PathBuf::from(name),
+                    };
+                    let mut file = std::fs::File::create_new(&output).map_err(|e| {
+                        anyhow::anyhow!("failed to create {}: {}", output.display(), e)
+                    })?;
+                    std::io::Write::write_all(&mut file, &contents)?;
+                    println!("wrote {} ({} bytes)", output.display(), contents.len());
+                }
+            }
+        }
+
+        Commands::Sync {
+            remote,
+            all,
+            dry_run,
+            no_push,
+        } => {
+            let remotes = match (remote, all) {
+                (Some(remote), _) => vec![remote.clone()],
+                (None, true) => entomologist::git::remotes(repo)?,
+                (None, false) => entomologist::git::default_sync_remotes(repo)?,
+            };
+            handle_sync(repo, git_ref, &remotes, *dry_run, *no_push)?;
+        }
+
+        Commands::Status => {
+            let statuses = entomologist::git::remote_status(repo, git_ref)?;
+            if statuses.is_empty() {
+                println!("no remotes");
+            }
+            for status in statuses {
+                if !status.has_branch {
+                    println!(
+                        "{}: no {} branch, {} commits to push",
+                        status.remote, status.branch, status.ahead
+                    );
+                } else if status.ahead == 0 && status.behind == 0 {
+                    println!("{}: up to date", status.remote);
+                } else {
+                    println!(
+                        "{}: {} ahead, {} behind",
+                        status.remote, status.ahead, status.behind
+                    );
+                }
+            }
+        }
+
+        Commands::Propose { name, command } => {
+            let propose_args = match command.is_empty() {
+                true => None,
+                false => Some(ProposeArgs::parse_from(
+                    std::iter::once(format!("ent propose {name}")).chain(command.iter().cloned()),
+                )),
+            };
+            if let Some(ProposeArgs {
+                command: Commands::Propose { .. },
+            }) = propose_args
+            {
+                return Err(anyhow::anyhow!("can't propose changes to a proposal"));
+            }
+            let proposal_branch = entomologist::proposal::create(repo, git_ref, name)?;
+            match propose_args {
+                Some(propose_args) => {
+                    handle_command(&propose_args.command, repo, &proposal_branch)?
+                }
+                None => println!("proposal branch {proposal_branch}"),
+            }
+        }
+
+        Commands::Proposals => {
+            let proposals = entomologist::proposal::list(repo, git_ref)?;
+            if proposals.is_empty() {
+                println!("no proposals");
+            }
+            for proposal in proposals {
+                if proposal.changes.is_empty() {
+                    println!("{}: no changes to the issues", proposal.branch);
+                    println!();
+                }
+                print_changes(&proposal.branch, &proposal.changes);
+            }
+        }
+
+        Commands::Accept { proposal } => {
+            let changes = entomologist::proposal::accept(repo, git_ref, proposal)?;
+            match changes.is_empty() {
+                true => println!("{proposal} has no changes to accept"),
+                false => print_changes(&format!("Accepted {proposal}"), &changes),
+            }
         }
 
         Commands::Assign {
@@ -421,7 +1440,7 @@ fn handle_command(
             new_assignee,
         } => match new_assignee {
             Some(new_assignee) => {
-                let mut issues = entomologist::IssuesMut::new_from_git(git_ref)?;
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
                 let Some(issue) = issues.get_issue_mut(issue_id) else {
                     return Err(anyhow::anyhow!("issue {} not found", issue_id));
                 };
@@ -434,7 +1453,7 @@ fn handle_command(
                 println!("assignee: {old_assignee} -> {new_assignee}");
             }
             None => {
-                let issues = entomologist::Issues::new_from_git(git_ref)?;
+                let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
                 let Some(original_issue) = issues.get_issue(issue_id) else {
                     return Err(anyhow::anyhow!("issue {} not found", issue_id));
                 };
@@ -453,7 +1472,7 @@ fn handle_command(
                 if tag.is_empty() {
                     return Err(anyhow::anyhow!("invalid zero-length tag"));
                 }
-                let mut issues = entomologist::IssuesMut::new_from_git(git_ref)?;
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
                 let Some(issue) = issues.get_issue_mut(issue_id) else {
                     return Err(anyhow::anyhow!("issue {} not found", issue_id));
                 };
@@ -465,7 +1484,7 @@ fn handle_command(
             }
             None => {
                 // Just list the tags.
-                let issues = entomologist::Issues::new_from_git(git_ref)?;
+                let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
                 let Some(issue) = issues.get_issue(issue_id) else {
                     return Err(anyhow::anyhow!("issue {} not found", issue_id));
                 };
@@ -490,7 +1509,7 @@ fn handle_command(
         } => match done_time {
             Some(done_time) => {
                 // Add or remove tag.
-                let mut issues = entomologist::IssuesMut::new_from_git(git_ref)?;
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
                 let Some(issue) = issues.get_issue_mut(issue_id) else {
                     return Err(anyhow::anyhow!("issue {} not found", issue_id));
                 };
@@ -504,7 +1523,7 @@ fn handle_command(
                 issue.set_done_time(done_time)?;
             }
             None => {
-                let issues = entomologist::Issues::new_from_git(git_ref)?;
+                let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
                 let Some(issue) = issues.get_issue(issue_id) else {
                     return Err(anyhow::anyhow!("issue {} not found", issue_id));
                 };
@@ -520,19 +1539,12 @@ fn handle_command(
             dependency_id,
         } => match dependency_id {
             Some(dep_id) => {
-                let mut issues = entomologist::IssuesMut::new_from_git(git_ref)?;
-                if let Some(_dep_issue) = issues.get_issue(dep_id) {
-                    if let Some(issue) = issues.get_issue_mut(issue_id) {
-                        issue.add_dependency(dep_id.clone())?;
-                    } else {
-                        Err(anyhow::anyhow!("issue {} not found", issue_id))?;
-                    };
-                } else {
-                    Err(anyhow::anyhow!("dependency {} not found", dep_id))?;
-                };
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+                issues.add_dependency(issue_id, dep_id)?;
+                update_blocked_states(&mut issues)?;
             }
             None => {
-                let issues = entomologist::Issues::new_from_git(git_ref)?;
+                let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
                 let Some(issue) = issues.get_issue(issue_id) else {
                     Err(anyhow::anyhow!("issue {} not found", issue_id))?
                 };
@@ -544,13 +1556,549 @@ fn handle_command(
                 } else {
                     println!("NONE");
                 }
+                println!("BLOCKS:");
+                let dependents = issues.dependents(issue_id);
+                if dependents.is_empty() {
+                    println!("NONE");
+                }
+                for dependent in dependents {
+                    println!("{}", dependent.id);
+                }
+            }
+        },
+
+        Commands::Link {
+            issue_id,
+            link_type,
+            target_id,
+        } => match (link_type, target_id) {
+            (Some(link_type), Some(target_id)) => {
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+                issues.add_link(issue_id, *link_type, target_id)?;
+                println!("issue: {issue_id}");
+                println!("{link_type}: {target_id}");
+            }
+            (None, None) => {
+                let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+                let Some(issue) = issues.get_issue(issue_id) else {
+                    return Err(anyhow::anyhow!("issue {} not found", issue_id));
+                };
+                let links_to = issues.links_to(issue_id);
+                if issue.links.is_empty() && links_to.is_empty() {
+                    println!("no links");
+                }
+                for link in &issue.links {
+                    println!("{} {}", link.link_type, link.target);
+                }
+                for (link_type, source) in links_to {
+                    println!("{} {}", link_type.reverse_name(), source);
+                }
+            }
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "link type and target must be given together"
+                ));
             }
         },
+
+        Commands::Unlink {
+            issue_id,
+            link_type,
+            target_id,
+        } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let Some(issue) = issues.get_issue_mut(issue_id) else {
+                return Err(anyhow::anyhow!("issue {} not found", issue_id));
+            };
+            issue.remove_link(*link_type, target_id)?;
+            println!("issue: {issue_id}");
+            println!("removed {link_type}: {target_id}");
+        }
+
+        Commands::Dup {
+            duplicate_id,
+            original_id,
+        } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            issues.mark_duplicate(duplicate_id, original_id)?;
+            println!("issue {duplicate_id} is a duplicate of {original_id}");
+            update_blocked_states(&mut issues)?;
+        }
+
+        Commands::Set { issue_id, fields } => {
+            if fields.is_empty() {
+                let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+                let Some(issue) = issues.get_issue(issue_id) else {
+                    return Err(anyhow::anyhow!("issue {} not found", issue_id));
+                };
+                println!("issue: {issue_id}");
+                for (name, value) in &issue.fields {
+                    println!("{name}: {value}");
+                }
+                return Ok(());
+            }
+
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            println!("issue: {issue_id}");
+            for field in fields {
+                let Some((name, value)) = field.split_once('=') else {
+                    return Err(anyhow::anyhow!("expected NAME=VALUE, got {:?}", field));
+                };
+                let old_value = match issues.get_issue(issue_id) {
+                    Some(issue) => match issue.fields.get(name) {
+                        Some(old_value) => old_value.to_string(),
+                        None => String::from("None"),
+                    },
+                    None => return Err(anyhow::anyhow!("issue {} not found", issue_id)),
+                };
+                issues.set_field(issue_id, name, value)?;
+                let new_value = if value.is_empty() { "None" } else { value };
+                println!("{name}: {old_value} -> {new_value}");
+            }
+        }
+
+        Commands::Parent {
+            issue_id,
+            parent_id,
+            clear,
+        } => {
+            if let Some(parent_id) = parent_id {
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+                issues.set_parent(issue_id, parent_id)?;
+                println!("issue: {issue_id}");
+                println!("parent: {parent_id}");
+            } else if *clear {
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+                let Some(issue) = issues.get_issue_mut(issue_id) else {
+                    return Err(anyhow::anyhow!("issue {} not found", issue_id));
+                };
+                issue.clear_parent()?;
+                println!("issue: {issue_id}");
+                println!("parent: None");
+            } else {
+                let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+                let Some(issue) = issues.get_issue(issue_id) else {
+                    return Err(anyhow::anyhow!("issue {} not found", issue_id));
+                };
+                println!("issue: {issue_id}");
+                match &issue.parent {
+                    Some(parent) => println!("parent: {parent}"),
+                    None => println!("parent: None"),
+                }
+            }
+        }
+
+        Commands::Estimate {
+            issue_id,
+            estimate,
+            clear,
+        } => {
+            if estimate.is_some() || *clear {
+                let estimate = match estimate {
+                    Some(estimate) => Some(entomologist::worklog::parse_duration(estimate)?),
+                    None => None,
+                };
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+                let Some(issue) = issues.get_issue_mut(issue_id) else {
+                    return Err(anyhow::anyhow!("issue {} not found", issue_id));
+                };
+                issue.set_estimate(estimate)?;
+            }
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+            let Some(issue) = issues.get_issue(issue_id) else {
+                return Err(anyhow::anyhow!("issue {} not found", issue_id));
+            };
+            println!("issue: {issue_id}");
+            match &issue.estimate {
+                Some(estimate) => println!(
+                    "estimate: {}",
+                    entomologist::worklog::format_duration(estimate)
+                ),
+                None => println!("estimate: None"),
+            }
+            println!(
+                "logged: {}",
+                entomologist::worklog::format_duration(&issue.logged_time())
+            );
+        }
+
+        Commands::Start { issue_id } => {
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+            if issues.get_issue(issue_id).is_none() {
+                return Err(anyhow::anyhow!("issue {} not found", issue_id));
+            }
+            entomologist::worklog::start_timer(repo, issue_id, chrono::Local::now())?;
+            println!("started timer on issue {issue_id}");
+        }
+
+        Commands::Stop { issue_id, note } => {
+            let issue_id = match issue_id {
+                Some(issue_id) => issue_id.clone(),
+                None => {
+                    let timers = entomologist::worklog::running_timers(repo)?;
+                    match timers.as_slice() {
+                        [] => return Err(anyhow::anyhow!("no timer is running")),
+                        [(issue_id, _)] => issue_id.clone(),
+                        _ => {
+                            let ids: Vec<&str> = timers.iter().map(|(id, _)| id.as_str()).collect();
+                            return Err(anyhow::anyhow!(
+                                "several timers are running, specify one of: {}",
+                                ids.join(", ")
+                            ));
+                        }
+                    }
+                }
+            };
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let user = entomologist::git::get_user_name_email(&issues.path())?;
+            let Some(issue) = issues.get_issue_mut(&issue_id) else {
+                return Err(anyhow::anyhow!("issue {} not found", issue_id));
+            };
+            let start = entomologist::worklog::timer_start(repo, &issue_id)?;
+            let duration = chrono::Local::now() - start;
+            issue.log_work(&user, start, duration, note.as_deref())?;
+            entomologist::worklog::clear_timer(repo, &issue_id)?;
+            println!(
+                "logged {} on issue {issue_id}",
+                entomologist::worklog::format_duration(&duration)
+            );
+        }
+
+        Commands::Milestone { command } => handle_milestone_command(command, repo, git_ref)?,
+
+        Commands::Multi { workspace, command } => handle_multi_command(command, workspace)?,
+
+        Commands::Unlock => {
+            let lock_name = entomologist::lock::branch_lock_name(git_ref);
+            match entomologist::lock::Lock::break_lock(repo, &lock_name)? {
+                Some(holder) => println!(
+                    "removed lock held by process {} on {} since {}",
+                    holder.pid, holder.host, holder.time
+                ),
+                None => println!("{git_ref:?} is not locked"),
+            }
+        }
+
+        Commands::Doctor => {
+            let mut problems = 0;
+            for worktree in entomologist::gitdb::worktree::prune_stale(repo)? {
+                problems += 1;
+                println!(
+                    "removed stale worktree {} (made by process {} on {} at {})",
+                    worktree.path.display(),
+                    worktree.owner.pid,
+                    worktree.owner.host,
+                    worktree.owner.time
+                );
+            }
+            let lock_name = entomologist::lock::branch_lock_name(git_ref);
+            if let Some(holder) = entomologist::lock::Lock::holder(repo, &lock_name)? {
+                problems += 1;
+                if holder.is_stale() {
+                    entomologist::lock::Lock::break_lock(repo, &lock_name)?;
+                    println!(
+                        "removed stale lock on {git_ref:?} (held by process {} on {} since {})",
+                        holder.pid, holder.host, holder.time
+                    );
+                } else {
+                    println!(
+                        "{git_ref:?} is locked by process {} on {} since {}; \
+                         if that process is gone, remove the lock with `ent unlock`",
+                        holder.pid, holder.host, holder.time
+                    );
+                }
+            }
+            if problems == 0 {
+                println!("no problems found");
+            }
+        }
+
+        Commands::Changelog { since, until, by } => {
+            let since = entomologist::changelog::parse_time(repo, since)?;
+            let until = match until {
+                Some(until) => entomologist::changelog::parse_time(repo, until)?,
+                None => chrono::Local::now(),
+            };
+            let group_by: entomologist::changelog::GroupBy = by.parse()?;
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+            print!(
+                "{}",
+                entomologist::changelog::render(&issues, since, until, group_by)
+            );
+        }
+
+        Commands::Report {
+            report: ReportCommands::Time { since, by },
+        } => {
+            let since = entomologist::worklog::parse_since(since, chrono::Local::now())?;
+            let group_by: entomologist::worklog::GroupBy = by.parse()?;
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+            let report = entomologist::worklog::time_report(&issues, since, group_by);
+            let mut total = chrono::TimeDelta::zero();
+            for (key, time) in &report {
+                total += *time;
+                let time = entomologist::worklog::format_duration(time);
+                match issues.get_issue(key) {
+                    Some(issue) if group_by == entomologist::worklog::GroupBy::Issue => {
+                        let estimate = match &issue.estimate {
+                            Some(estimate) => entomologist::worklog::format_duration(estimate),
+                            None => String::from("-"),
+                        };
+                        println!("{time:>8}  {estimate:>8}  {key}  {}", issue.title());
+                    }
+                    _ => println!("{time:>8}  {key}"),
+                }
+            }
+            println!(
+                "{:>8}  total since {}",
+                entomologist::worklog::format_duration(&total),
+                since.format("%Y-%m-%d")
+            );
+        }
     }
 
     Ok(())
 }
 
+fn handle_milestone_command(
+    command: &MilestoneCommands,
+    repo: &entomologist::Repo,
+    git_ref: &str,
+) -> anyhow::Result<()> {
+    match command {
+        MilestoneCommands::New {
+            name,
+            description,
+            target,
+        } => {
+            let target_date = match target {
+                Some(target) => Some(entomologist::milestone::parse_target_date(target)?),
+                None => None,
+            };
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            let description = description.as_deref().unwrap_or_default();
+            let milestone = issues.new_milestone(name, description, target_date)?;
+            println!("created milestone {}", milestone.name);
+        }
+
+        MilestoneCommands::List => {
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+            let today = chrono::Local::now().date_naive();
+            for (name, milestone) in issues.milestones() {
+                let (closed, total) = issues.milestone_progress(name);
+                let target = match &milestone.target_date {
+                    Some(target_date) => target_date.format("%Y-%m-%d").to_string(),
+                    None => String::from("-"),
+                };
+                println!(
+                    "{:<12}  {:<6}  {:<10}  {:>3}/{:<3}  {}{}",
+                    name,
+                    if milestone.closed { "closed" } else { "open" },
+                    target,
+                    closed,
+                    total,
+                    milestone.title(),
+                    if milestone.is_overdue(today) {
+                        "  (overdue)"
+                    } else {
+                        ""
+                    }
+                );
+            }
+        }
+
+        MilestoneCommands::Show { name } => {
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+            let Some(milestone) = issues.get_milestone(name) else {
+                return Err(anyhow::anyhow!("milestone {} not found", name));
+            };
+            let today = chrono::Local::now().date_naive();
+            println!("milestone {name}");
+            println!(
+                "state: {}",
+                if milestone.closed { "closed" } else { "open" }
+            );
+            if let Some(target_date) = &milestone.target_date {
+                if milestone.is_overdue(today) {
+                    println!(
+                        "target_date: {} (overdue by {} days)",
+                        target_date.format("%Y-%m-%d"),
+                        (today - *target_date).num_days()
+                    );
+                } else {
+                    println!("target_date: {}", target_date.format("%Y-%m-%d"));
+                }
+            }
+            let (closed, total) = issues.milestone_progress(name);
+            match (closed * 100).checked_div(total) {
+                Some(percent) => println!("progress: {closed}/{total} done ({percent}%)"),
+                None => println!("progress: no issues"),
+            }
+            let blockers = issues.milestone_blockers(name);
+            if !blockers.is_empty() {
+                println!("blockers:");
+                for issue in blockers {
+                    print!("    {}  {}", issue.id, issue.title());
+                    let deps = issues.blocking_dependencies(&issue.id);
+                    if !deps.is_empty() {
+                        let deps: Vec<&str> = deps.iter().map(|dep| dep.as_str()).collect();
+                        print!("  (waiting on {})", deps.join(", "));
+                    }
+                    println!();
+                }
+            }
+            if !milestone.description.is_empty() {
+                println!();
+                println!("{}", milestone.description.trim_end());
+            }
+            println!();
+            let workflow = &issues.config().workflow;
+            for issue in issues.milestone_issues(name) {
+                let state = issues.effective_state(issue);
+                println!(
+                    "{:<10}  {}  {}",
+                    workflow.label(&state),
+                    issue.id,
+                    issue.title()
+                );
+            }
+        }
+
+        MilestoneCommands::Close { name, reopen } => {
+            let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+            issues.set_milestone_closed(name, !*reopen)?;
+            println!(
+                "milestone {name}: {}",
+                if *reopen { "open" } else { "closed" }
+            );
+        }
+
+        MilestoneCommands::Set {
+            issue_id,
+            milestone,
+            clear,
+        } => {
+            if milestone.is_some() || *clear {
+                let mut issues = entomologist::IssuesMut::new_from_git(repo, git_ref)?;
+                issues.set_milestone(issue_id, milestone.as_deref())?;
+            }
+            let issues = entomologist::Issues::new_from_git(repo, git_ref)?;
+            let Some(issue) = issues.get_issue(issue_id) else {
+                return Err(anyhow::anyhow!("issue {} not found", issue_id));
+            };
+            println!("issue: {issue_id}");
+            println!(
+                "milestone: {}",
+                issue.milestone.as_deref().unwrap_or("None")
+            );
+        }
+    }
+    Ok(())
+}
+
+fn handle_multi_command(
+    command: &MultiCommands,
+    workspace: &std::path::Path,
+) -> anyhow::Result<()> {
+    match command {
+        MultiCommands::List { filter, json } => {
+            let workspace = entomologist::multi::Workspace::new_from_file(workspace)?;
+            let multi = entomologist::multi::MultiIssues::new_from_workspace(&workspace)?;
+
+            // Each repo has its own workflow, so each gets its own
+            // Filter, with the repo's open states as the default.
+            let mut filters = std::collections::HashMap::<&str, entomologist::Filter>::new();
+            for (repo_name, issues) in &multi.repos {
+                let mut f = entomologist::Filter::new_for_workflow(&issues.config().workflow);
+                for filter_str in filter {
+                    f.parse(filter_str)?;
+                }
+                filters.insert(repo_name, f);
+            }
+
+            let mut listed =
+                Vec::<(&str, &entomologist::Issues, &entomologist::Issue, State)>::new();
+            for (repo_name, issues, issue) in multi.iter() {
+                let state = multi.effective_state(repo_name, issue);
+                if filters[repo_name].matches(issue, &state) {
+                    listed.push((repo_name, issues, issue, state));
+                }
+            }
+            listed.sort_by_key(|(_, _, issue, _)| issue.creation_time);
+
+            if *json {
+                let json_issues: Vec<serde_json::Value> = listed
+                    .iter()
+                    .map(|(repo_name, _, issue, state)| {
+                        serde_json::json!({
+                            "repo": repo_name,
+                            "id": format!("{}:{}", repo_name, issue.id),
+                            "title": issue.title(),
+                            "state": state.to_string(),
+                            "assignee": issue.assignee,
+                            "tags": issue.tags,
+                            "milestone": issue.milestone,
+                            "dependencies": issue
+                                .dependencies
+                                .iter()
+                                .flatten()
+                                .map(|dep| match entomologist::multi::split_issue_ref(dep) {
+                                    (Some(_), _) => dep.clone(),
+                                    (None, dep_id) => format!("{repo_name}:{dep_id}"),
+                                })
+                                .collect::<Vec<String>>(),
+                            "blocked_by": multi.blocking_dependencies(repo_name, issue),
+                            "creation_time": issue.creation_time.to_rfc3339(),
+                            "done_time": issue.done_time.map(|t| t.to_rfc3339()),
+                        })
+                    })
+                    .collect();
+                println!("{}", serde_json::to_string_pretty(&json_issues)?);
+                return Ok(());
+            }
+
+            // Show the states in the order of the first repo's workflow
+            // that has them, followed by any states no workflow knows
+            // about.
+            let mut states = Vec::<(State, String)>::new();
+            for (_, issues) in &multi.repos {
+                let workflow = &issues.config().workflow;
+                for state in workflow.states() {
+                    if !states.iter().any(|(s, _)| s == state) {
+                        states.push((state.clone(), workflow.label(state)));
+                    }
+                }
+            }
+            for (_, _, _, state) in &listed {
+                if !states.iter().any(|(s, _)| s == state) {
+                    states.push((state.clone(), state.to_string()));
+                }
+            }
+            for (state, label) in states {
+                let these_issues: Vec<_> = listed
+                    .iter()
+                    .filter(|(_, _, _, issue_state)| *issue_state == state)
+                    .collect();
+                if these_issues.is_empty() {
+                    continue;
+                }
+                println!("{label}:");
+                for (repo_name, issues, issue, _) in these_issues {
+                    let blocked_by = multi.blocking_dependencies(repo_name, issue);
+                    let line = format_issue_line(issues, &issue.id);
+                    if blocked_by.is_empty() {
+                        println!("{repo_name}:{line}");
+                    } else {
+                        println!("{repo_name}:{line} (⌛ {})", blocked_by.join(", "));
+                    }
+                }
+                println!();
+            }
+        }
+    }
+    Ok(())
+}
+
 fn main() -> anyhow::Result<()> {
     #[cfg(feature = "log")]
     simple_logger::SimpleLogger::new().env().init().unwrap();
@@ -558,7 +2106,19 @@ fn main() -> anyhow::Result<()> {
     let args: Args = Args::parse();
     // println!("{:?}", args);
 
-    handle_command(&args, &args.issues_branch)?;
+    let repo = match &args.repo {
+        Some(path) => entomologist::Repo::new(path),
+        None => entomologist::Repo::current(),
+    }
+    .with_lock_timeout(std::time::Duration::from_secs(args.lock_timeout))
+    .with_busy_callback(|holder| {
+        eprintln!(
+            "issue database is busy (locked by process {} on {} since {}), waiting...",
+            holder.pid, holder.host, holder.time
+        );
+    });
+    entomologist::cleanup::cleanup_on_signals()?;
+    handle_command(&args.command, &repo, &args.issues_branch)?;
 
     Ok(())
 }
@@ -0,0 +1,264 @@
+//! Release notes from the issues completed in a time window.
+//!
+//! When grouping by tag, each issue goes in the first changelog section
+//! that lists one of its tags, and in the `other` section if none do.
+//! The sections are configured in the `[changelog]` table of the
+//! database's `config.toml`, for example:
+//!
+//! ```toml
+//! [changelog]
+//! other = "Changed"
+//!
+//! [[changelog.sections]]
+//! name = "Added"
+//! tags = ["feature", "enhancement"]
+//!
+//! [[changelog.sections]]
+//! name = "Fixed"
+//! tags = ["bug"]
+//! ```
+//!
+//! Without a `[changelog]` table the sections are the ones above.
+
+use std::str::FromStr;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ChangelogError {
+    #[error(transparent)]
+    GitError(#[from] crate::git::GitError),
+    #[error("{0:?} is not a date (YYYY-MM-DD), a time (RFC 3339), or a git tag")]
+    InvalidTime(String),
+    #[error("invalid grouping {0:?}, expected tag or milestone")]
+    InvalidGroupBy(String),
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct Section {
+    pub name: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(default)]
+pub struct ChangelogConfig {
+    /// The sections of the changelog, in the order they're shown.
+    pub sections: Vec<Section>,
+    /// The section for issues with none of the tags of the sections.
+    pub other: String,
+}
+
+impl Default for ChangelogConfig {
+    fn default() -> Self {
+        Self {
+            sections: vec![
+                Section {
+                    name: String::from("Added"),
+                    tags: vec![String::from("feature"), String::from("enhancement")],
+                },
+                Section {
+                    name: String::from("Fixed"),
+                    tags: vec![String::from("bug")],
+                },
+            ],
+            other: String::from("Changed"),
+        }
+    }
+}
+
+impl ChangelogConfig {
+    /// The name of the section an issue with these tags goes in.
+    pub fn section_for(&self, tags: &[String]) -> &str {
+        match self
+            .sections
+            .iter()
+            .find(|section| section.tags.iter().any(|tag| tags.contains(tag)))
+        {
+            Some(section) => &section.name,
+            None => &self.other,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// How the changelog groups the issues.
+pub enum GroupBy {
+    Tag,
+    Milestone,
+}
+
+impl FromStr for GroupBy {
+    type Err = ChangelogError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tag" => Ok(GroupBy::Tag),
+            "milestone" => Ok(GroupBy::Milestone),
+            _ => Err(ChangelogError::InvalidGroupBy(String::from(s))),
+        }
+    }
+}
+
+/// Parse the start or end of a changelog window: an RFC 3339 time,
+/// a date (meaning the start of that day), or a git tag in the repo
+/// (meaning the time of the tagged commit).
+pub fn parse_time(
+    repo: &crate::Repo,
+    s: &str,
+) -> Result<chrono::DateTime<chrono::Local>, ChangelogError> {
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&chrono::Local));
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        && let Some(time) = date
+            .and_time(chrono::NaiveTime::MIN)
+            .and_local_timezone(chrono::Local)
+            .earliest()
+    {
+        return Ok(time);
+    }
+    match crate::git::tag_time(repo, s)? {
+        Some(time) => Ok(time),
+        None => Err(ChangelogError::InvalidTime(String::from(s))),
+    }
+}
+
+/// The Issues completed between `since` (inclusive) and `until`
+/// (exclusive), sorted by done time.  Issues closed as WontDo are left
+/// out, they didn't change anything.
+pub fn completed_issues(
+    issues: &crate::Issues,
+    since: chrono::DateTime<chrono::Local>,
+    until: chrono::DateTime<chrono::Local>,
+) -> Vec<&crate::Issue> {
+    let workflow = &issues.config().workflow;
+    let mut completed: Vec<&crate::Issue> = issues
+        .iter()
+        .map(|(_, issue)| issue)
+        .filter(|issue| {
+            workflow.is_closed(&issue.state) && issue.state != crate::issue::State::WontDo
+        })
+        .filter(|issue| match issue.done_time {
+            Some(done_time) => since <= done_time && done_time < until,
+            None => false,
+        })
+        .collect();
+    completed.sort_by_key(|issue| issue.done_time);
+    completed
+}
+
+/// Render the Issues completed in a time window as Markdown release
+/// notes.
+pub fn render(
+    issues: &crate::Issues,
+    since: chrono::DateTime<chrono::Local>,
+    until: chrono::DateTime<chrono::Local>,
+    group_by: GroupBy,
+) -> String {
+    let completed = completed_issues(issues, since, until);
+
+    // Group names in the order they're rendered.
+    let mut groups = Vec::<(String, Vec<&crate::Issue>)>::new();
+    match group_by {
+        GroupBy::Tag => {
+            let config = &issues.config().changelog;
+            for section in &config.sections {
+                groups.push((section.name.clone(), Vec::new()));
+            }
+            groups.push((config.other.clone(), Vec::new()));
+            for issue in &completed {
+                let section = config.section_for(&issue.tags);
+                if let Some((_, group)) = groups.iter_mut().find(|(name, _)| name == section) {
+                    group.push(issue);
+                }
+            }
+        }
+        GroupBy::Milestone => {
+            for (name, milestone) in issues.milestones() {
+                let heading = match milestone.title() {
+                    "" => name.clone(),
+                    title => format!("{name}: {title}"),
+                };
+                groups.push((
+                    heading,
+                    completed
+                        .iter()
+                        .filter(|issue| issue.milestone.as_deref() == Some(name))
+                        .copied()
+                        .collect(),
+                ));
+            }
+            groups.push((
+                String::from("Other"),
+                completed
+                    .iter()
+                    .filter(|issue| match &issue.milestone {
+                        Some(milestone) => issues.get_milestone(milestone).is_none(),
+                        None => true,
+                    })
+                    .copied()
+                    .collect(),
+            ));
+        }
+    }
+
+    let mut changelog = format!(
+        "# Changes from {} to {}\n",
+        since.format("%Y-%m-%d"),
+        until.format("%Y-%m-%d")
+    );
+    if completed.is_empty() {
+        changelog.push_str("\nNo issues were completed.\n");
+    }
+    for (name, group) in groups {
+        if group.is_empty() {
+            continue;
+        }
+        changelog.push_str(&format!("\n## {name}\n\n"));
+        for issue in group {
+            changelog.push_str(&format!("- {} ({})\n", issue.title(), issue.id));
+        }
+    }
+    changelog
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn changelog_sections() {
+        let config: ChangelogConfig = toml::from_str(
+            r#"
+            [[sections]]
+            name = "Fixed"
+            tags = ["bug", "regression"]
+
+            [[sections]]
+            name = "Hardware"
+            tags = ["pcb"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.other, "Changed");
+        let tags =
+            |tags: &[&str]| -> Vec<String> { tags.iter().map(|t| String::from(*t)).collect() };
+        assert_eq!(config.section_for(&tags(&["pcb", "regression"])), "Fixed");
+        assert_eq!(config.section_for(&tags(&["pcb"])), "Hardware");
+        assert_eq!(config.section_for(&tags(&["docs"])), "Changed");
+        assert_eq!(config.section_for(&[]), "Changed");
+
+        let config = ChangelogConfig::default();
+        assert_eq!(config.section_for(&tags(&["enhancement"])), "Added");
+    }
+
+    #[test]
+    fn changelog_times() {
+        let time = parse_time(&crate::Repo::current(), "2025-07-24T10:08:38-06:00").unwrap();
+        assert_eq!(
+            time,
+            chrono::DateTime::parse_from_rfc3339("2025-07-24T16:08:38Z").unwrap()
+        );
+        let date = parse_time(&crate::Repo::current(), "2025-07-24").unwrap();
+        assert_eq!(date.time(), chrono::NaiveTime::MIN);
+    }
+}
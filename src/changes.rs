@@ -0,0 +1,321 @@
+//! Summaries of how the issues changed between two commits of an
+//! issues branch, for showing what `ent sync` brings in and sends out.
+
+use crate::git::GitError;
+
+/// The id of git's empty tree, to diff against when there's no "from"
+/// commit.
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Directories at the top of the issues branch that aren't issues.
+const NON_ISSUE_DIRS: [&str; 3] = ["templates", "milestones", "blobs"];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IssueStatus {
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// One change to an existing Issue.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+    State {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    Assignee {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    Description,
+    TagAdded(String),
+    TagRemoved(String),
+    CommentAdded(String),
+    CommentEdited(String),
+    CommentRemoved(String),
+    /// Some other part of the issue changed, named by its file or
+    /// directory in the issue, like "dependencies" or "worklog".
+    Other(String),
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let or_none = |value: &Option<String>| match value {
+            Some(value) => value.clone(),
+            None => String::from("none"),
+        };
+        match self {
+            Change::State { old, new } => write!(f, "state: {} -> {}", or_none(old), or_none(new)),
+            Change::Assignee { old, new } => {
+                write!(f, "assignee: {} -> {}", or_none(old), or_none(new))
+            }
+            Change::Description => write!(f, "description edited"),
+            Change::TagAdded(tag) => write!(f, "tag added: {tag}"),
+            Change::TagRemoved(tag) => write!(f, "tag removed: {tag}"),
+            Change::CommentAdded(id) => write!(f, "comment added: {id}"),
+            Change::CommentEdited(id) => write!(f, "comment edited: {id}"),
+            Change::CommentRemoved(id) => write!(f, "comment removed: {id}"),
+            Change::Other(name) => write!(f, "{name} changed"),
+        }
+    }
+}
+
+/// How one Issue changed.  Created and deleted issues have no
+/// `changes`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IssueChanges {
+    pub id: String,
+    /// The title of the issue, as of the newer commit (the older one
+    /// for deleted issues).
+    pub title: String,
+    pub status: IssueStatus,
+    pub changes: Vec<Change>,
+}
+
+/// How the issues changed between two commits.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Changes {
+    pub issues: Vec<IssueChanges>,
+    /// Changed files outside of issues, like `config.toml`.
+    pub other: Vec<String>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty() && self.other.is_empty()
+    }
+
+    /// Summarize the changes from commit `from` to commit `to` of an
+    /// issues branch in the repo.  If `from` is None, everything in
+    /// `to` is new.
+    pub fn between(repo: &crate::Repo, from: Option<&str>, to: &str) -> Result<Self, GitError> {
+        let from = from.unwrap_or(EMPTY_TREE);
+        let output = crate::git::run(repo.git().args([
+            "diff",
+            "--name-status",
+            "-z",
+            "--no-renames",
+            from,
+            to,
+            "--",
+        ]))?;
+        let output = String::from_utf8_lossy(&output.stdout);
+        let mut fields = output.split('\0');
+
+        let mut changes = Changes::default();
+        while let (Some(status), Some(path)) = (fields.next(), fields.next()) {
+            let components: Vec<&str> = path.split('/').collect();
+            if components.len() < 2 || NON_ISSUE_DIRS.contains(&components[0]) {
+                changes.other.push(String::from(path));
+                continue;
+            }
+            let id = components[0];
+            if changes.issues.last().map(|issue| issue.id.as_str()) != Some(id) {
+                changes.issues.push(IssueChanges {
+                    id: String::from(id),
+                    title: String::new(),
+                    status: IssueStatus::Modified,
+                    changes: Vec::new(),
+                });
+            }
+            let Some(issue) = changes.issues.last_mut() else {
+                continue;
+            };
+            let change = match (&components[1..], status) {
+                (["description"], "A") => {
+                    issue.status = IssueStatus::Created;
+                    continue;
+                }
+                (["description"], "D") => {
+                    issue.status = IssueStatus::Deleted;
+                    continue;
+                }
+                (["description"], _) => Change::Description,
+                (["state"], _) => Change::State {
+                    old: read_file(repo, from, path)?,
+                    new: read_file(repo, to, path)?,
+                },
+                (["assignee"], _) => Change::Assignee {
+                    old: read_file(repo, from, path)?,
+                    new: read_file(repo, to, path)?,
+                },
+                (["tags", tag], "A") => Change::TagAdded(tag_name(tag)),
+                (["tags", tag], "D") => Change::TagRemoved(tag_name(tag)),
+                (["comments", comment, "description"], "A") => {
+                    Change::CommentAdded(String::from(*comment))
+                }
+                (["comments", comment, "description"], "D") => {
+                    Change::CommentRemoved(String::from(*comment))
+                }
+                (["comments", comment, ..], _) => Change::CommentEdited(String::from(*comment)),
+                ([name, ..], _) => Change::Other(String::from(*name)),
+                ([], _) => continue,
+            };
+            if !issue.changes.contains(&change) {
+                issue.changes.push(change);
+            }
+        }
+
+        for issue in &mut changes.issues {
+            if issue.status != IssueStatus::Modified {
+                issue.changes.clear();
+            }
+            // Comments that were added or removed weren't also edited.
+            let added_or_removed: Vec<String> = issue
+                .changes
+                .iter()
+                .filter_map(|change| match change {
+                    Change::CommentAdded(id) | Change::CommentRemoved(id) => Some(id.clone()),
+                    _ => None,
+                })
+                .collect();
+            issue.changes.retain(
+                |change| !matches!(change, Change::CommentEdited(id) if added_or_removed.contains(id)),
+            );
+            let rev = match issue.status {
+                IssueStatus::Deleted => from,
+                _ => to,
+            };
+            let description = read_file(repo, rev, &format!("{}/description", issue.id))?;
+            issue.title = match description {
+                Some(description) => String::from(description.lines().next().unwrap_or("")),
+                None => String::new(),
+            };
+        }
+
+        Ok(changes)
+    }
+}
+
+fn tag_name(filename: &str) -> String {
+    match crate::issue::Issue::tag_from_filename(filename) {
+        Ok(tag) => tag,
+        Err(_) => String::from(filename),
+    }
+}
+
+/// The trimmed contents of a file in a commit, or None if there's no
+/// such file.
+fn read_file(repo: &crate::Repo, rev: &str, path: &str) -> Result<Option<String>, GitError> {
+    let result = repo
+        .git()
+        .args(["cat-file", "-p", &format!("{rev}:{path}")])
+        .output()?;
+    if !result.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(
+        String::from_utf8_lossy(&result.stdout).trim().to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn summarize_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let result = std::process::Command::new("git")
+                .args(args)
+                .current_dir(dir.path())
+                .output()
+                .unwrap();
+            assert!(result.status.success(), "git {args:?} failed");
+            String::from_utf8_lossy(&result.stdout).trim().to_string()
+        };
+        let write = |path: &str, contents: &str| {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        };
+        let a = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+        let b = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+        let c = "cccccccccccccccccccccccccccccccc";
+
+        git(&["init"]);
+        git(&["config", "user.name", "Test"]);
+        git(&["config", "user.email", "test@example.com"]);
+        write(
+            &format!("{a}/description"),
+            "Motor stalls\n\nAt low speed.\n",
+        );
+        write(&format!("{a}/state"), "New");
+        write(&format!("{a}/comments/1111/description"), "me too");
+        write(&format!("{b}/description"), "Old issue\n");
+        git(&["add", "."]);
+        git(&["commit", "-m", "first"]);
+        let first = git(&["rev-parse", "HEAD"]);
+
+        write(&format!("{a}/state"), "InProgress");
+        write(&format!("{a}/assignee"), "seb");
+        write(&format!("{a}/tags/bug"), "");
+        write(&format!("{a}/comments/1111/description"), "me too!");
+        write(&format!("{a}/comments/2222/description"), "fixed?");
+        write(&format!("{a}/dependencies/{b}"), "");
+        std::fs::remove_dir_all(dir.path().join(b)).unwrap();
+        write(&format!("{c}/description"), "New issue\n");
+        write("config.toml", "auto_blocked = true\n");
+        git(&["add", "--all", "."]);
+        git(&["commit", "-m", "second"]);
+        let second = git(&["rev-parse", "HEAD"]);
+
+        let repo = crate::Repo::new(dir.path());
+        let changes = Changes::between(&repo, Some(&first), &second).unwrap();
+        assert_eq!(changes.other, vec![String::from("config.toml")]);
+        assert_eq!(
+            changes.issues,
+            vec![
+                IssueChanges {
+                    id: String::from(a),
+                    title: String::from("Motor stalls"),
+                    status: IssueStatus::Modified,
+                    changes: vec![
+                        Change::Assignee {
+                            old: None,
+                            new: Some(String::from("seb")),
+                        },
+                        Change::CommentEdited(String::from("1111")),
+                        Change::CommentAdded(String::from("2222")),
+                        Change::Other(String::from("dependencies")),
+                        Change::State {
+                            old: Some(String::from("New")),
+                            new: Some(String::from("InProgress")),
+                        },
+                        Change::TagAdded(String::from("bug")),
+                    ],
+                },
+                IssueChanges {
+                    id: String::from(b),
+                    title: String::from("Old issue"),
+                    status: IssueStatus::Deleted,
+                    changes: vec![],
+                },
+                IssueChanges {
+                    id: String::from(c),
+                    title: String::from("New issue"),
+                    status: IssueStatus::Created,
+                    changes: vec![],
+                },
+            ]
+        );
+
+        let everything = Changes::between(&repo, None, &first).unwrap();
+        assert_eq!(
+            everything
+                .issues
+                .iter()
+                .map(|issue| issue.status)
+                .collect::<Vec<_>>(),
+            vec![IssueStatus::Created, IssueStatus::Created]
+        );
+        assert!(
+            Changes::between(&repo, Some(&second), &second)
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,107 @@
+//! Cleaning up after `ent` is killed.
+//!
+//! Worktrees, snapshots and locks clean up after themselves when
+//! they're dropped, but nothing is dropped when the process is killed
+//! by a signal (Ctrl-C while the editor is open, for example).  Live
+//! worktrees, snapshots and locks register themselves here, and
+//! `cleanup_on_signals()` starts a thread that removes them when the
+//! process gets SIGINT, SIGTERM or SIGHUP, and then exits.
+//!
+//! Whatever is left behind anyway (after SIGKILL, or a power cut) is
+//! found later by its owner's process id, see
+//! `crate::gitdb::worktree::prune_stale()` and `crate::lock`.  A
+//! snapshot left behind is only a temporary directory, the repo doesn't
+//! know about it.
+
+enum Resource {
+    Worktree {
+        repo: crate::Repo,
+        path: std::path::PathBuf,
+    },
+    Lock(std::path::PathBuf),
+    Directory(std::path::PathBuf),
+}
+
+static RESOURCES: std::sync::Mutex<Vec<(u64, Resource)>> = std::sync::Mutex::new(Vec::new());
+static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// A registered resource.  Dropping the Registration unregisters it,
+/// which its owner must do *before* cleaning the resource up itself.
+#[derive(Debug)]
+pub(crate) struct Registration(u64);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        resources().retain(|(id, _)| *id != self.0);
+    }
+}
+
+fn resources() -> std::sync::MutexGuard<'static, Vec<(u64, Resource)>> {
+    RESOURCES
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn register(resource: Resource) -> Registration {
+    let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    resources().push((id, resource));
+    Registration(id)
+}
+
+pub(crate) fn register_worktree(repo: &crate::Repo, path: &std::path::Path) -> Registration {
+    register(Resource::Worktree {
+        repo: repo.clone(),
+        path: std::path::PathBuf::from(path),
+    })
+}
+
+pub(crate) fn register_lock(path: &std::path::Path) -> Registration {
+    register(Resource::Lock(std::path::PathBuf::from(path)))
+}
+
+pub(crate) fn register_directory(path: &std::path::Path) -> Registration {
+    register(Resource::Directory(std::path::PathBuf::from(path)))
+}
+
+/// Start a thread that cleans up all registered worktrees, snapshots
+/// and locks and exits the process when it gets SIGINT, SIGTERM or SIGHUP.  The
+/// exit status is 128 plus the signal number, like for a process
+/// killed by the signal.
+///
+/// This is for programs using the library, like `ent`; the library
+/// never installs signal handlers by itself.
+pub fn cleanup_on_signals() -> std::io::Result<()> {
+    use signal_hook::consts::signal::{SIGHUP, SIGINT, SIGTERM};
+    let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM, SIGHUP])?;
+    std::thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            cleanup();
+            std::process::exit(128 + signal);
+        }
+    });
+    Ok(())
+}
+
+/// Clean up everything that's registered, newest first, so worktrees
+/// are removed before the locks protecting them are released.
+fn cleanup() {
+    let registered = std::mem::take(&mut *resources());
+    for (_, resource) in registered.into_iter().rev() {
+        match resource {
+            Resource::Worktree { repo, path } => {
+                // Not taking the worktrees lock here: this process may
+                // be the one holding it.
+                let _ = repo
+                    .git()
+                    .args(["worktree", "remove", "--force", &path.to_string_lossy()])
+                    .output();
+            }
+            Resource::Lock(path) => {
+                let _ = std::fs::remove_file(path);
+            }
+            Resource::Directory(path) => {
+                let _ = std::fs::remove_dir_all(path);
+            }
+        }
+    }
+}
@@ -6,6 +6,13 @@ pub struct Comment {
     pub author: String,
     pub creation_time: chrono::DateTime<chrono::Local>,
     pub description: String,
+    /// The uuid of the Comment this Comment is a reply to.
+    pub in_reply_to: Option<String>,
+    /// The users who reacted to the Comment, by reaction.  Each reaction
+    /// is stored as the empty file `reactions/REACTION/USER`, so
+    /// concurrent reactions from different users merge cleanly.
+    pub reactions: std::collections::BTreeMap<String, Vec<String>>,
+    pub attachments: Vec<crate::attachment::Attachment>,
 
     #[ignored(PartialEq)]
     /// This is the directory that the comment lives in.  Only used
@@ -31,6 +38,10 @@ pub enum CommentError {
     EmptyDescription,
     #[error("stdin/stdout is not a terminal")]
     StdioIsNotTerminal,
+    #[error("invalid reaction {0:?}")]
+    InvalidReaction(String),
+    #[error(transparent)]
+    AttachmentError(#[from] crate::attachment::AttachmentError),
 }
 
 impl Comment {
@@ -38,6 +49,9 @@ impl Comment {
         let mut author: Option<String> = None;
         let mut creation_time: Option<chrono::DateTime<chrono::Local>> = None;
         let mut description: Option<String> = None;
+        let mut in_reply_to: Option<String> = None;
+        let mut reactions = std::collections::BTreeMap::<String, Vec<String>>::new();
+        let mut attachments = Vec::<crate::attachment::Attachment>::new();
 
         for direntry in (comment_dir.read_dir()?).flatten() {
             let file_name = direntry.file_name();
@@ -50,6 +64,14 @@ impl Comment {
                 creation_time = Some(raw_creation_time.into());
             } else if file_name == "description" {
                 description = Some(std::fs::read_to_string(direntry.path())?);
+            } else if file_name == "in_reply_to" {
+                in_reply_to = Some(String::from(
+                    std::fs::read_to_string(direntry.path())?.trim(),
+                ));
+            } else if file_name == "reactions" && direntry.metadata()?.is_dir() {
+                reactions = Self::read_reactions(&direntry.path())?;
+            } else if file_name == "attachments" && direntry.metadata()?.is_dir() {
+                attachments = crate::attachment::read_attachments(&direntry.path())?;
             } else {
                 #[cfg(feature = "log")]
                 debug!(
@@ -90,6 +112,9 @@ impl Comment {
             author,
             creation_time,
             description,
+            in_reply_to,
+            reactions,
+            attachments,
             dir: std::path::PathBuf::from(comment_dir),
         })
     }
@@ -98,6 +123,16 @@ impl Comment {
     pub fn new(
         issue: &crate::issue::Issue,
         description: &Option<String>,
+    ) -> Result<crate::comment::Comment, CommentError> {
+        Self::new_reply(issue, description, None)
+    }
+
+    /// Create a new Comment on the specified Issue, optionally as
+    /// a reply to another Comment on the Issue.  Commits.
+    pub fn new_reply(
+        issue: &crate::issue::Issue,
+        description: &Option<String>,
+        in_reply_to: Option<&str>,
     ) -> Result<crate::comment::Comment, CommentError> {
         let mut dir = std::path::PathBuf::from(&issue.dir);
         dir.push("comments");
@@ -115,6 +150,9 @@ impl Comment {
             author: crate::git::get_user_name_email(&dir)?,
             creation_time: chrono::Local::now(),
             description: String::from(""), // this will be set immediately below
+            in_reply_to: in_reply_to.map(String::from),
+            reactions: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
             dir: dir.clone(),
         };
 
@@ -139,22 +177,27 @@ impl Comment {
         let mut creation_time_file = std::fs::File::create(&creation_time_filename)?;
         write!(creation_time_file, "{}", comment.creation_time.to_rfc3339())?;
 
-        crate::git::add(&dir)?;
-        if crate::git::worktree_is_dirty(&dir.to_string_lossy())? {
-            crate::git::commit(
-                &dir,
-                &format!(
-                    "add comment {} on issue {}",
-                    comment.uuid,
-                    issue
-                        .dir
-                        .file_name()
-                        .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
-                        .to_string_lossy(),
-                ),
-            )?;
+        if let Some(in_reply_to) = &comment.in_reply_to {
+            let mut in_reply_to_filename = dir.clone();
+            in_reply_to_filename.push("in_reply_to");
+            let mut in_reply_to_file = std::fs::File::create(&in_reply_to_filename)?;
+            write!(in_reply_to_file, "{in_reply_to}")?;
         }
 
+        crate::git::add(&dir)?;
+        crate::git::commit(
+            &dir,
+            &format!(
+                "add comment {} on issue {}",
+                comment.uuid,
+                issue
+                    .dir
+                    .file_name()
+                    .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
+                    .to_string_lossy(),
+            ),
+        )?;
+
         Ok(comment)
     }
 
@@ -175,27 +218,205 @@ impl Comment {
         self.edit_description_file()?;
         let description_filename = self.description_filename();
         crate::git::add(&description_filename)?;
-        if crate::git::worktree_is_dirty(&self.dir.to_string_lossy())? {
-            crate::git::commit(
-                description_filename
-                    .parent()
-                    .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?,
-                &format!(
-                    "edit comment {} on issue FIXME", // FIXME: name the issue that the comment is on
-                    self.dir
-                        .file_name()
-                        .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
-                        .to_string_lossy()
-                ),
-            )?;
-            self.read_description()?;
+        crate::git::commit(
+            description_filename
+                .parent()
+                .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?,
+            &format!(
+                "edit comment {} on issue FIXME", // FIXME: name the issue that the comment is on
+                self.dir
+                    .file_name()
+                    .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
+                    .to_string_lossy()
+            ),
+        )?;
+        self.read_description()?;
+        Ok(())
+    }
+
+    /// Replace the description of the Comment with a tombstone, keeping
+    /// its author, creation time, replies and reactions.  Commits.
+    ///
+    /// The original description is still in the git history.
+    pub fn redact(&mut self) -> Result<(), CommentError> {
+        let description_filename = self.description_filename();
+        std::fs::write(&description_filename, crate::issue::REDACTED_DESCRIPTION)?;
+        self.description = String::from(crate::issue::REDACTED_DESCRIPTION);
+        crate::git::add(&description_filename)?;
+        crate::git::commit(&self.dir, &format!("redact comment {}", self.uuid))?;
+        Ok(())
+    }
+
+    /// Move the Comment to another Issue, keeping its author, creation
+    /// time and reactions.  A moved Comment is no longer a reply, since
+    /// the Comment it replied to stays behind.  Does not commit, the
+    /// caller commits both Issue directories.
+    pub(crate) fn move_to(&mut self, issue_dir: &std::path::Path) -> Result<(), CommentError> {
+        // The author and creation time of old comments may only be
+        // known from the git log, which doesn't follow the move, so
+        // write them down.
+        let mut author_file = std::fs::File::create(self.author_filename())?;
+        write!(author_file, "{}", &self.author)?;
+        let mut creation_time_file = std::fs::File::create(self.creation_time_filename())?;
+        write!(creation_time_file, "{}", self.creation_time.to_rfc3339())?;
+
+        if self.in_reply_to.take().is_some() {
+            let mut in_reply_to_filename = self.dir.clone();
+            in_reply_to_filename.push("in_reply_to");
+            std::fs::remove_file(&in_reply_to_filename)?;
         }
+
+        let mut new_dir = std::path::PathBuf::from(issue_dir);
+        new_dir.push("comments");
+        std::fs::create_dir_all(&new_dir)?;
+        new_dir.push(&self.uuid);
+        std::fs::rename(&self.dir, &new_dir)?;
+        self.dir = new_dir;
         Ok(())
     }
+
+    /// Add a reaction from a user to the Comment.  Adding a reaction
+    /// the user already made does nothing.  Commits.
+    pub fn add_reaction(&mut self, reaction: &str, user: &str) -> Result<(), CommentError> {
+        let reaction_filename = self.reaction_filename(reaction, user)?;
+        let users = self.reactions.entry(String::from(reaction)).or_default();
+        if users.iter().any(|u| u == user) {
+            return Ok(());
+        }
+        users.push(String::from(user));
+        users.sort();
+        if let Some(reaction_dir) = reaction_filename.parent() {
+            std::fs::create_dir_all(reaction_dir)?;
+        }
+        std::fs::File::create(&reaction_filename)?;
+        self.commit_reactions(&format!(
+            "add reaction {} by {} to comment {}",
+            reaction, user, self.uuid
+        ))
+    }
+
+    /// Remove a reaction of a user from the Comment.  Commits.
+    pub fn remove_reaction(&mut self, reaction: &str, user: &str) -> Result<(), CommentError> {
+        let reaction_filename = self.reaction_filename(reaction, user)?;
+        if let Some(users) = self.reactions.get_mut(reaction) {
+            users.retain(|u| u != user);
+            if users.is_empty() {
+                self.reactions.remove(reaction);
+            }
+        }
+        match std::fs::remove_file(&reaction_filename) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+            Ok(_) => (),
+        }
+        self.commit_reactions(&format!(
+            "remove reaction {} by {} from comment {}",
+            reaction, user, self.uuid
+        ))
+    }
+
+    /// Attach a file to the Comment.  If `use_blobs` is true the
+    /// contents go in the database's blob store (see `crate::attachment`).
+    /// Commits.
+    pub fn add_attachment(
+        &mut self,
+        file: &std::path::Path,
+        name: &str,
+        use_blobs: bool,
+    ) -> Result<crate::attachment::Attachment, CommentError> {
+        let db_dir = self.db_dir()?;
+        let attachment =
+            crate::attachment::add_attachment(&self.dir, &db_dir, file, name, use_blobs)?;
+        self.attachments.push(attachment.clone());
+        self.attachments.sort();
+        crate::git::add(&self.dir)?;
+        if use_blobs {
+            let mut blobs_dir = db_dir.clone();
+            blobs_dir.push("blobs");
+            crate::git::add(&blobs_dir)?;
+        }
+        crate::git::commit(
+            &self.dir,
+            &format!("add attachment {} to comment {}", name, self.uuid),
+        )?;
+        Ok(attachment)
+    }
+
+    /// Read the contents of an attachment of the Comment.
+    pub fn read_attachment(&self, name: &str) -> Result<Vec<u8>, CommentError> {
+        let Some(attachment) = self.attachments.iter().find(|a| a.name == name) else {
+            return Err(crate::attachment::AttachmentError::NotFound(String::from(name)).into());
+        };
+        Ok(crate::attachment::read_contents(
+            &self.dir,
+            &self.db_dir()?,
+            attachment,
+        )?)
+    }
 }
 
 // This is the private, internal API.
 impl Comment {
+    fn read_reactions(
+        dir: &std::path::Path,
+    ) -> Result<std::collections::BTreeMap<String, Vec<String>>, CommentError> {
+        let mut reactions = std::collections::BTreeMap::<String, Vec<String>>::new();
+        for reaction_direntry in (dir.read_dir()?).flatten() {
+            let reaction = crate::issue::Issue::tag_from_filename(
+                &reaction_direntry.file_name().to_string_lossy(),
+            )
+            .map_err(|_| CommentError::CommentParseError)?;
+            let mut users = Vec::<String>::new();
+            for user_direntry in (reaction_direntry.path().read_dir()?).flatten() {
+                users.push(
+                    crate::issue::Issue::tag_from_filename(
+                        &user_direntry.file_name().to_string_lossy(),
+                    )
+                    .map_err(|_| CommentError::CommentParseError)?,
+                );
+            }
+            if !users.is_empty() {
+                users.sort();
+                reactions.insert(reaction, users);
+            }
+        }
+        Ok(reactions)
+    }
+
+    fn reaction_filename(
+        &self,
+        reaction: &str,
+        user: &str,
+    ) -> Result<std::path::PathBuf, CommentError> {
+        if reaction.is_empty()
+            || reaction == "."
+            || reaction == ".."
+            || reaction.chars().any(|c| c.is_whitespace())
+        {
+            return Err(CommentError::InvalidReaction(String::from(reaction)));
+        }
+        let mut reaction_filename = std::path::PathBuf::from(&self.dir);
+        reaction_filename.push("reactions");
+        reaction_filename.push(crate::issue::Issue::tag_to_filename(reaction));
+        reaction_filename.push(crate::issue::Issue::tag_to_filename(user));
+        Ok(reaction_filename)
+    }
+
+    /// The top of the issues database, the comment lives in
+    /// `ISSUE/comments/UUID` below it.
+    fn db_dir(&self) -> Result<std::path::PathBuf, CommentError> {
+        match self.dir.ancestors().nth(3) {
+            Some(db_dir) => Ok(std::path::PathBuf::from(db_dir)),
+            None => Err(CommentError::CommentParseError),
+        }
+    }
+
+    fn commit_reactions(&self, commit_message: &str) -> Result<(), CommentError> {
+        crate::git::add(&self.dir)?;
+        crate::git::commit(&self.dir, commit_message)?;
+        Ok(())
+    }
+
     fn description_filename(&self) -> std::path::PathBuf {
         let mut description_filename = std::path::PathBuf::from(&self.dir);
         description_filename.push("description");
@@ -232,8 +453,6 @@ impl Comment {
             .spawn()?
             .wait_with_output()?;
         if !result.status.success() {
-            println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-            println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
             return Err(CommentError::EditorError);
         }
 
@@ -260,7 +479,9 @@ mod tests {
         let issue_uuid = "dd79c8cfb8beeacd0460429944b4ecbe";
         let comment_uuid = "9055dac36045fe36545bed7ae7b49347";
 
-        let issues = crate::Issues::new_from_git("entomologist-data-test-0001").unwrap();
+        let issues =
+            crate::Issues::new_from_git(&crate::Repo::current(), "entomologist-data-test-0001")
+                .unwrap();
         let issue = issues.get_issue(issue_uuid).unwrap();
         let comment = issue.get_comment(comment_uuid).unwrap();
 
@@ -273,6 +494,9 @@ mod tests {
             description: String::from(
                 "This is a comment on issue dd79c8cfb8beeacd0460429944b4ecbe\n\nIt has multiple lines\n",
             ),
+            in_reply_to: None,
+            reactions: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
             dir: std::path::PathBuf::from("ignored"),
         };
 
@@ -0,0 +1,163 @@
+//! Custom fields on issues.
+//!
+//! Each issue database can declare its own fields in the `[fields]`
+//! table of its `config.toml`, for example:
+//!
+//! ```toml
+//! [fields.component]
+//! type = "enum"
+//! values = ["power", "motor", "firmware"]
+//!
+//! [fields.revision]
+//! type = "string"
+//!
+//! [fields.severity]
+//! type = "int"
+//!
+//! [fields.found]
+//! type = "date"
+//!
+//! [fields.reviewer]
+//! type = "user"
+//! ```
+//!
+//! The value of a field is stored in the file `fields/NAME` in the
+//! issue's directory.
+
+use core::fmt;
+
+#[derive(Debug, thiserror::Error)]
+pub enum FieldError {
+    #[error("invalid field name {0:?}")]
+    InvalidName(String),
+    #[error("invalid integer {value:?} for field {field}")]
+    InvalidInt { field: String, value: String },
+    #[error("invalid date {value:?} for field {field}, expected YYYY-MM-DD")]
+    InvalidDate { field: String, value: String },
+    #[error("invalid value {value:?} for field {field}, expected one of {values:?}")]
+    InvalidEnum {
+        field: String,
+        value: String,
+        values: Vec<String>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+/// The type of a custom field, as declared in `config.toml`.
+pub enum FieldType {
+    String,
+    Enum { values: Vec<String> },
+    Int,
+    Date,
+    User,
+}
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+/// The value of a custom field on an issue.
+pub enum FieldValue {
+    String(String),
+    Enum(String),
+    Int(i64),
+    Date(chrono::NaiveDate),
+    User(String),
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldValue::String(s) => write!(f, "{s}"),
+            FieldValue::Enum(s) => write!(f, "{s}"),
+            FieldValue::Int(i) => write!(f, "{i}"),
+            FieldValue::Date(d) => write!(f, "{}", d.format("%Y-%m-%d")),
+            FieldValue::User(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+impl FieldType {
+    /// Parse the string representation of a value of this type.
+    pub fn parse(&self, field: &str, value: &str) -> Result<FieldValue, FieldError> {
+        match self {
+            FieldType::String => Ok(FieldValue::String(String::from(value))),
+            FieldType::Enum { values } => {
+                if values.iter().any(|v| v == value) {
+                    Ok(FieldValue::Enum(String::from(value)))
+                } else {
+                    Err(FieldError::InvalidEnum {
+                        field: String::from(field),
+                        value: String::from(value),
+                        values: values.clone(),
+                    })
+                }
+            }
+            FieldType::Int => match value.parse::<i64>() {
+                Ok(i) => Ok(FieldValue::Int(i)),
+                Err(_) => Err(FieldError::InvalidInt {
+                    field: String::from(field),
+                    value: String::from(value),
+                }),
+            },
+            FieldType::Date => match chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                Ok(d) => Ok(FieldValue::Date(d)),
+                Err(_) => Err(FieldError::InvalidDate {
+                    field: String::from(field),
+                    value: String::from(value),
+                }),
+            },
+            FieldType::User => Ok(FieldValue::User(String::from(value))),
+        }
+    }
+}
+
+/// Field names are used as filenames, so they're restricted to
+/// letters, digits, '-' and '_'.
+pub fn validate_name(name: &str) -> Result<(), FieldError> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(FieldError::InvalidName(String::from(name)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_field_values() {
+        let fields: std::collections::BTreeMap<String, FieldType> = toml::from_str(
+            r#"
+            component = { type = "enum", values = ["power", "motor"] }
+            severity = { type = "int" }
+            found = { type = "date" }
+            "#,
+        )
+        .unwrap();
+
+        let component = &fields["component"];
+        assert_eq!(
+            component.parse("component", "motor").unwrap(),
+            FieldValue::Enum(String::from("motor"))
+        );
+        assert!(component.parse("component", "wheels").is_err());
+
+        let severity = &fields["severity"];
+        assert_eq!(severity.parse("severity", "3").unwrap(), FieldValue::Int(3));
+        assert!(severity.parse("severity", "high").is_err());
+
+        let found = fields["found"].parse("found", "2025-07-24").unwrap();
+        assert_eq!(found.to_string(), "2025-07-24");
+    }
+
+    #[test]
+    fn field_names() {
+        assert!(validate_name("hardware_revision").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("../state").is_err());
+    }
+}
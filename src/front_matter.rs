@@ -0,0 +1,220 @@
+//! An Issue as a single document: its metadata in a TOML front-matter
+//! block between `+++` lines, followed by its description.
+//!
+//! ```text
+//! +++
+//! state = "InProgress"
+//! assignee = "seb"
+//! tags = ["bug", "firmware"]
+//! dependencies = ["7792b063eef6d33e7da5dc1856750c14"]
+//!
+//! [fields]
+//! component = "motor"
+//! +++
+//! Motor stalls at low speed
+//!
+//! Free-form description...
+//! ```
+//!
+//! This is what `ent edit --full` and `ent new --full` open in the
+//! editor.
+
+use std::io::{IsTerminal, Write};
+
+const DELIMITER: &str = "+++";
+const ERROR_PREFIX: &str = "# error: ";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FrontMatterError {
+    #[error(transparent)]
+    StdIoError(#[from] std::io::Error),
+    #[error(transparent)]
+    EnvVarError(#[from] std::env::VarError),
+    #[error("the document does not start with a front-matter block between '+++' lines")]
+    MissingFrontMatter,
+    #[error(transparent)]
+    TomlDeserializeError(#[from] toml::de::Error),
+    #[error(transparent)]
+    TomlSerializeError(#[from] toml::ser::Error),
+    #[error("Failed to run editor")]
+    EditorError,
+    #[error("stdin/stdout is not a terminal")]
+    StdioIsNotTerminal,
+}
+
+/// The metadata of an Issue, as written in the front matter.  All the
+/// values are strings as the user typed them, they're checked against
+/// the issues database by `IssuesMut`.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Metadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignee: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub dependencies: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<String>,
+    /// RFC 3339 timestamp.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub done_time: Option<String>,
+    /// Link targets by link type name.
+    #[serde(default)]
+    pub links: std::collections::BTreeMap<String, Vec<String>>,
+    /// Custom field values by field name.
+    #[serde(default)]
+    pub fields: std::collections::BTreeMap<String, String>,
+}
+
+impl From<&crate::template::Template> for Metadata {
+    fn from(template: &crate::template::Template) -> Self {
+        Self {
+            state: template.state.clone(),
+            assignee: template.assignee.clone(),
+            tags: template.tags.clone(),
+            fields: template.fields.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Render metadata and a description as a document.
+pub fn render(metadata: &Metadata, description: &str) -> Result<String, FrontMatterError> {
+    Ok(format!(
+        "{DELIMITER}\n{}{DELIMITER}\n{description}",
+        toml::to_string(metadata)?
+    ))
+}
+
+/// Split a document into its metadata and its description.
+pub fn parse(document: &str) -> Result<(Metadata, String), FrontMatterError> {
+    let Some(rest) = document.strip_prefix(&format!("{DELIMITER}\n")) else {
+        return Err(FrontMatterError::MissingFrontMatter);
+    };
+    let mut front_matter = String::new();
+    let mut lines = rest.split_inclusive('\n');
+    loop {
+        match lines.next() {
+            None => return Err(FrontMatterError::MissingFrontMatter),
+            Some(line) if line.trim_end() == DELIMITER => break,
+            Some(line) => front_matter.push_str(line),
+        }
+    }
+    let metadata: Metadata = toml::from_str(&front_matter)?;
+    let description: String = lines.collect();
+    Ok((metadata, description))
+}
+
+/// Let the user edit a document in their editor until `accept` accepts
+/// it.  When `accept` returns an error, the error is added to the top
+/// of the front matter as a comment and the editor is opened again.
+///
+/// Returns None if the user emptied the document, which means they
+/// changed their mind.
+pub fn edit<T>(
+    document: &str,
+    mut accept: impl FnMut(&str) -> Result<T, String>,
+) -> Result<Option<T>, FrontMatterError> {
+    if !std::io::stdin().is_terminal() || !std::io::stdout().is_terminal() {
+        return Err(FrontMatterError::StdioIsNotTerminal);
+    }
+    let mut file = tempfile::Builder::new()
+        .prefix("ent-")
+        .suffix(".md")
+        .tempfile()?;
+    file.write_all(document.as_bytes())?;
+    file.flush()?;
+
+    loop {
+        let editor = match std::env::var("EDITOR") {
+            Ok(editor) => editor,
+            Err(std::env::VarError::NotPresent) => String::from("vi"),
+            Err(e) => return Err(e.into()),
+        };
+        let result = std::process::Command::new(editor)
+            .arg(file.path().as_os_str())
+            .spawn()?
+            .wait_with_output()?;
+        if !result.status.success() {
+            return Err(FrontMatterError::EditorError);
+        }
+
+        let document = std::fs::read_to_string(file.path())?;
+        if document.trim().is_empty() {
+            return Ok(None);
+        }
+        match accept(&document) {
+            Ok(t) => return Ok(Some(t)),
+            Err(error) => {
+                std::fs::write(file.path(), add_error_comment(&document, &error))?;
+            }
+        }
+    }
+}
+
+/// Replace the error comments at the top of the front matter with
+/// a new one.
+fn add_error_comment(document: &str, error: &str) -> String {
+    let body = match document.strip_prefix(&format!("{DELIMITER}\n")) {
+        Some(rest) => rest,
+        None => document,
+    };
+    let body: String = body
+        .split_inclusive('\n')
+        .skip_while(|line| line.starts_with(ERROR_PREFIX))
+        .collect();
+    let error: String = error
+        .lines()
+        .map(|line| format!("{ERROR_PREFIX}{line}\n"))
+        .collect();
+    format!("{DELIMITER}\n{error}{body}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn front_matter_round_trip() {
+        let metadata = Metadata {
+            state: Some(String::from("InProgress")),
+            assignee: Some(String::from("seb")),
+            tags: vec![String::from("bug")],
+            dependencies: vec![String::from("7792b063eef6d33e7da5dc1856750c14")],
+            fields: std::collections::BTreeMap::from([(
+                String::from("component"),
+                String::from("motor"),
+            )]),
+            ..Default::default()
+        };
+        let description = "Motor stalls\n\n+++ is not a delimiter here\n";
+        let document = render(&metadata, description).unwrap();
+        assert_eq!(
+            parse(&document).unwrap(),
+            (metadata, String::from(description))
+        );
+    }
+
+    #[test]
+    fn invalid_front_matter() {
+        assert!(parse("no front matter\n").is_err());
+        assert!(parse("+++\nstate = \"New\"\nno end delimiter\n").is_err());
+        assert!(parse("+++\ncolour = \"red\"\n+++\ntitle\n").is_err());
+    }
+
+    #[test]
+    fn error_comments() {
+        let document = "+++\n# error: old error\nstate = \"New\"\n+++\ntitle\n";
+        assert_eq!(
+            add_error_comment(document, "new error"),
+            "+++\n# error: new error\nstate = \"New\"\n+++\ntitle\n"
+        );
+        assert!(parse(&add_error_comment(document, "new error")).is_ok());
+    }
+}
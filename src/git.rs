@@ -4,16 +4,249 @@ pub enum GitError {
     StdIoError(#[from] std::io::Error),
     #[error(transparent)]
     ParseIntError(#[from] std::num::ParseIntError),
+    #[error(transparent)]
+    Command(Box<CommandError>),
     #[error("Failed to fetch from remote {remote:?}:\n{error}")]
-    FetchError { remote: String, error: String },
-    #[error("Failed to merge {remote}/{branch} into {branch}:\n{error}")]
+    FetchError {
+        remote: String,
+        error: Box<CommandError>,
+    },
+    #[error(
+        "Failed to merge {remote}/{branch} into {branch}, a human needs to fix the mess in {branch:?}:\n{error}"
+    )]
     MergeError {
         remote: String,
         branch: String,
-        error: String,
+        error: Box<CommandError>,
+    },
+    #[error("Failed to push {branch} to remote {remote:?}:\n{error}")]
+    PushError {
+        remote: String,
+        branch: String,
+        error: Box<CommandError>,
+    },
+    #[error("Failed to rewrite the history of {branch}:\n{error}")]
+    PurgeError {
+        branch: String,
+        error: Box<CommandError>,
     },
-    #[error("Oops, something went wrong")]
-    Oops,
+    #[error("Failed to commit {message:?}:\n{error}")]
+    CommitError {
+        message: String,
+        error: Box<CommandError>,
+    },
+    #[error(
+        "Failed to commit {message:?}, it conflicts with a concurrent change to {}",
+        .paths.join(", ")
+    )]
+    ConcurrentChangeConflict { message: String, paths: Vec<String> },
+    #[error("Failed to commit {message:?}: the branch kept changing, too many concurrent writers")]
+    TooManyConcurrentWriters { message: String },
+    #[error("Unexpected output from {command}: {output:?}")]
+    UnexpectedOutput { command: String, output: String },
+    #[error(transparent)]
+    Lock(Box<crate::lock::LockError>),
+}
+
+impl From<crate::lock::LockError> for GitError {
+    fn from(error: crate::lock::LockError) -> Self {
+        GitError::Lock(Box::new(error))
+    }
+}
+
+impl GitError {
+    /// The git command that failed, if this error is about one.
+    pub fn command_error(&self) -> Option<&CommandError> {
+        match self {
+            GitError::Command(error)
+            | GitError::FetchError { error, .. }
+            | GitError::MergeError { error, .. }
+            | GitError::PushError { error, .. }
+            | GitError::PurgeError { error, .. }
+            | GitError::CommitError { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+
+    /// What kind of failure this is, `FailureKind::Other` if it's not
+    /// one of the common ones.
+    pub fn kind(&self) -> FailureKind {
+        match (self, self.command_error()) {
+            (GitError::ConcurrentChangeConflict { .. }, _) => FailureKind::MergeConflict,
+            (_, Some(error)) => error.kind(),
+            (_, None) => FailureKind::Other,
+        }
+    }
+}
+
+/// The common reasons git commands fail.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailureKind {
+    /// The directory is not in a git repo.
+    NotARepo,
+    /// The branch is already checked out in another worktree.
+    CheckedOutElsewhere,
+    /// Merging failed because of conflicting changes.
+    MergeConflict,
+    /// The remote refused the push, usually because it has changes we
+    /// don't.
+    PushRejected,
+    /// Git doesn't know who the user is, `user.name` or `user.email`
+    /// isn't configured.
+    NoIdentity,
+    Other,
+}
+
+/// A git command that ran and failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandError {
+    /// The command line, starting with "git".
+    pub command: Vec<String>,
+    /// The directory the command ran in, if not the current directory.
+    pub dir: Option<std::path::PathBuf>,
+    /// The exit status, None if git was killed by a signal.
+    pub status: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl CommandError {
+    pub fn new(command: &std::process::Command, output: &std::process::Output) -> Self {
+        let mut command_line = vec![command.get_program().to_string_lossy().into_owned()];
+        command_line.extend(
+            command
+                .get_args()
+                .map(|arg| arg.to_string_lossy().into_owned()),
+        );
+        Self {
+            command: command_line,
+            dir: command.get_current_dir().map(std::path::PathBuf::from),
+            status: output.status.code(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+    }
+
+    /// The git subcommand, like "push".
+    fn subcommand(&self) -> Option<&str> {
+        self.command.get(1).map(String::as_str)
+    }
+
+    pub fn kind(&self) -> FailureKind {
+        let stderr = &self.stderr;
+        if stderr.contains("not a git repository") {
+            FailureKind::NotARepo
+        } else if stderr.contains("is already checked out at")
+            || stderr.contains("is already used by worktree")
+        {
+            FailureKind::CheckedOutElsewhere
+        } else if stderr.contains("Please tell me who you are")
+            || stderr.contains("Author identity unknown")
+            || stderr.contains("empty ident name")
+            || (self.subcommand() == Some("config")
+                && self.command.iter().any(|arg| arg.starts_with("user."))
+                && self.status == Some(1))
+        {
+            FailureKind::NoIdentity
+        } else if self.stdout.contains("CONFLICT")
+            || stderr.contains("CONFLICT")
+            || (self.subcommand() == Some("merge-tree") && self.status == Some(1))
+        {
+            FailureKind::MergeConflict
+        } else if self.subcommand() == Some("push")
+            && (stderr.contains("[rejected]") || stderr.contains("[remote rejected]"))
+        {
+            FailureKind::PushRejected
+        } else {
+            FailureKind::Other
+        }
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "`{}`", self.command.join(" "))?;
+        if let Some(dir) = &self.dir {
+            write!(f, " in {}", dir.display())?;
+        }
+        match self.status {
+            Some(status) => write!(f, " failed with exit status {status}")?,
+            None => write!(f, " was killed")?,
+        }
+        let output = match self.stderr.trim() {
+            "" => self.stdout.trim(),
+            stderr => stderr,
+        };
+        if !output.is_empty() {
+            write!(f, ":\n{output}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl From<CommandError> for GitError {
+    fn from(error: CommandError) -> Self {
+        GitError::Command(Box::new(error))
+    }
+}
+
+/// Run a git command, returning its output if it succeeded and a
+/// CommandError if it failed.
+pub fn run(command: &mut std::process::Command) -> Result<std::process::Output, GitError> {
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(CommandError::new(command, &output).into());
+    }
+    Ok(output)
+}
+
+/// Run a git command, returning its trimmed stdout if it succeeded.
+pub fn run_stdout(command: &mut std::process::Command) -> Result<String, GitError> {
+    let output = run(command)?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Run a git command, feeding it `input` on stdin, and return its
+/// trimmed stdout if it succeeded.
+pub fn run_with_input(
+    command: &mut std::process::Command,
+    input: &[u8],
+) -> Result<String, GitError> {
+    let mut child = command
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        use std::io::Write;
+        stdin.write_all(input)?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(CommandError::new(command, &output).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Run git in `dir`, or in the repo if `dir` is in a `GitDbMut`
+/// snapshot, which isn't a git worktree.
+fn git_in(dir: &std::path::Path) -> std::process::Command {
+    if let Some(writer) = crate::gitdb::writer::find(dir) {
+        return writer.repo().git();
+    }
+    let mut command = std::process::Command::new("git");
+    command.current_dir(dir);
+    command
+}
+
+/// The repo that `dir` is in.
+fn repo_of(dir: &std::path::Path) -> crate::Repo {
+    match crate::gitdb::writer::find(dir) {
+        Some(writer) => writer.repo().clone(),
+        None => crate::Repo::new(dir),
+    }
 }
 
 #[derive(Debug)]
@@ -22,62 +255,49 @@ pub enum GitError {
 /// when the `Worktree` struct is dropped.
 pub struct Worktree {
     path: tempfile::TempDir,
+    repo: crate::Repo,
 }
 
 impl Drop for Worktree {
     fn drop(&mut self) {
-        let result = std::process::Command::new("git")
-            .args([
-                "worktree",
-                "remove",
-                "--force",
-                &self.path.path().to_string_lossy(),
-            ])
-            .output();
-        match result {
-            Err(e) => {
-                println!("failed to run git: {e:#?}");
-            }
-            Ok(result) => {
-                if !result.status.success() {
-                    println!("failed to remove git worktree: {result:#?}");
-                }
-            }
+        let _result = run(self.repo.git().args([
+            "worktree",
+            "remove",
+            "--force",
+            &self.path.path().to_string_lossy(),
+        ]));
+        #[cfg(feature = "log")]
+        if let Err(e) = _result {
+            log::warn!("failed to remove git worktree: {e}");
         }
     }
 }
 
 impl Worktree {
-    pub fn new(branch: &str) -> Result<Worktree, GitError> {
+    pub fn new(repo: &crate::Repo, branch: &str) -> Result<Worktree, GitError> {
         let path = tempfile::tempdir()?;
-        let result = std::process::Command::new("git")
-            .args(["worktree", "add", &path.path().to_string_lossy(), branch])
-            .output()?;
-        if !result.status.success() {
-            println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-            println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-            return Err(GitError::Oops);
-        }
-        Ok(Self { path })
+        run(repo
+            .git()
+            .args(["worktree", "add", &path.path().to_string_lossy(), branch]))?;
+        Ok(Self {
+            path,
+            repo: repo.clone(),
+        })
     }
 
-    pub fn new_detached(branch: &str) -> Result<Worktree, GitError> {
+    pub fn new_detached(repo: &crate::Repo, branch: &str) -> Result<Worktree, GitError> {
         let path = tempfile::tempdir()?;
-        let result = std::process::Command::new("git")
-            .args([
-                "worktree",
-                "add",
-                "--detach",
-                &path.path().to_string_lossy(),
-                branch,
-            ])
-            .output()?;
-        if !result.status.success() {
-            println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-            println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-            return Err(GitError::Oops);
-        }
-        Ok(Self { path })
+        run(repo.git().args([
+            "worktree",
+            "add",
+            "--detach",
+            &path.path().to_string_lossy(),
+            branch,
+        ]))?;
+        Ok(Self {
+            path,
+            repo: repo.clone(),
+        })
     }
 
     pub fn path(&self) -> &std::path::Path {
@@ -86,39 +306,69 @@ impl Worktree {
 }
 
 pub fn checkout_branch_in_worktree(
+    repo: &crate::Repo,
     branch: &str,
     worktree_dir: &std::path::Path,
 ) -> Result<(), GitError> {
-    let result = std::process::Command::new("git")
-        .args(["worktree", "add", &worktree_dir.to_string_lossy(), branch])
-        .output()?;
-    if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
-    }
+    run(repo
+        .git()
+        .args(["worktree", "add", &worktree_dir.to_string_lossy(), branch]))?;
     Ok(())
 }
 
-pub fn git_worktree_prune() -> Result<(), GitError> {
-    let result = std::process::Command::new("git")
-        .args(["worktree", "prune"])
-        .output()?;
-    if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
-    }
+pub fn git_worktree_prune(repo: &crate::Repo) -> Result<(), GitError> {
+    run(repo.git().args(["worktree", "prune"]))?;
     Ok(())
 }
 
-pub fn git_branch_exists(branch: &str) -> Result<bool, GitError> {
-    let result = std::process::Command::new("git")
-        .args(["show-ref", "--quiet", branch])
-        .output()?;
+pub fn git_branch_exists(repo: &crate::Repo, branch: &str) -> Result<bool, GitError> {
+    let result = repo.git().args(["show-ref", "--quiet", branch]).output()?;
     Ok(result.status.success())
 }
 
+/// The git dir of the repo, shared by all its worktrees.
+pub fn git_common_dir(repo: &crate::Repo) -> Result<std::path::PathBuf, GitError> {
+    Ok(std::path::PathBuf::from(run_stdout(repo.git().args([
+        "rev-parse",
+        "--path-format=absolute",
+        "--git-common-dir",
+    ]))?))
+}
+
+/// The private git dir of the worktree in `dir`.
+pub fn git_dir(dir: &std::path::Path) -> Result<std::path::PathBuf, GitError> {
+    Ok(std::path::PathBuf::from(run_stdout(git_in(dir).args([
+        "rev-parse",
+        "--path-format=absolute",
+        "--git-dir",
+    ]))?))
+}
+
+/// The commit time of a git tag in the repo, or None if there's no
+/// such tag.
+pub fn tag_time(
+    repo: &crate::Repo,
+    tag: &str,
+) -> Result<Option<chrono::DateTime<chrono::Local>>, GitError> {
+    let result = repo
+        .git()
+        .args([
+            "log",
+            "-1",
+            "--format=%cI",
+            &format!("refs/tags/{tag}"),
+            "--",
+        ])
+        .output()?;
+    if !result.status.success() {
+        return Ok(None);
+    }
+    match chrono::DateTime::parse_from_rfc3339(String::from_utf8_lossy(&result.stdout).trim()) {
+        Ok(time) => Ok(Some(time.with_timezone(&chrono::Local))),
+        Err(_) => Ok(None),
+    }
+}
+
 pub fn worktree_is_dirty(dir: &str) -> Result<bool, GitError> {
     // `git status --porcelain` prints a terse list of files added or
     // modified (both staged and not), and new untracked files.  So if
@@ -130,140 +380,273 @@ pub fn worktree_is_dirty(dir: &str) -> Result<bool, GitError> {
     Ok(!result.stdout.is_empty())
 }
 
+/// The directory containing `file`, to run git in.
+fn parent_dir(file: &std::path::Path) -> Result<&std::path::Path, GitError> {
+    Ok(file
+        .parent()
+        .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?)
+}
+
+/// Include `file` (a file or a directory, which may have been removed)
+/// in the next `commit()` in its directory.  In a `GitDbMut` this only
+/// records the path, see `crate::gitdb::writer`.
 pub fn add(file: &std::path::Path) -> Result<(), GitError> {
-    let result = std::process::Command::new("git")
-        .args(["add", &file.to_string_lossy()])
-        .current_dir(
-            file.parent()
-                .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?,
-        )
-        .output()?;
-    if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
+    if crate::gitdb::writer::stage(file) {
+        return Ok(());
     }
+    run(git_in(parent_dir(file)?).args(["add", &file.to_string_lossy()]))?;
     Ok(())
 }
 
+/// Put `file` back the way it is in the last commit.
 pub fn restore_file(file: &std::path::Path) -> Result<(), GitError> {
-    let result = std::process::Command::new("git")
-        .args(["restore", &file.to_string_lossy()])
-        .current_dir(
-            file.parent()
-                .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?,
-        )
-        .output()?;
-    if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
+    if let Some(writer) = crate::gitdb::writer::find(file) {
+        return writer.restore(file);
     }
+    run(git_in(parent_dir(file)?).args([
+        "restore",
+        "--source=HEAD",
+        "--",
+        &file.to_string_lossy(),
+    ]))?;
     Ok(())
 }
 
-pub fn commit(dir: &std::path::Path, msg: &str) -> Result<(), GitError> {
-    let result = std::process::Command::new("git")
-        .args(["commit", "-m", msg])
-        .current_dir(dir)
-        .output()?;
+/// Run git in `dir`, returning its trimmed stdout, or a CommitError.
+fn git_output(dir: &std::path::Path, args: &[&str], msg: &str) -> Result<String, GitError> {
+    let mut command = git_in(dir);
+    command.args(args);
+    let result = command.output()?;
     if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
+        return Err(GitError::CommitError {
+            message: String::from(msg),
+            error: Box::new(CommandError::new(&command, &result)),
+        });
+    }
+    Ok(String::from_utf8_lossy(&result.stdout).trim().to_string())
+}
+
+/// Commit the changes added (with `add()`) in the directory `dir`, if
+/// there are any.
+///
+/// In a `GitDbMut` the commit is built with plumbing on top of the
+/// current tip of the branch, without using the worktree, and a
+/// concurrent change to the same files is a ConcurrentChangeConflict,
+/// see `crate::gitdb::writer`.  Anywhere else the commit is made on
+/// the branch checked out in `dir`, with a compare-and-swap update so
+/// a concurrent commit is never overwritten.
+pub fn commit(dir: &std::path::Path, msg: &str) -> Result<(), GitError> {
+    if let Some(writer) = crate::gitdb::writer::find(dir) {
+        return writer.commit(msg);
     }
+    let tree = git_output(dir, &["write-tree"], msg)?;
+    let head = git_output(dir, &["rev-parse", "HEAD", "HEAD^{tree}"], msg)?;
+    let Some((parent, parent_tree)) = head.split_once('\n') else {
+        return Err(GitError::UnexpectedOutput {
+            command: String::from("git rev-parse"),
+            output: head,
+        });
+    };
+    if tree == parent_tree {
+        // Nothing to commit.
+        return Ok(());
+    }
+    let commit = git_output(dir, &["commit-tree", &tree, "-p", parent, "-m", msg], msg)?;
+    git_output(
+        dir,
+        &["update-ref", "-m", msg, "HEAD", &commit, parent],
+        msg,
+    )?;
     Ok(())
 }
 
 pub fn git_commit_file(file: &std::path::Path) -> Result<(), GitError> {
-    let mut git_dir = std::path::PathBuf::from(file);
-    git_dir.pop();
+    let git_dir = parent_dir(file)?;
+    let file_name = file
+        .file_name()
+        .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
+        .to_string_lossy();
 
-    let result = std::process::Command::new("git")
-        .args([
-            "add",
-            &file
+    run(git_in(git_dir).args(["add", &file_name]))?;
+    run(git_in(git_dir).args([
+        "commit",
+        "-m",
+        &format!(
+            "update '{}' in issue {}",
+            file_name,
+            git_dir
                 .file_name()
                 .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
-                .to_string_lossy(),
-        ])
-        .current_dir(&git_dir)
-        .output()?;
-    if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
-    }
+                .to_string_lossy()
+        ),
+    ]))?;
 
-    let result = std::process::Command::new("git")
-        .args([
-            "commit",
-            "-m",
-            &format!(
-                "update '{}' in issue {}",
-                file.file_name()
-                    .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
-                    .to_string_lossy(),
-                git_dir
-                    .file_name()
-                    .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
-                    .to_string_lossy()
-            ),
-        ])
-        .current_dir(&git_dir)
-        .output()?;
-    if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
-    }
+    Ok(())
+}
 
+/// Merge `rev` into the branch in `dir`, with the commit message `msg`
+/// unless it's a fast-forward.  Conflicts are a Command error whose
+/// kind is `FailureKind::MergeConflict`.  In a `GitDbMut` the merge is
+/// done with plumbing and leaves the branch alone if it fails, anywhere
+/// else it's a `git merge`.
+pub fn merge(dir: &std::path::Path, rev: &str, msg: &str) -> Result<(), GitError> {
+    if let Some(writer) = crate::gitdb::writer::find(dir) {
+        return writer.merge(rev, msg);
+    }
+    run(git_in(dir).args(["merge", "-m", msg, rev]))?;
     Ok(())
 }
 
 fn fetch(dir: &std::path::Path, remote: &str) -> Result<(), GitError> {
-    let result = std::process::Command::new("git")
-        .args(["fetch", remote])
-        .current_dir(dir)
-        .output()?;
-    if !result.status.success() {
-        return Err(GitError::FetchError {
+    match run(git_in(dir).args(["fetch", remote])) {
+        Err(GitError::Command(error)) => Err(GitError::FetchError {
             remote: String::from(remote),
-            error: String::from_utf8_lossy(&result.stderr).into_owned(),
-        });
+            error,
+        }),
+        Err(e) => Err(e),
+        Ok(_) => Ok(()),
     }
-    Ok(())
 }
 
 pub fn get_user_name_email(dir: &std::path::Path) -> Result<String, GitError> {
-    let result = std::process::Command::new("git")
-        .args(["config", "user.name"])
-        .current_dir(dir)
-        .output()?;
+    let user_name = run_stdout(git_in(dir).args(["config", "user.name"]))?;
+    let user_email = run_stdout(git_in(dir).args(["config", "user.email"]))?;
+    Ok(format!("{user_name} <{user_email}>"))
+}
+
+/// What `sync()` brings in from the remote and sends out to it: the
+/// changes on each side since the branches last met.
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncSummary {
+    pub incoming: crate::changes::Changes,
+    pub outgoing: crate::changes::Changes,
+}
+
+impl SyncSummary {
+    /// Summarize the changes between the `local` and `remote` commits
+    /// of a branch, either of which may not exist.
+    fn new(
+        repo: &crate::Repo,
+        local: Option<&str>,
+        remote: Option<&str>,
+    ) -> Result<Self, GitError> {
+        let base = match (local, remote) {
+            (Some(local), Some(remote)) => resolve(repo.git().args(["merge-base", local, remote]))?,
+            _ => None,
+        };
+        let changes = |tip: Option<&str>| match tip {
+            Some(tip) => crate::changes::Changes::between(repo, base.as_deref(), tip),
+            None => Ok(crate::changes::Changes::default()),
+        };
+        Ok(Self {
+            incoming: changes(remote)?,
+            outgoing: changes(local)?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncOptions {
+    /// Push the merged branch back to the remote.  Without this the
+    /// merge is left in the local branch, for review.
+    pub push: bool,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self { push: true }
+    }
+}
+
+/// Run a git command that prints an object id, returning None if it
+/// fails.
+pub(crate) fn resolve(command: &mut std::process::Command) -> Result<Option<String>, GitError> {
+    let result = command.output()?;
     if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
+        return Ok(None);
     }
-    let user_name_string = String::from_utf8_lossy(&result.stdout);
-    let user_name = user_name_string.lines().next().ok_or(GitError::Oops)?;
+    Ok(Some(
+        String::from_utf8_lossy(&result.stdout).trim().to_string(),
+    ))
+}
 
-    let result = std::process::Command::new("git")
-        .args(["config", "user.email"])
-        .current_dir(dir)
-        .output()?;
+/// The commit a ref points at, or None if there's no such ref.
+pub(crate) fn resolve_ref(repo: &crate::Repo, refname: &str) -> Result<Option<String>, GitError> {
+    resolve(repo.git().args([
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        &format!("{refname}^{{commit}}"),
+    ]))
+}
+
+/// All the values of a git config key in the repo, none if it's not
+/// set.
+fn config_values(repo: &crate::Repo, key: &str) -> Result<Vec<String>, GitError> {
+    let mut command = repo.git();
+    command.args(["config", "--get-all", key]);
+    let result = command.output()?;
     if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
+        // Exit status 1 means the key isn't set.
+        if result.status.code() == Some(1) {
+            return Ok(Vec::new());
+        }
+        return Err(CommandError::new(&command, &result).into());
     }
-    let user_email_string = String::from_utf8_lossy(&result.stdout);
-    let user_email = user_email_string.lines().next().ok_or(GitError::Oops)?;
+    Ok(String::from_utf8_lossy(&result.stdout)
+        .lines()
+        .map(String::from)
+        .collect())
+}
 
-    Ok(format!("{user_name} <{user_email}>"))
+/// All the remotes of the repo.
+pub fn remotes(repo: &crate::Repo) -> Result<Vec<String>, GitError> {
+    Ok(run_stdout(repo.git().arg("remote"))?
+        .lines()
+        .map(String::from)
+        .collect())
+}
+
+/// The remotes to sync with when none is named, from the multi-valued
+/// `entomologist.remote` git config of the repo, or origin.
+pub fn default_sync_remotes(repo: &crate::Repo) -> Result<Vec<String>, GitError> {
+    let remotes = config_values(repo, "entomologist.remote")?;
+    if remotes.is_empty() {
+        return Ok(vec![String::from("origin")]);
+    }
+    Ok(remotes)
+}
+
+/// The name of the issues branch `branch` on `remote`: the
+/// `remote.REMOTE.entomologistBranch` git config of the repo, or
+/// `branch` itself.
+pub fn remote_branch_name(
+    repo: &crate::Repo,
+    remote: &str,
+    branch: &str,
+) -> Result<String, GitError> {
+    let names = config_values(repo, &format!("remote.{remote}.entomologistBranch"))?;
+    Ok(match names.last() {
+        Some(name) => name.clone(),
+        None => String::from(branch),
+    })
+}
+
+/// Sync `branch` with the remote, in the `GitDbMut` snapshot (or the
+/// worktree) in `dir`, with the default SyncOptions.
+pub fn sync(dir: &std::path::Path, remote: &str, branch: &str) -> Result<SyncSummary, GitError> {
+    sync_with_options(dir, remote, branch, &SyncOptions::default())
 }
 
-pub fn sync(dir: &std::path::Path, remote: &str, branch: &str) -> Result<(), GitError> {
+/// Sync `branch` with the remote, in the `GitDbMut` snapshot (or the
+/// worktree) in `dir`.  The branch is called `remote_branch_name()` on
+/// the remote.
+pub fn sync_with_options(
+    dir: &std::path::Path,
+    remote: &str,
+    branch: &str,
+    options: &SyncOptions,
+) -> Result<SyncSummary, GitError> {
     // We do all the work in a directory that's (FIXME) hopefully a
     // worktree.  If anything goes wrong we just fail out and ask the
     // human to fix it by hand :-/
@@ -273,157 +656,396 @@ pub fn sync(dir: &std::path::Path, remote: &str, branch: &str) -> Result<(), Git
 
     fetch(dir, remote)?;
 
-    // FIXME: Possible things to add:
-    // * `git log -p` shows diff
-    // * `git log --numstat` shows machine-readable diffstat
-
-    let have_local_branch = git_branch_exists(branch)?;
-    let have_remote_branch = git_branch_exists(&format!("{remote}/{branch}"))?;
-
-    match (have_local_branch, have_remote_branch) {
-        (true, true) => {
-            // Both local and remote branches exist.
-            // Show what we just fetched from the remote.
-            let result = std::process::Command::new("git")
-                .args([
-                    "log",
-                    "--no-merges",
-                    "--pretty=format:%an: %s",
-                    &format!("{remote}/{branch}"),
-                    &format!("^{branch}"),
-                ])
-                .current_dir(dir)
-                .output()?;
-            if result.status.success() && !result.stdout.is_empty() {
-                println!("Changes fetched from remote {remote}:");
-                println!("{}", &String::from_utf8_lossy(&result.stdout));
-                println!();
-            }
-            // Show what we are about to push to the remote.
-            let result = std::process::Command::new("git")
-                .args([
-                    "log",
-                    "--no-merges",
-                    "--pretty=format:%an: %s",
-                    branch,
-                    &format!("^{remote}/{branch}"),
-                ])
-                .current_dir(dir)
-                .output()?;
-            if result.status.success() && !result.stdout.is_empty() {
-                println!("Changes to push to remote {remote}:");
-                println!("{}", &String::from_utf8_lossy(&result.stdout));
-                println!();
-            }
-        }
+    let repo = repo_of(dir);
+    let remote_branch_name = remote_branch_name(&repo, remote, branch)?;
+    let remote_branch = format!("{remote}/{remote_branch_name}");
+    let local_tip = resolve_ref(&repo, &format!("refs/heads/{branch}"))?;
+    let remote_tip = resolve_ref(&repo, &format!("refs/remotes/{remote_branch}"))?;
+    let summary = SyncSummary::new(&repo, local_tip.as_deref(), remote_tip.as_deref())?;
 
-        (true, false) => {
-            // Local branch exists, remote does not.
-            // Show what we are about to push to the remote.
-            let result = std::process::Command::new("git")
-                .args(["log", "--no-merges", "--pretty=format:%an: %s", branch])
-                .current_dir(dir)
-                .output()?;
-            if result.status.success() && !result.stdout.is_empty() {
-                println!("Changes to push to remote {remote}:");
-                println!("{}", &String::from_utf8_lossy(&result.stdout));
-                println!();
+    // Merge remote branch into local.
+    if remote_tip.is_some() {
+        match merge(
+            dir,
+            &remote_branch,
+            &format!("Merge {remote_branch} into {branch}"),
+        ) {
+            Err(GitError::Command(error)) => {
+                return Err(GitError::MergeError {
+                    remote: String::from(remote),
+                    branch: String::from(branch),
+                    error,
+                });
             }
+            Err(e) => return Err(e),
+            Ok(_) => (),
         }
+    }
 
-        (false, true) => {
-            // Local branch does not exist, remote branch does exist.
-            // Show what we are about to push to the remote.
-            let result = std::process::Command::new("git")
-                .args([
-                    "log",
-                    "--no-merges",
-                    "--pretty=format:%an: %s",
-                    &format!("{remote}/{branch}"),
-                ])
-                .current_dir(dir)
-                .output()?;
-            if result.status.success() && !result.stdout.is_empty() {
-                println!("Changes to push to remote {remote}:");
-                println!("{}", &String::from_utf8_lossy(&result.stdout));
-                println!();
-            }
-        }
+    if !options.push {
+        return Ok(summary);
+    }
+
+    // Push merged branch to remote.
+    match run(git_in(dir).args([
+        "push",
+        remote,
+        &format!("refs/heads/{branch}:refs/heads/{remote_branch_name}"),
+    ])) {
+        Err(GitError::Command(error)) => Err(GitError::PushError {
+            remote: String::from(remote),
+            branch: String::from(branch),
+            error,
+        }),
+        Err(e) => Err(e),
+        Ok(_) => Ok(summary),
+    }
+}
+
+/// What `sync()` would bring in from the remote and send out to it
+/// right now, without changing the branch or the remote-tracking
+/// branch.  The remote's commits are fetched into the object database,
+/// but no refs are updated.
+pub fn sync_preview(
+    repo: &crate::Repo,
+    remote: &str,
+    branch: &str,
+) -> Result<SyncSummary, GitError> {
+    let fetch_error = |e: GitError| match e {
+        GitError::Command(error) => GitError::FetchError {
+            remote: String::from(remote),
+            error,
+        },
+        e => e,
+    };
+    let refname = format!("refs/heads/{}", remote_branch_name(repo, remote, branch)?);
+    let listing =
+        run_stdout(repo.git().args(["ls-remote", remote, &refname])).map_err(fetch_error)?;
+    let remote_tip = listing
+        .lines()
+        .find_map(|line| match line.split_once('\t') {
+            Some((id, name)) if name == refname => Some(String::from(id)),
+            _ => None,
+        });
+    if remote_tip.is_some() {
+        // An empty refmap keeps fetch from updating the remote-tracking
+        // branch.
+        run(repo.git().args([
+            "fetch",
+            "--quiet",
+            "--no-tags",
+            "--no-write-fetch-head",
+            "--refmap=",
+            remote,
+            &refname,
+        ]))
+        .map_err(fetch_error)?;
+    }
+    let local_tip = resolve_ref(repo, &format!("refs/heads/{branch}"))?;
+    SyncSummary::new(repo, local_tip.as_deref(), remote_tip.as_deref())
+}
+
+/// How a local branch compares to the same branch on a remote.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RemoteStatus {
+    pub remote: String,
+    /// The name of the issues branch on the remote.
+    pub branch: String,
+    /// False if the remote doesn't have the branch (as far as we know).
+    pub has_branch: bool,
+    /// How many commits the local branch has that the remote doesn't.
+    pub ahead: usize,
+    /// How many commits the remote has that the local branch doesn't.
+    pub behind: usize,
+}
+
+/// Compare `branch` with the issues branch on each of the repo's
+/// remotes (see `remote_branch_name()`), as of the last fetch from
+/// each.
+pub fn remote_status(repo: &crate::Repo, branch: &str) -> Result<Vec<RemoteStatus>, GitError> {
+    let local_tip = resolve_ref(repo, &format!("refs/heads/{branch}"))?;
+    let mut statuses = Vec::<RemoteStatus>::new();
+    for remote in remotes(repo)? {
+        let remote_branch_name = remote_branch_name(repo, &remote, branch)?;
+        let remote_tip = resolve_ref(repo, &format!("refs/remotes/{remote}/{remote_branch_name}"))?;
+        let count = |revs: &[&str]| -> Result<usize, GitError> {
+            let mut args = vec!["rev-list", "--count"];
+            args.extend(revs);
+            Ok(run_stdout(repo.git().args(&args))?.parse()?)
+        };
+        let (ahead, behind) = match (&local_tip, &remote_tip) {
+            (Some(local), Some(remote)) => (
+                count(&[local, &format!("^{remote}")])?,
+                count(&[remote, &format!("^{local}")])?,
+            ),
+            (Some(local), None) => (count(&[local])?, 0),
+            (None, Some(remote)) => (0, count(&[remote])?),
+            (None, None) => (0, 0),
+        };
+        statuses.push(RemoteStatus {
+            remote,
+            branch: remote_branch_name,
+            has_branch: remote_tip.is_some(),
+            ahead,
+            behind,
+        });
+    }
+    Ok(statuses)
+}
+
+/// Rewrite the whole history of `branch` so that no commit contains the
+/// original contents of `path` (relative to the top of the branch).  If
+/// `replacement` is None, `path` (a file or a directory) is removed from
+/// every commit, otherwise every version of the file `path` is replaced
+/// with `replacement`.  A `*` first component of `path` stands for
+/// every top-level directory, so `*/comments/UUID` finds a comment in
+/// whichever issue it was in at the time.  `dir` must be a GitDbMut
+/// snapshot of `branch` with nothing left to commit.
+///
+/// Each commit is remade with `commit-tree`, keeping its author,
+/// committer and message, and the branch is moved to the new history
+/// with a compare-and-swap ref update.  The branch's reflog is expired,
+/// but nothing else in the repo is touched: the original contents stay
+/// in the object database until the repo is garbage-collected (`git gc
+/// --prune=now`), and in remotes and other clones until the rewritten
+/// branch is force-pushed and they reset to it.
+pub fn purge_from_history(
+    repo: &crate::Repo,
+    dir: &std::path::Path,
+    branch: &str,
+    path: &str,
+    replacement: Option<&str>,
+) -> Result<(), GitError> {
+    let purge_error = |e: GitError| match e {
+        GitError::Command(error) => GitError::PurgeError {
+            branch: String::from(branch),
+            error,
+        },
+        e => e,
+    };
+    let refname = format!("refs/heads/{branch}");
+    let replacement = match replacement {
+        None => None,
+        Some(replacement) => Some(
+            crate::gitdb::plumbing::hash_object(repo, replacement.as_bytes())
+                .map_err(purge_error)?,
+        ),
+    };
+
+    let _lock = crate::lock::Lock::acquire(repo, &crate::lock::branch_lock_name(branch))?;
+    let tip =
+        run_stdout(repo.git().args(["rev-parse", "--verify", &refname])).map_err(purge_error)?;
+    let commits =
+        run_stdout(
+            repo.git()
+                .args(["rev-list", "--reverse", "--topo-order", "--parents", &tip]),
+        )
+        .map_err(purge_error)?;
+
+    // The rewritten commit of each original one.
+    let mut rewritten = std::collections::HashMap::<String, String>::new();
+    for line in commits.lines() {
+        let mut hashes = line.split(' ');
+        let Some(commit) = hashes.next() else {
+            continue;
+        };
+        let parents: Vec<&str> = hashes
+            .map(|parent| rewritten.get(parent).map_or(parent, String::as_str))
+            .collect();
+        let new = rewrite_commit(repo, commit, &parents, path, replacement.as_deref())
+            .map_err(purge_error)?;
+        rewritten.insert(String::from(commit), new);
+    }
+    let Some(new_tip) = rewritten.get(&tip) else {
+        return Ok(());
+    };
+    if *new_tip == tip {
+        return Ok(());
+    }
 
-        (false, false) => {
-            // No local branch and no remote branch.  What are we doing here??
+    crate::gitdb::plumbing::update_ref(repo, &refname, new_tip, Some(&tip)).map_err(purge_error)?;
+    match crate::gitdb::writer::find(dir) {
+        Some(writer) => writer.set_head(new_tip),
+        None => {
+            run(git_in(dir).args(["update-ref", "--no-deref", "HEAD", new_tip]))
+                .map_err(purge_error)?;
         }
     }
+    run(repo.git().args([
+        "reflog",
+        "expire",
+        "--expire=now",
+        "--expire-unreachable=now",
+        &refname,
+    ]))
+    .map_err(purge_error)?;
+    Ok(())
+}
 
-    // Merge remote branch into local.
-    if have_remote_branch {
-        let result = std::process::Command::new("git")
-            .args(["merge", &format!("{remote}/{branch}")])
-            .current_dir(dir)
-            .output()?;
-        if !result.status.success() {
-            return Err(GitError::MergeError {
-                remote: String::from(remote),
-                branch: String::from(branch),
-                error: format!(
-                    "Merge error - failed to merge {remote}/{branch} into {branch}!  Help, a human needs to fix the mess in {branch:?}:\n{}{}",
-                    &format!("stdout: {}\n", &String::from_utf8_lossy(&result.stdout)),
-                    &format!("stderr: {}\n", &String::from_utf8_lossy(&result.stderr)),
-                ),
-            });
+/// Remake `commit` on top of `parents`, with `path` (see
+/// `purge_from_history()`) removed from its tree, or replaced by the
+/// blob `replacement`.  Returns `commit` itself if nothing changed.
+fn rewrite_commit(
+    repo: &crate::Repo,
+    commit: &str,
+    parents: &[&str],
+    path: &str,
+    replacement: Option<&str>,
+) -> Result<String, GitError> {
+    let info = run_stdout(repo.git().args([
+        "log",
+        "-1",
+        "--date=raw",
+        "--format=%T%x00%P%x00%an%x00%ae%x00%ad%x00%cn%x00%ce%x00%cd",
+        commit,
+    ]))?;
+    let fields: Vec<&str> = info.split('\0').collect();
+    let [
+        tree,
+        old_parents,
+        author_name,
+        author_email,
+        author_date,
+        committer_name,
+        committer_email,
+        committer_date,
+    ] = fields[..]
+    else {
+        return Err(GitError::UnexpectedOutput {
+            command: String::from("git log"),
+            output: info,
+        });
+    };
+
+    let paths = match path.split_once('/') {
+        Some(("*", rest)) => crate::gitdb::plumbing::ls_tree(repo, tree)?
+            .into_iter()
+            .filter(|entry| entry.kind == "tree")
+            .map(|entry| format!("{}/{rest}", entry.name))
+            .collect(),
+        _ => vec![String::from(path)],
+    };
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+    let existing = crate::gitdb::plumbing::ls_files(repo, commit, &paths)?;
+    let mut removed = Vec::<&str>::new();
+    let mut replaced = Vec::<crate::gitdb::plumbing::TreeEntry>::new();
+    for path in paths {
+        let dir_prefix = format!("{path}/");
+        match replacement {
+            None if existing
+                .iter()
+                .any(|entry| entry.name == path || entry.name.starts_with(&dir_prefix)) =>
+            {
+                removed.push(path)
+            }
+            None => (),
+            Some(blob) => {
+                if let Some(entry) = existing.iter().find(|entry| entry.name == path) {
+                    replaced.push(crate::gitdb::plumbing::TreeEntry {
+                        hash: String::from(blob),
+                        ..entry.clone()
+                    });
+                }
+            }
         }
     }
+    let mut changes = std::collections::BTreeMap::new();
+    for path in removed {
+        changes.insert(path, None);
+    }
+    for entry in &replaced {
+        changes.insert(entry.name.as_str(), Some(entry));
+    }
+    let new_tree = match crate::gitdb::writer::apply(repo, Some(tree), &changes)? {
+        Some(new_tree) => new_tree,
+        None => crate::gitdb::plumbing::mktree(repo, &[])?,
+    };
+    if new_tree == tree && parents.join(" ") == old_parents {
+        return Ok(String::from(commit));
+    }
 
-    // Push merged branch to remote.
-    let result = std::process::Command::new("git")
-        .args(["push", remote, branch])
-        .current_dir(dir)
-        .output()?;
-    if !result.status.success() {
-        println!("Sync failed!  Push error!  Help, a human needs to fix the mess in {branch:?}",);
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
+    let message = run(repo.git().args(["cat-file", "commit", commit]))?;
+    let message = String::from_utf8_lossy(&message.stdout);
+    let message = message
+        .split_once("\n\n")
+        .map_or("", |(_, message)| message);
+    let mut command = repo.git();
+    command.args(["commit-tree", &new_tree]);
+    for parent in parents {
+        command.args(["-p", parent]);
     }
+    command
+        .env("GIT_AUTHOR_NAME", author_name)
+        .env("GIT_AUTHOR_EMAIL", author_email)
+        .env("GIT_AUTHOR_DATE", author_date)
+        .env("GIT_COMMITTER_NAME", committer_name)
+        .env("GIT_COMMITTER_EMAIL", committer_email)
+        .env("GIT_COMMITTER_DATE", committer_date);
+    run_with_input(&mut command, message.as_bytes())
+}
 
-    Ok(())
+/// Compute the git object ids of files, optionally writing them to the
+/// object database.  Returns one id per file, in order.
+pub fn hash_objects<P: AsRef<std::path::Path>>(
+    dir: &std::path::Path,
+    files: &[P],
+    write: bool,
+) -> Result<Vec<String>, GitError> {
+    let mut command = git_in(dir);
+    command.arg("hash-object");
+    if write {
+        command.arg("-w");
+    }
+    command.arg("--");
+    for file in files {
+        // git runs in `dir`, not in our current directory.
+        command.arg(std::path::absolute(file.as_ref())?);
+    }
+    Ok(run_stdout(&mut command)?
+        .lines()
+        .map(String::from)
+        .collect())
 }
 
 pub fn git_log_oldest_author_timestamp(
     path: &std::path::Path,
 ) -> Result<(String, chrono::DateTime<chrono::Local>), GitError> {
-    let mut git_dir = std::path::PathBuf::from(path);
-    git_dir.pop();
-    let result = std::process::Command::new("git")
-        .args([
+    let output = match crate::gitdb::writer::find(path) {
+        Some(writer) => run_stdout(writer.repo().git().args([
             "log",
             "--pretty=format:%at %an <%ae>",
+            &writer.head()?,
             "--",
-            &path
-                .file_name()
-                .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
-                .to_string_lossy(),
-        ])
-        .current_dir(&git_dir)
-        .output()?;
-    if !result.status.success() {
-        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
-        return Err(GitError::Oops);
-    }
+            &writer.relative(path),
+        ]))?,
+        None => run_stdout(
+            git_in(parent_dir(path)?).args([
+                "log",
+                "--pretty=format:%at %an <%ae>",
+                "--",
+                &path
+                    .file_name()
+                    .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?
+                    .to_string_lossy(),
+            ]),
+        )?,
+    };
+    let unexpected = || GitError::UnexpectedOutput {
+        command: String::from("git log"),
+        output: output.clone(),
+    };
 
-    let raw_output_str = String::from_utf8_lossy(&result.stdout);
-    let Some(raw_output_last) = raw_output_str.split("\n").last() else {
-        return Err(GitError::Oops);
+    let Some(raw_output_last) = output.split("\n").last() else {
+        return Err(unexpected());
     };
     let Some(index) = raw_output_last.find(' ') else {
-        return Err(GitError::Oops);
+        return Err(unexpected());
     };
     let author_str = &raw_output_last[index + 1..];
     let timestamp_str = &raw_output_last[0..index];
     let timestamp_i64 = timestamp_str.parse::<i64>()?;
     let timestamp = chrono::DateTime::from_timestamp(timestamp_i64, 0)
-        .unwrap()
+        .ok_or_else(unexpected)?
         .with_timezone(&chrono::Local);
 
     Ok((String::from(author_str), timestamp))
@@ -438,7 +1060,7 @@ mod tests {
     fn test_worktree() {
         let mut p = std::path::PathBuf::new();
         {
-            let worktree = Worktree::new("origin/main").unwrap();
+            let worktree = Worktree::new(&crate::Repo::current(), "origin/main").unwrap();
 
             p.push(worktree.path());
             assert!(p.exists());
@@ -465,16 +1087,70 @@ mod tests {
         //   fetch everything.
         //
         // This works for now but could be better.
-        let r = git_branch_exists("origin/main").unwrap();
+        let r = git_branch_exists(&crate::Repo::current(), "origin/main").unwrap();
         assert_eq!(r, true);
     }
 
+    #[test]
+    fn classify_command_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let error = run(git_in(dir.path()).args(["status"])).unwrap_err();
+        assert_eq!(error.kind(), FailureKind::NotARepo);
+        let command_error = error.command_error().unwrap();
+        assert_eq!(command_error.command, vec!["git", "status"]);
+        assert_eq!(command_error.dir.as_deref(), Some(dir.path()));
+        assert_eq!(command_error.status, Some(128));
+
+        let failure = |command: &[&str], status, stderr: &str| CommandError {
+            command: command.iter().map(|arg| String::from(*arg)).collect(),
+            dir: None,
+            status: Some(status),
+            stdout: String::new(),
+            stderr: String::from(stderr),
+        };
+        assert_eq!(
+            failure(
+                &["git", "push", "origin", "entomologist-data"],
+                1,
+                " ! [rejected]        entomologist-data -> entomologist-data (fetch first)\nerror: failed to push some refs\n"
+            )
+            .kind(),
+            FailureKind::PushRejected
+        );
+        assert_eq!(
+            failure(
+                &["git", "merge", "origin/entomologist-data"],
+                1,
+                "CONFLICT (content): Merge conflict in abc/state\n"
+            )
+            .kind(),
+            FailureKind::MergeConflict
+        );
+        assert_eq!(
+            failure(&["git", "config", "user.email"], 1, "").kind(),
+            FailureKind::NoIdentity
+        );
+        assert_eq!(
+            failure(
+                &["git", "worktree", "add", "/tmp/x", "entomologist-data"],
+                128,
+                "fatal: 'entomologist-data' is already checked out at '/tmp/y'\n"
+            )
+            .kind(),
+            FailureKind::CheckedOutElsewhere
+        );
+        assert_eq!(
+            failure(&["git", "fetch", "origin"], 128, "fatal: no such remote\n").kind(),
+            FailureKind::Other
+        );
+    }
+
     #[test]
     fn test_branch_exists_1() {
         let rnd: u128 = rand::random();
         let mut branch = std::string::String::from("entomologist-missing-branch-");
         branch.push_str(&format!("{:032x}", rnd));
-        let r = git_branch_exists(&branch).unwrap();
+        let r = git_branch_exists(&crate::Repo::current(), &branch).unwrap();
         assert_eq!(r, false);
     }
 }
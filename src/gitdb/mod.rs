@@ -1,12 +1,22 @@
-//! gitdb is a front end that lets you access a git branch by checking
-//! it out in a temporary worktree.
+//! gitdb is a front end that lets you access a git branch through a
+//! copy of its files in a temporary directory.  Reads check the branch
+//! out in a detached worktree, which can be added in bare repos too.
+//! Writes don't use a worktree: the files are written out from the
+//! tip of the branch through a temporary index (see `snapshot`), and
+//! changes are committed with git plumbing and a compare-and-swap ref
+//! update (see `writer`).  A missing branch is created with plumbing
+//! too.
+//!
+//! Both still write out every file of the branch, since `Issues` and
+//! `IssuesMut` parse the whole database from the files.
 //!
 //! This module is used internally by entomologist, the user generally
 //! doesn't need to care about it or use it directly.
 
+pub mod plumbing;
+pub(crate) mod snapshot;
 pub mod worktree;
-
-use std::io::Write;
+pub(crate) mod writer;
 
 /// GitDb checks out a git ref in detached head mode, so any changes
 /// made to the worktree can **not** be committed back to the ref. This
@@ -17,13 +27,25 @@ pub struct GitDb {
     worktree: crate::gitdb::worktree::Worktree,
 }
 
-/// GitDbMut checks out a git ref in normal (named branch) mode, so
-/// any changes made to the worktree **can** be committed back to the
-/// ref. This makes the GitDbMut mutable, in the sense that it can add
-/// commits to the git ref.
+/// GitDbMut writes out the files of a branch in a snapshot directory
+/// that isn't a worktree (see `snapshot`), to read the issues from and
+/// to make changes in.  Changes made in the snapshot are committed to
+/// the branch with `crate::git::add()` and `crate::git::commit()`,
+/// which build the commit from the tip of the branch with plumbing
+/// (see `writer`).  This makes the GitDbMut mutable, in the sense that
+/// it can add commits to the branch.
+///
+/// Any number of GitDbMuts can be open on a branch at once.  Each
+/// commit takes the repo's lock on the branch (see `crate::lock`) while
+/// it's published, so only one process on this machine commits to the
+/// branch at a time.  Changes committed by the others since the
+/// snapshot was taken (or by a `git` command, or an `ent` on another
+/// machine sharing the repo) are merged by `crate::git::commit()`.
 #[derive(Debug)]
 pub struct GitDbMut {
-    worktree: crate::gitdb::worktree::Worktree,
+    // The snapshot is unregistered before it's removed.
+    _writer: writer::Registration,
+    snapshot: snapshot::Snapshot,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -31,25 +53,23 @@ pub enum Error {
     #[error(transparent)]
     StdIoError(#[from] std::io::Error),
 
-    #[error("Error from git:\nstdout: {stdout}\nstderr: {stderr}")]
-    Git { stdout: String, stderr: String },
-
-    // The GitError is a leftover, should go away when the
-    // entomologist::git module gets incorporated into entomologist::gitdb.
     #[error(transparent)]
     GitError(#[from] crate::git::GitError),
 
     #[error(transparent)]
     Worktree(#[from] worktree::Error),
+
+    #[error(transparent)]
+    Lock(#[from] crate::lock::LockError),
 }
 
 impl GitDb {
-    /// Check out a git ref into an ephemeral worktree, in detached
-    /// head mode..
-    pub fn get(git_ref: &str) -> Result<GitDb, Error> {
-        ensure_branch_exists(git_ref)?;
+    /// Check out a git ref of a repo into an ephemeral worktree, in
+    /// detached head mode.
+    pub fn get(repo: &crate::Repo, git_ref: &str) -> Result<GitDb, Error> {
+        ensure_branch_exists(repo, git_ref)?;
         Ok(GitDb {
-            worktree: worktree::Worktree::new_detached(git_ref)?,
+            worktree: worktree::Worktree::new_detached(repo, git_ref)?,
         })
     }
 
@@ -60,140 +80,91 @@ impl GitDb {
 }
 
 impl GitDbMut {
-    /// Check out a git ref into an ephemeral worktree, in normal (named
-    /// branch) mode.
-    pub fn get(git_ref: &str) -> Result<GitDbMut, Error> {
-        ensure_branch_exists(git_ref)?;
+    /// Write out a snapshot of a branch of a repo, for writing.
+    pub fn get(repo: &crate::Repo, git_ref: &str) -> Result<GitDbMut, Error> {
+        // Clean up after ents that crashed, while we're here.
+        worktree::prune_stale(repo)?;
+        ensure_branch_exists(repo, git_ref)?;
+        let head = crate::git::run_stdout(repo.git().args([
+            "rev-parse",
+            "--verify",
+            &format!("refs/heads/{git_ref}^{{commit}}"),
+        ]))?;
+        let snapshot = snapshot::Snapshot::new(repo, &head)?;
         Ok(GitDbMut {
-            worktree: worktree::Worktree::new(git_ref)?,
+            _writer: writer::register(repo, git_ref, snapshot.path(), &head),
+            snapshot,
         })
     }
 
-    /// Get the path of the worktree.
+    /// Get the path of the snapshot.
     pub fn path(&self) -> std::path::PathBuf {
-        self.worktree.path().into()
-    }
-}
-
-fn create_orphan_branch(branch: &str) -> Result<(), Error> {
-    {
-        let tmp_worktree = tempfile::tempdir().unwrap();
-        create_orphan_branch_at_path(branch, tmp_worktree.path())?;
-    }
-    // The temp dir is now removed / cleaned up.
-
-    let result = std::process::Command::new("git")
-        .args(["worktree", "prune"])
-        .output()?;
-    if !result.status.success() {
-        return Err(Error::Git {
-            stdout: String::from_utf8_lossy(&result.stdout).into(),
-            stderr: String::from_utf8_lossy(&result.stderr).into(),
-        });
+        self.snapshot.path().into()
     }
-
-    Ok(())
 }
 
-fn create_orphan_branch_at_path(
-    branch: &str,
-    worktree_path: &std::path::Path,
-) -> Result<(), Error> {
-    let worktree_dir = worktree_path.to_string_lossy();
-
-    // Create a worktree at the path, with a detached head.
-    let result = std::process::Command::new("git")
-        .args(["worktree", "add", &worktree_dir, "HEAD"])
-        .output()?;
-    if !result.status.success() {
-        return Err(Error::Git {
-            stdout: String::from_utf8_lossy(&result.stdout).into(),
-            stderr: String::from_utf8_lossy(&result.stderr).into(),
-        });
-    }
-
-    // Create an empty orphan branch in the worktree.
-    let result = std::process::Command::new("git")
-        .args(["switch", "--orphan", branch])
-        .current_dir(worktree_path)
-        .output()?;
-    if !result.status.success() {
-        return Err(Error::Git {
-            stdout: String::from_utf8_lossy(&result.stdout).into(),
-            stderr: String::from_utf8_lossy(&result.stderr).into(),
-        });
-    }
-
-    let mut readme_filename = std::path::PathBuf::from(worktree_path);
-    readme_filename.push("README.md");
-    let mut readme = std::fs::File::create(readme_filename)?;
-    write!(
-        readme,
-        "This branch is used by entomologist to track issues."
+/// Create an orphan branch holding just a README, using plumbing so it
+/// works in bare repos and in repos whose HEAD has no commits.
+fn create_orphan_branch(repo: &crate::Repo, branch: &str) -> Result<(), Error> {
+    let readme = plumbing::hash_object(
+        repo,
+        b"This branch is used by entomologist to track issues.",
     )?;
-
-    let result = std::process::Command::new("git")
-        .args(["add", "README.md"])
-        .current_dir(worktree_path)
-        .output()?;
-    if !result.status.success() {
-        return Err(Error::Git {
-            stdout: String::from_utf8_lossy(&result.stdout).into(),
-            stderr: String::from_utf8_lossy(&result.stderr).into(),
-        });
-    }
-
-    let result = std::process::Command::new("git")
-        .args(["commit", "-m", "create entomologist issue branch"])
-        .current_dir(worktree_path)
-        .output()?;
-    if !result.status.success() {
-        return Err(Error::Git {
-            stdout: String::from_utf8_lossy(&result.stdout).into(),
-            stderr: String::from_utf8_lossy(&result.stderr).into(),
-        });
+    let tree = plumbing::mktree(repo, &[plumbing::TreeEntry::blob(&readme, "README.md")])?;
+    let commit = plumbing::commit_tree(repo, &tree, &[], "create entomologist issue branch")?;
+
+    let refname = format!("refs/heads/{branch}");
+    if let Err(e) = plumbing::update_ref(repo, &refname, &commit, None) {
+        // Someone else may have created the branch since we looked,
+        // that's fine.
+        if !crate::git::git_branch_exists(repo, &refname)? {
+            return Err(e.into());
+        }
     }
 
     Ok(())
 }
 
-fn ensure_branch_exists(branch: &str) -> Result<(), Error> {
+pub(crate) fn ensure_branch_exists(repo: &crate::Repo, branch: &str) -> Result<(), Error> {
     // Check for a local branch with the specified name.
-    if crate::git::git_branch_exists(&format!("refs/heads/{branch}"))? {
+    if crate::git::git_branch_exists(repo, &format!("refs/heads/{branch}"))? {
         return Ok(());
     }
 
+    // Check for the branch on remotes that call it something else.
+    for remote in crate::git::remotes(repo)? {
+        let remote_branch_name = crate::git::remote_branch_name(repo, &remote, branch)?;
+        let remote_branch = format!("refs/remotes/{remote}/{remote_branch_name}");
+        if remote_branch_name != branch && crate::git::git_branch_exists(repo, &remote_branch)? {
+            crate::git::run(repo.git().args(["branch", branch, &remote_branch]))?;
+            return Ok(());
+        }
+    }
+
     // Check for *any* branch with the specified name, even remote.
-    let result = std::process::Command::new("git")
-        .args(["show-ref", branch])
-        .output()?;
+    let result = repo.git().args(["show-ref", branch]).output()?;
     match result.status.success() {
         true => {
             // Some remote has this branch, make a local branch from
             // the first one found.
             let output = String::from_utf8_lossy(&result.stdout);
-            let line = output.split('\n').next().ok_or(Error::Git {
-                stdout: String::from_utf8_lossy(&result.stdout).into(),
-                stderr: String::from_utf8_lossy(&result.stderr).into(),
-            })?;
-            let remote_branch = line.split_whitespace().last().ok_or(Error::Git {
-                stdout: String::from_utf8_lossy(&result.stdout).into(),
-                stderr: String::from_utf8_lossy(&result.stderr).into(),
-            })?;
-
-            let result = std::process::Command::new("git")
-                .args(["branch", branch, remote_branch])
-                .output()?;
-            if !result.status.success() {
-                return Err(Error::Git {
-                    stdout: String::from_utf8_lossy(&result.stdout).into(),
-                    stderr: String::from_utf8_lossy(&result.stderr).into(),
-                });
-            }
+            let Some(remote_branch) = output
+                .split('\n')
+                .next()
+                .and_then(|line| line.split_whitespace().last())
+            else {
+                return Err(crate::git::GitError::UnexpectedOutput {
+                    command: String::from("git show-ref"),
+                    output: output.into_owned(),
+                }
+                .into());
+            };
+
+            crate::git::run(repo.git().args(["branch", branch, remote_branch]))?;
         }
         false => {
             // No remote has this branch, make an empty one locally now.
-            create_orphan_branch(branch)?;
+            create_orphan_branch(repo, branch)?;
         }
     }
 
@@ -205,16 +176,8 @@ mod tests {
     use super::*;
     use pretty_assertions::assert_eq;
 
-    fn git_remove_branch(branch: &str) -> Result<(), Error> {
-        let result = std::process::Command::new("git")
-            .args(["branch", "-D", branch])
-            .output()?;
-        if !result.status.success() {
-            return Err(Error::Git {
-                stdout: String::from_utf8_lossy(&result.stdout).into(),
-                stderr: String::from_utf8_lossy(&result.stderr).into(),
-            });
-        }
+    fn git_remove_branch(repo: &crate::Repo, branch: &str) -> Result<(), Error> {
+        crate::git::run(repo.git().args(["branch", "-D", branch]))?;
         Ok(())
     }
 
@@ -223,8 +186,9 @@ mod tests {
         let rnd: u128 = rand::random();
         let mut branch = std::string::String::from("entomologist-test-branch-");
         branch.push_str(&format!("{:032x}", rnd));
-        create_orphan_branch(&branch).unwrap();
-        assert_eq!(crate::git::git_branch_exists(&branch).unwrap(), true);
-        git_remove_branch(&branch).unwrap();
+        let repo = crate::Repo::current();
+        create_orphan_branch(&repo, &branch).unwrap();
+        assert_eq!(crate::git::git_branch_exists(&repo, &branch).unwrap(), true);
+        git_remove_branch(&repo, &branch).unwrap();
     }
 }
@@ -0,0 +1,223 @@
+//! Thin wrappers around the git plumbing commands used to build trees
+//! and commits directly in the object database, without a worktree,
+//! an index, or a valid HEAD.  These work the same in bare repos.
+
+use std::io::Write;
+
+use crate::git::{GitError, run, run_with_input};
+
+/// One entry of a tree object, as given to `git mktree`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TreeEntry {
+    pub mode: String,
+    pub kind: String,
+    pub hash: String,
+    pub name: String,
+}
+
+impl TreeEntry {
+    /// A regular (non-executable) file.
+    pub fn blob(hash: &str, name: &str) -> Self {
+        Self {
+            mode: String::from("100644"),
+            kind: String::from("blob"),
+            hash: String::from(hash),
+            name: String::from(name),
+        }
+    }
+
+    /// A subdirectory.
+    pub fn tree(hash: &str, name: &str) -> Self {
+        Self {
+            mode: String::from("040000"),
+            kind: String::from("tree"),
+            hash: String::from(hash),
+            name: String::from(name),
+        }
+    }
+}
+
+/// Write `contents` to the object database as a blob, returning its id.
+pub fn hash_object(repo: &crate::Repo, contents: &[u8]) -> Result<String, GitError> {
+    run_with_input(repo.git().args(["hash-object", "-w", "--stdin"]), contents)
+}
+
+/// Write the files at `paths` to the object database as blobs,
+/// returning their ids in the same order.
+pub fn hash_files(
+    repo: &crate::Repo,
+    paths: &[std::path::PathBuf],
+) -> Result<Vec<String>, GitError> {
+    if paths.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut input = Vec::<u8>::new();
+    for path in paths {
+        writeln!(input, "{}", path.display())?;
+    }
+    let output = run_with_input(
+        repo.git()
+            .args(["hash-object", "-w", "--no-filters", "--stdin-paths"]),
+        &input,
+    )?;
+    let hashes: Vec<String> = output.lines().map(String::from).collect();
+    if hashes.len() != paths.len() {
+        return Err(GitError::UnexpectedOutput {
+            command: String::from("git hash-object"),
+            output,
+        });
+    }
+    Ok(hashes)
+}
+
+/// The entries of a tree object, or of the tree of a commit.
+pub fn ls_tree(repo: &crate::Repo, tree: &str) -> Result<Vec<TreeEntry>, GitError> {
+    let output = run(repo.git().args(["ls-tree", "-z", tree]))?;
+    parse_ls_tree(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// The files (blobs) below `paths` in the tree of `rev`, recursively.
+/// The paths are relative to the top of the tree, and an empty path
+/// means the whole tree.  The names of the returned entries are full
+/// paths.
+pub fn ls_files(repo: &crate::Repo, rev: &str, paths: &[&str]) -> Result<Vec<TreeEntry>, GitError> {
+    let mut args = vec!["ls-tree", "-r", "-z", "--full-tree", rev, "--"];
+    if !paths.contains(&"") {
+        args.extend(paths);
+    }
+    let output = run(repo.git().args(&args))?;
+    parse_ls_tree(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_ls_tree(output: &str) -> Result<Vec<TreeEntry>, GitError> {
+    let mut entries = Vec::<TreeEntry>::new();
+    for line in output.split('\0').filter(|line| !line.is_empty()) {
+        let unexpected = || GitError::UnexpectedOutput {
+            command: String::from("git ls-tree"),
+            output: String::from(line),
+        };
+        let (info, name) = line.split_once('\t').ok_or_else(unexpected)?;
+        let mut info = info.split(' ');
+        let (Some(mode), Some(kind), Some(hash)) = (info.next(), info.next(), info.next()) else {
+            return Err(unexpected());
+        };
+        entries.push(TreeEntry {
+            mode: String::from(mode),
+            kind: String::from(kind),
+            hash: String::from(hash),
+            name: String::from(name),
+        });
+    }
+    Ok(entries)
+}
+
+/// Write a tree object with the given entries, returning its id.
+pub fn mktree(repo: &crate::Repo, entries: &[TreeEntry]) -> Result<String, GitError> {
+    let mut input = Vec::<u8>::new();
+    for entry in entries {
+        write!(
+            input,
+            "{} {} {}\t{}\0",
+            entry.mode, entry.kind, entry.hash, entry.name
+        )?;
+    }
+    run_with_input(repo.git().args(["mktree", "-z"]), &input)
+}
+
+/// Write a commit object for `tree` with the given parents, returning
+/// its id.  The author and committer come from the repo's git config,
+/// like for `git commit`.
+pub fn commit_tree(
+    repo: &crate::Repo,
+    tree: &str,
+    parents: &[&str],
+    message: &str,
+) -> Result<String, GitError> {
+    let mut args = vec!["commit-tree", tree];
+    for parent in parents {
+        args.push("-p");
+        args.push(parent);
+    }
+    run_with_input(repo.git().args(&args), message.as_bytes())
+}
+
+/// Point `refname` at `new`.  If `old` is Some, the ref is only updated
+/// if it currently points at `old`; if `old` is None, the ref is only
+/// created if it doesn't exist yet.  Fails if someone else changed the
+/// ref first.
+pub fn update_ref(
+    repo: &crate::Repo,
+    refname: &str,
+    new: &str,
+    old: Option<&str>,
+) -> Result<(), GitError> {
+    run(repo
+        .git()
+        .args(["update-ref", refname, new, old.unwrap_or("")]))?;
+    Ok(())
+}
+
+/// The object id that `rev` resolves to, or None if it doesn't resolve.
+pub fn rev_parse(repo: &crate::Repo, rev: &str) -> Result<Option<String>, GitError> {
+    let result = repo
+        .git()
+        .args(["rev-parse", "--verify", "--quiet", rev])
+        .output()?;
+    if !result.status.success() {
+        return Ok(None);
+    }
+    Ok(Some(
+        String::from_utf8_lossy(&result.stdout).trim().to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn build_commit_in_bare_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = std::process::Command::new("git")
+            .args(["init", "--bare", &dir.path().to_string_lossy()])
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        let repo = crate::Repo::new(dir.path());
+        run(repo.git().args(["config", "user.name", "Test"])).unwrap();
+        run(repo
+            .git()
+            .args(["config", "user.email", "test@example.com"]))
+        .unwrap();
+
+        let blob = hash_object(&repo, b"hello\n").unwrap();
+        assert_eq!(blob, "ce013625030ba8dba906f756967f9e9ca394464a");
+        let subtree = mktree(&repo, &[TreeEntry::blob(&blob, "greeting")]).unwrap();
+        let tree = mktree(
+            &repo,
+            &[
+                TreeEntry::blob(&blob, "README.md"),
+                TreeEntry::tree(&subtree, "dir"),
+            ],
+        )
+        .unwrap();
+        let commit = commit_tree(&repo, &tree, &[], "first").unwrap();
+
+        update_ref(&repo, "refs/heads/data", &commit, None).unwrap();
+        assert_eq!(
+            rev_parse(&repo, "refs/heads/data").unwrap(),
+            Some(commit.clone())
+        );
+        // The branch exists now, so creating it again fails.
+        assert!(update_ref(&repo, "refs/heads/data", &commit, None).is_err());
+
+        let second = commit_tree(&repo, &tree, &[&commit], "second").unwrap();
+        // Compare-and-swap with a stale old value fails...
+        assert!(update_ref(&repo, "refs/heads/data", &second, Some(&blob)).is_err());
+        // ... and with the current value succeeds.
+        update_ref(&repo, "refs/heads/data", &second, Some(&commit)).unwrap();
+        assert_eq!(rev_parse(&repo, "refs/heads/data").unwrap(), Some(second));
+        assert_eq!(rev_parse(&repo, "refs/heads/nope").unwrap(), None);
+    }
+}
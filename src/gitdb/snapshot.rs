@@ -0,0 +1,93 @@
+//! A snapshot of a commit in a temporary directory, to read issues from
+//! and make changes in without a worktree.
+//!
+//! The commit's tree is read into a temporary index (`read-tree`) and
+//! its files are written out from there (`checkout-index`).  Unlike a
+//! worktree nothing is registered in the repo, so there's no HEAD or
+//! index to keep up to date, nothing to prune after a crash, and bare
+//! repos need no special treatment.  The snapshot has no `.git`, the
+//! git commands for files in it are run in the repo, see
+//! `crate::gitdb::writer`.
+
+use crate::git::{GitError, run};
+
+/// Entomologist's snapshots are made in temporary directories whose
+/// names start with this.
+const SNAPSHOT_PREFIX: &str = "entomologist-snapshot-";
+
+#[derive(Debug)]
+pub(crate) struct Snapshot {
+    // The directory is unregistered before it's removed.
+    _registration: crate::cleanup::Registration,
+    dir: tempfile::TempDir,
+}
+
+impl Snapshot {
+    /// Write out the files of `commit` in a new temporary directory.
+    pub(crate) fn new(repo: &crate::Repo, commit: &str) -> Result<Snapshot, GitError> {
+        let dir = tempfile::Builder::new().prefix(SNAPSHOT_PREFIX).tempdir()?;
+        let registration = crate::cleanup::register_directory(dir.path());
+
+        let index_dir = tempfile::tempdir()?;
+        let index = index_dir.path().join("index");
+        run(repo
+            .git()
+            .env("GIT_INDEX_FILE", &index)
+            .args(["read-tree", commit]))?;
+        // checkout-index needs a work tree, even in a bare repo.
+        run(repo
+            .git()
+            .env("GIT_INDEX_FILE", &index)
+            .env("GIT_WORK_TREE", dir.path())
+            .args(["checkout-index", "--all", "--force"]))?;
+
+        Ok(Snapshot {
+            _registration: registration,
+            dir,
+        })
+    }
+
+    pub(crate) fn path(&self) -> &std::path::Path {
+        self.dir.path()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gitdb::plumbing::{TreeEntry, commit_tree, hash_object, mktree};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn snapshot_of_bare_repo() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = std::process::Command::new("git")
+            .args(["init", "--bare", &dir.path().to_string_lossy()])
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        let repo = crate::Repo::new(dir.path());
+        run(repo.git().args(["config", "user.name", "Test"])).unwrap();
+        run(repo
+            .git()
+            .args(["config", "user.email", "test@example.com"]))
+        .unwrap();
+
+        let blob = hash_object(&repo, b"hello\n").unwrap();
+        let subtree = mktree(&repo, &[TreeEntry::blob(&blob, "greeting")]).unwrap();
+        let tree = mktree(&repo, &[TreeEntry::tree(&subtree, "dir")]).unwrap();
+        let commit = commit_tree(&repo, &tree, &[], "first").unwrap();
+
+        let snapshot = Snapshot::new(&repo, &commit).unwrap();
+        let path = snapshot.path().to_path_buf();
+        assert_eq!(
+            std::fs::read_to_string(path.join("dir/greeting")).unwrap(),
+            "hello\n"
+        );
+        // Nothing but the files of the commit.
+        assert_eq!(std::fs::read_dir(&path).unwrap().count(), 1);
+
+        drop(snapshot);
+        assert!(!path.exists());
+    }
+}
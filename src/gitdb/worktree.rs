@@ -2,77 +2,241 @@
 pub enum Error {
     #[error(transparent)]
     StdIoError(#[from] std::io::Error),
-    #[error("Error from git:\nstdout: {stdout}\nstderr: {stderr}")]
-    Git { stdout: String, stderr: String },
+    #[error(transparent)]
+    GitError(#[from] crate::git::GitError),
+    #[error(transparent)]
+    Lock(#[from] crate::lock::LockError),
 }
 
+/// The lock taken around adding and removing worktrees, since `git
+/// worktree add` trips over worktrees being added concurrently.
+const WORKTREES_LOCK: &str = "worktrees";
+
+/// Entomologist's worktrees are made in temporary directories whose
+/// names start with this.
+const WORKTREE_PREFIX: &str = "entomologist-worktree-";
+
+/// The file in a worktree's private git dir (`.git/worktrees/NAME`)
+/// saying which process made it, as a `crate::lock::LockInfo`.  Its
+/// presence marks the worktree as entomologist's.
+const OWNER_FILE: &str = "entomologist-owner";
+
 #[derive(Debug)]
 /// `Worktree` is a struct that manages a temporary directory containing
 /// a checkout of a specific branch.  The worktree is removed and pruned
-/// when the `Worktree` struct is dropped.
+/// when the `Worktree` struct is dropped, or when the process is killed
+/// by a signal if `crate::cleanup::cleanup_on_signals()` is in effect.
 pub struct Worktree {
     path: tempfile::TempDir,
+    repo: crate::Repo,
+    registration: Option<crate::cleanup::Registration>,
 }
 
 impl Drop for Worktree {
     fn drop(&mut self) {
-        let result = std::process::Command::new("git")
-            .args([
-                "worktree",
-                "remove",
-                "--force",
-                &self.path.path().to_string_lossy(),
-            ])
-            .output();
-        match result {
-            Err(e) => {
-                println!("failed to run git: {e:#?}");
-            }
-            Ok(result) => {
-                if !result.status.success() {
-                    println!("failed to remove git worktree: {result:#?}");
-                }
-            }
+        self.registration.take();
+        // Remove the worktree even if we can't get the lock.
+        let _lock = crate::lock::Lock::acquire(&self.repo, WORKTREES_LOCK);
+        let _result = crate::git::run(self.repo.git().args([
+            "worktree",
+            "remove",
+            "--force",
+            &self.path.path().to_string_lossy(),
+        ]));
+        #[cfg(feature = "log")]
+        if let Err(e) = _result {
+            log::warn!("failed to remove git worktree: {e}");
         }
     }
 }
 
 impl Worktree {
-    pub fn new(branch: &str) -> Result<Worktree, Error> {
-        let path = tempfile::tempdir()?;
-        let result = std::process::Command::new("git")
-            .args(["worktree", "add", &path.path().to_string_lossy(), branch])
-            .output()?;
-        if !result.status.success() {
-            return Err(Error::Git {
-                stdout: String::from_utf8_lossy(&result.stdout).into(),
-                stderr: String::from_utf8_lossy(&result.stderr).into(),
-            });
-        }
-        Ok(Self { path })
+    pub fn new(repo: &crate::Repo, branch: &str) -> Result<Worktree, Error> {
+        Self::add(repo, branch, false)
     }
 
-    pub fn new_detached(branch: &str) -> Result<Worktree, Error> {
-        let path = tempfile::tempdir()?;
-        let result = std::process::Command::new("git")
-            .args([
-                "worktree",
-                "add",
-                "--detach",
-                &path.path().to_string_lossy(),
-                branch,
-            ])
-            .output()?;
-        if !result.status.success() {
-            return Err(Error::Git {
-                stdout: String::from_utf8_lossy(&result.stdout).into(),
-                stderr: String::from_utf8_lossy(&result.stderr).into(),
-            });
+    pub fn new_detached(repo: &crate::Repo, branch: &str) -> Result<Worktree, Error> {
+        Self::add(repo, branch, true)
+    }
+
+    fn add(repo: &crate::Repo, branch: &str, detach: bool) -> Result<Worktree, Error> {
+        let path = tempfile::Builder::new().prefix(WORKTREE_PREFIX).tempdir()?;
+        let path_str = path.path().to_string_lossy().into_owned();
+        let mut args = vec!["worktree", "add"];
+        if detach {
+            args.push("--detach");
+        }
+        args.push(&path_str);
+        args.push(branch);
+
+        let _lock = crate::lock::Lock::acquire(repo, WORKTREES_LOCK)?;
+        let mut result = crate::git::run(repo.git().args(&args));
+        if let Err(e) = &result
+            && e.kind() == crate::git::FailureKind::CheckedOutElsewhere
+            // Maybe by an ent that crashed, try again without its
+            // worktree.
+            && !prune_stale_locked(repo)?.is_empty()
+        {
+            result = crate::git::run(repo.git().args(&args));
         }
-        Ok(Self { path })
+        result?;
+
+        // Register the worktree for cleanup before anything else can
+        // go wrong.
+        let registration = Some(crate::cleanup::register_worktree(repo, path.path()));
+        let worktree = Self {
+            path,
+            repo: repo.clone(),
+            registration,
+        };
+        let owner = toml::to_string(&crate::lock::LockInfo::this_process())
+            .map_err(std::io::Error::other)?;
+        std::fs::write(
+            crate::git::git_dir(worktree.path())?.join(OWNER_FILE),
+            owner,
+        )?;
+        Ok(worktree)
     }
 
     pub fn path(&self) -> &std::path::Path {
         self.path.as_ref()
     }
 }
+
+/// A worktree made by entomologist, as found by `owned_worktrees()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OwnedWorktree {
+    pub path: std::path::PathBuf,
+    pub owner: crate::lock::LockInfo,
+}
+
+impl OwnedWorktree {
+    /// A worktree is stale if the process that made it is gone (see
+    /// `crate::lock::LockInfo::is_stale()`), or if its directory is.
+    pub fn is_stale(&self) -> bool {
+        self.owner.is_stale() || !self.path.exists()
+    }
+}
+
+/// All the worktrees of the repo that were made by entomologist, by
+/// any process, live or dead.
+pub fn owned_worktrees(repo: &crate::Repo) -> Result<Vec<OwnedWorktree>, Error> {
+    let worktrees_dir = crate::git::git_common_dir(repo)?.join("worktrees");
+    let entries = match std::fs::read_dir(&worktrees_dir) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+        Ok(entries) => entries,
+    };
+    let mut worktrees = Vec::<OwnedWorktree>::new();
+    for entry in entries {
+        let admin_dir = entry?.path();
+        let Ok(owner) = std::fs::read_to_string(admin_dir.join(OWNER_FILE)) else {
+            continue;
+        };
+        let Ok(owner) = toml::from_str::<crate::lock::LockInfo>(&owner) else {
+            continue;
+        };
+        // `gitdir` holds the path of the `.git` file in the worktree.
+        let Ok(gitdir) = std::fs::read_to_string(admin_dir.join("gitdir")) else {
+            continue;
+        };
+        let Some(path) = std::path::Path::new(gitdir.trim()).parent() else {
+            continue;
+        };
+        worktrees.push(OwnedWorktree {
+            path: std::path::PathBuf::from(path),
+            owner,
+        });
+    }
+    worktrees.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(worktrees)
+}
+
+/// Remove the stale worktrees left behind by entomologist processes
+/// that crashed or were killed.  Returns the worktrees removed.
+pub fn prune_stale(repo: &crate::Repo) -> Result<Vec<OwnedWorktree>, Error> {
+    let _lock = crate::lock::Lock::acquire(repo, WORKTREES_LOCK)?;
+    prune_stale_locked(repo)
+}
+
+/// `prune_stale()`, for callers already holding the worktrees lock.
+fn prune_stale_locked(repo: &crate::Repo) -> Result<Vec<OwnedWorktree>, Error> {
+    let stale: Vec<OwnedWorktree> = owned_worktrees(repo)?
+        .into_iter()
+        .filter(OwnedWorktree::is_stale)
+        .collect();
+    if stale.is_empty() {
+        return Ok(stale);
+    }
+    for worktree in &stale {
+        if worktree.path.exists() {
+            crate::git::run(repo.git().args([
+                "worktree",
+                "remove",
+                "--force",
+                &worktree.path.to_string_lossy(),
+            ]))?;
+        }
+    }
+    // Forget the worktrees whose directories were already gone.
+    crate::git::git_worktree_prune(repo)?;
+    Ok(stale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn prune_stale_worktrees() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = std::process::Command::new("git")
+            .args(["init", "--bare", &dir.path().to_string_lossy()])
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        let repo = crate::Repo::new(dir.path());
+        crate::git::run(repo.git().args(["config", "user.name", "Test"])).unwrap();
+        crate::git::run(
+            repo.git()
+                .args(["config", "user.email", "test@example.com"]),
+        )
+        .unwrap();
+        super::super::ensure_branch_exists(&repo, "data").unwrap();
+
+        let live = Worktree::new_detached(&repo, "data").unwrap();
+        let crashed = Worktree::new(&repo, "data").unwrap();
+        let owned = owned_worktrees(&repo).unwrap();
+        assert_eq!(owned.len(), 2);
+        assert!(owned.iter().all(|w| w.owner.pid == std::process::id()));
+        assert!(owned.iter().all(|w| !w.is_stale()));
+        assert!(prune_stale(&repo).unwrap().is_empty());
+
+        // Make `crashed` look like it was left behind by a process
+        // that's gone.
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        let owner = crate::lock::LockInfo {
+            pid: dead_pid,
+            ..crate::lock::LockInfo::this_process()
+        };
+        let crashed_path = std::path::PathBuf::from(crashed.path());
+        std::fs::write(
+            crate::git::git_dir(&crashed_path).unwrap().join(OWNER_FILE),
+            toml::to_string(&owner).unwrap(),
+        )
+        .unwrap();
+        std::mem::forget(crashed);
+
+        // The branch is still checked out in `crashed`, so this only
+        // works by pruning it.
+        let again = Worktree::new(&repo, "data").unwrap();
+        assert!(!crashed_path.exists());
+        let owned = owned_worktrees(&repo).unwrap();
+        assert_eq!(owned.len(), 2);
+        assert!(owned.iter().any(|w| w.path == live.path()));
+        assert!(owned.iter().any(|w| w.path == again.path()));
+    }
+}
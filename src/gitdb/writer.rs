@@ -0,0 +1,598 @@
+//! Committing to an issues branch without a worktree or an index.
+//!
+//! A `GitDbMut` registers the directory holding its snapshot of the
+//! branch (see `crate::gitdb::snapshot`) here, with the commit it's a
+//! snapshot of.  `crate::git::add()` of a path in the snapshot only
+//! records the path, and `crate::git::commit()` builds the new tree
+//! from the tip of the branch: the files below the recorded paths are
+//! written to the object database (`hash-object`), the trees on the
+//! way to them are rebuilt (`ls-tree`, `mktree`), and the commit
+//! (`commit-tree`) is published with a compare-and-swap `update-ref`,
+//! holding the repo's lock on the branch.  Nothing is checked out.
+//!
+//! The snapshot isn't a git worktree, so the other git commands for
+//! files in it are run in the repo instead, see `find()`.
+//!
+//! If the branch moved since the snapshot was taken, the commit is made
+//! on top of the new tip, unless the concurrent change touched one of
+//! the files we changed, which is a conflict.  The concurrent change is
+//! then copied into the snapshot, so it's not undone by the next
+//! commit.
+
+use super::plumbing;
+use crate::git::{GitError, run, run_stdout};
+
+/// How many times a commit is rebuilt on a branch that keeps moving
+/// before giving up.
+const COMMIT_ATTEMPTS: usize = 10;
+
+struct Writer {
+    root: std::path::PathBuf,
+    repo: crate::Repo,
+    branch: String,
+    /// The commit the snapshot is of.
+    head: String,
+    /// The paths added since the last commit, relative to `root`, with
+    /// '/' separators.  The empty path is the whole snapshot.
+    staged: std::collections::BTreeSet<String>,
+    /// The number of open Batches, commits wait until they're all done.
+    batches: usize,
+}
+
+static WRITERS: std::sync::Mutex<Vec<(u64, Writer)>> = std::sync::Mutex::new(Vec::new());
+static NEXT_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+fn writers() -> std::sync::MutexGuard<'static, Vec<(u64, Writer)>> {
+    WRITERS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// A registered snapshot.  Dropping the Registration unregisters it.
+#[derive(Debug)]
+pub(crate) struct Registration(u64);
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        writers().retain(|(id, _)| *id != self.0);
+    }
+}
+
+/// Register `root`, a snapshot of the commit `head` of `branch` in the
+/// repo, for writing.
+pub(crate) fn register(
+    repo: &crate::Repo,
+    branch: &str,
+    root: &std::path::Path,
+    head: &str,
+) -> Registration {
+    let id = NEXT_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    writers().push((
+        id,
+        Writer {
+            root: std::path::PathBuf::from(root),
+            repo: repo.clone(),
+            branch: String::from(branch),
+            head: String::from(head),
+            staged: std::collections::BTreeSet::new(),
+            batches: 0,
+        },
+    ));
+    Registration(id)
+}
+
+/// Record that `path` (a file or a directory, which may not exist
+/// anymore) changed, to be included in the next commit.  Returns false
+/// if `path` isn't in a registered snapshot.
+pub(crate) fn stage(path: &std::path::Path) -> bool {
+    for (_, writer) in writers().iter_mut() {
+        if let Ok(relative) = path.strip_prefix(&writer.root) {
+            writer.staged.insert(relative_name(relative));
+            return true;
+        }
+    }
+    false
+}
+
+/// The writer of the registered snapshot that `path` is in, if any.
+/// Git commands about files in a snapshot are run in the writer's
+/// repo, with the paths made relative to the top of the snapshot.
+pub(crate) fn find(path: &std::path::Path) -> Option<Handle> {
+    writers()
+        .iter()
+        .find(|(_, writer)| path.starts_with(&writer.root))
+        .map(|(id, writer)| Handle {
+            id: *id,
+            root: writer.root.clone(),
+            repo: writer.repo.clone(),
+            branch: writer.branch.clone(),
+        })
+}
+
+fn relative_name(relative: &std::path::Path) -> String {
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Several changes to be made as one commit: while a Batch is open,
+/// commits to its snapshot are put off, and `Batch::commit()` makes a
+/// single commit of all of them.  Dropping the Batch without committing
+/// puts the files changed since it was opened back the way they were.
+pub(crate) struct Batch {
+    handle: Handle,
+    done: bool,
+}
+
+/// Open a Batch on the registered snapshot that `path` is in, if any.
+pub(crate) fn batch(path: &std::path::Path) -> Option<Batch> {
+    let handle = find(path)?;
+    if let Some((_, writer)) = writers().iter_mut().find(|(id, _)| *id == handle.id) {
+        writer.batches += 1;
+    }
+    Some(Batch {
+        handle,
+        done: false,
+    })
+}
+
+impl Batch {
+    /// Commit all the changes made since the Batch was opened.  If the
+    /// commit fails the files are put back, like when the Batch is
+    /// dropped.
+    pub(crate) fn commit(mut self, msg: &str) -> Result<(), GitError> {
+        self.close();
+        let result = self.handle.commit(msg);
+        if result.is_err() {
+            let _ = self.handle.discard();
+        }
+        result
+    }
+
+    fn close(&mut self) {
+        if self.done {
+            return;
+        }
+        self.done = true;
+        if let Some((_, writer)) = writers().iter_mut().find(|(id, _)| *id == self.handle.id) {
+            writer.batches -= 1;
+        }
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        if !self.done {
+            self.close();
+            let _ = self.handle.discard();
+        }
+    }
+}
+
+/// The new contents of each changed file, by path: None for removed
+/// files.
+type Changes = std::collections::BTreeMap<String, Option<plumbing::TreeEntry>>;
+
+/// A registered writer, as found by `find()`.
+pub(crate) struct Handle {
+    id: u64,
+    root: std::path::PathBuf,
+    repo: crate::Repo,
+    branch: String,
+}
+
+impl Handle {
+    /// The repo the snapshot's branch is in.
+    pub(crate) fn repo(&self) -> &crate::Repo {
+        &self.repo
+    }
+
+    /// The path of `path`, a file or directory in the snapshot,
+    /// relative to the top of the snapshot, with '/' separators.
+    pub(crate) fn relative(&self, path: &std::path::Path) -> String {
+        relative_name(path.strip_prefix(&self.root).unwrap_or(path))
+    }
+
+    /// Put the file `path` in the snapshot back the way it is in the
+    /// snapshot's commit.
+    pub(crate) fn restore(&self, path: &std::path::Path) -> Result<(), GitError> {
+        self.refresh(&self.head()?, &self.relative(path))
+    }
+
+    /// Commit the staged paths to the branch, on top of its current
+    /// tip.
+    pub(crate) fn commit(&self, msg: &str) -> Result<(), GitError> {
+        let staged: Vec<String> = match writers().iter().find(|(id, _)| *id == self.id) {
+            Some((_, writer)) if writer.batches > 0 => return Ok(()),
+            Some((_, writer)) => writer.staged.iter().cloned().collect(),
+            None => Vec::new(),
+        };
+        if staged.is_empty() {
+            return Ok(());
+        }
+        let head = self.head()?;
+        let changes = self.changes(&head, &staged)?;
+        if !changes.is_empty() {
+            let _lock = self.lock()?;
+            self.publish(&head, &changes, msg)?;
+        }
+        if let Some((_, writer)) = writers().iter_mut().find(|(id, _)| *id == self.id) {
+            for path in &staged {
+                writer.staged.remove(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Put the staged paths back the way they are in the snapshot's
+    /// commit, and forget them.
+    fn discard(&self) -> Result<(), GitError> {
+        let staged: Vec<String> = match writers().iter_mut().find(|(id, _)| *id == self.id) {
+            Some((_, writer)) => std::mem::take(&mut writer.staged).into_iter().collect(),
+            None => Vec::new(),
+        };
+        if staged.is_empty() {
+            return Ok(());
+        }
+        let head = self.head()?;
+        let staged: Vec<&str> = staged.iter().map(String::as_str).collect();
+        let mut files = std::collections::BTreeSet::<String>::new();
+        for path in &staged {
+            list_files(&self.root, path, &mut files)?;
+        }
+        for entry in plumbing::ls_files(&self.repo, &head, &staged)? {
+            files.insert(entry.name);
+        }
+        for file in files {
+            self.refresh(&head, &file)?;
+        }
+        Ok(())
+    }
+
+    /// Merge `rev` into the branch.  Conflicts are a Command error
+    /// whose kind is `crate::git::FailureKind::MergeConflict`, and
+    /// leave the branch and the snapshot alone.
+    pub(crate) fn merge(&self, rev: &str, msg: &str) -> Result<(), GitError> {
+        let head = self.head()?;
+        let rev = run_stdout(self.repo.git().args([
+            "rev-parse",
+            "--verify",
+            &format!("{rev}^{{commit}}"),
+        ]))?;
+        let _lock = self.lock()?;
+        for _ in 0..COMMIT_ATTEMPTS {
+            let tip = self.tip()?;
+            let new = if self.is_ancestor(&rev, &tip)? {
+                tip.clone()
+            } else if self.is_ancestor(&tip, &rev)? {
+                rev.clone()
+            } else {
+                let merged = run_stdout(self.repo.git().args([
+                    "merge-tree",
+                    "--write-tree",
+                    "--no-messages",
+                    &tip,
+                    &rev,
+                ]))?;
+                let Some(tree) = merged.lines().next() else {
+                    return Err(GitError::UnexpectedOutput {
+                        command: String::from("git merge-tree"),
+                        output: merged,
+                    });
+                };
+                plumbing::commit_tree(&self.repo, tree, &[&tip, &rev], msg)?
+            };
+            if self.update(&new, &tip)? {
+                return self.advance(&head, &new, &new);
+            }
+        }
+        Err(GitError::TooManyConcurrentWriters {
+            message: String::from(msg),
+        })
+    }
+
+    /// Take the repo's lock on the branch, for publishing a commit.
+    fn lock(&self) -> Result<crate::lock::Lock, GitError> {
+        Ok(crate::lock::Lock::acquire(
+            &self.repo,
+            &crate::lock::branch_lock_name(&self.branch),
+        )?)
+    }
+
+    fn branch_ref(&self) -> String {
+        format!("refs/heads/{}", self.branch)
+    }
+
+    /// The commit the snapshot is of.
+    pub(crate) fn head(&self) -> Result<String, GitError> {
+        match writers().iter().find(|(id, _)| *id == self.id) {
+            Some((_, writer)) => Ok(writer.head.clone()),
+            None => self.tip(),
+        }
+    }
+
+    /// Make the snapshot a snapshot of `commit`, which must have the
+    /// same files.
+    pub(crate) fn set_head(&self, commit: &str) {
+        if let Some((_, writer)) = writers().iter_mut().find(|(id, _)| *id == self.id) {
+            writer.head = String::from(commit);
+        }
+    }
+
+    /// The commit the branch is at now.
+    fn tip(&self) -> Result<String, GitError> {
+        run_stdout(
+            self.repo
+                .git()
+                .args(["rev-parse", "--verify", &self.branch_ref()]),
+        )
+    }
+
+    fn is_ancestor(&self, ancestor: &str, commit: &str) -> Result<bool, GitError> {
+        let result = self
+            .repo
+            .git()
+            .args(["merge-base", "--is-ancestor", ancestor, commit])
+            .output()?;
+        Ok(result.status.success())
+    }
+
+    /// How the files below the staged paths in the snapshot differ from
+    /// the commit `head`.
+    fn changes(&self, head: &str, staged: &[String]) -> Result<Changes, GitError> {
+        let staged: Vec<&str> = staged.iter().map(String::as_str).collect();
+        let base: std::collections::HashMap<String, plumbing::TreeEntry> =
+            plumbing::ls_files(&self.repo, head, &staged)?
+                .into_iter()
+                .map(|entry| (entry.name.clone(), entry))
+                .collect();
+
+        let mut files = std::collections::BTreeSet::<String>::new();
+        for path in staged {
+            list_files(&self.root, path, &mut files)?;
+        }
+        let files: Vec<String> = files.into_iter().collect();
+        let paths: Vec<std::path::PathBuf> =
+            files.iter().map(|file| self.root.join(file)).collect();
+        let hashes = plumbing::hash_files(&self.repo, &paths)?;
+
+        let mut changes = Changes::new();
+        for (file, hash) in files.iter().zip(hashes) {
+            let entry = match base.get(file) {
+                Some(entry) if entry.hash == hash => continue,
+                Some(entry) => plumbing::TreeEntry {
+                    hash,
+                    ..entry.clone()
+                },
+                None => plumbing::TreeEntry::blob(&hash, file),
+            };
+            changes.insert(file.clone(), Some(entry));
+        }
+        for file in base.keys() {
+            if files.binary_search(file).is_err() {
+                changes.insert(file.clone(), None);
+            }
+        }
+        Ok(changes)
+    }
+
+    /// Commit `changes` to the branch, on top of whatever it's at now.
+    /// `head` is the commit the changes were made to.
+    fn publish(&self, head: &str, changes: &Changes, msg: &str) -> Result<(), GitError> {
+        for _ in 0..COMMIT_ATTEMPTS {
+            let tip = self.tip()?;
+            if tip != head {
+                let conflicts = self.conflicts(head, &tip, changes)?;
+                if !conflicts.is_empty() {
+                    return Err(GitError::ConcurrentChangeConflict {
+                        message: String::from(msg),
+                        paths: conflicts,
+                    });
+                }
+            }
+            let tip_tree = run_stdout(
+                self.repo
+                    .git()
+                    .args(["rev-parse", &format!("{tip}^{{tree}}")]),
+            )?;
+            let changes = changes
+                .iter()
+                .map(|(path, entry)| (path.as_str(), entry.as_ref()))
+                .collect();
+            let tree = match apply(&self.repo, Some(&tip_tree), &changes)? {
+                Some(tree) => tree,
+                None => plumbing::mktree(&self.repo, &[])?,
+            };
+            let new = match tree == tip_tree {
+                // Someone else made the same changes.
+                true => tip.clone(),
+                false => plumbing::commit_tree(&self.repo, &tree, &[&tip], msg)?,
+            };
+            if self.update(&new, &tip)? {
+                return self.advance(head, &tip, &new);
+            }
+        }
+        Err(GitError::TooManyConcurrentWriters {
+            message: String::from(msg),
+        })
+    }
+
+    /// The changed files that were also changed, differently, between
+    /// `head` and `tip`.
+    fn conflicts(&self, head: &str, tip: &str, changes: &Changes) -> Result<Vec<String>, GitError> {
+        let paths: Vec<&str> = changes.keys().map(String::as_str).collect();
+        let hashes = |rev: &str| -> Result<std::collections::HashMap<String, String>, GitError> {
+            Ok(plumbing::ls_files(&self.repo, rev, &paths)?
+                .into_iter()
+                .map(|entry| (entry.name, entry.hash))
+                .collect())
+        };
+        let base = hashes(head)?;
+        let theirs = hashes(tip)?;
+        Ok(changes
+            .iter()
+            .filter(|(path, ours)| {
+                let theirs = theirs.get(*path);
+                theirs != base.get(*path) && theirs != ours.as_ref().map(|entry| &entry.hash)
+            })
+            .map(|(path, _)| path.clone())
+            .collect())
+    }
+
+    /// Move the branch from `old` to `new`, if it's still at `old`.
+    /// Returns false if someone else moved it first.
+    fn update(&self, new: &str, old: &str) -> Result<bool, GitError> {
+        if new == old {
+            return Ok(true);
+        }
+        match plumbing::update_ref(&self.repo, &self.branch_ref(), new, Some(old)) {
+            Ok(()) => Ok(true),
+            Err(e) => match self.tip()? == old {
+                true => Err(e),
+                false => Ok(false),
+            },
+        }
+    }
+
+    /// Move the snapshot from `head` to `new`.  The snapshot has the
+    /// changes from `tip` to `new` already (they're the ones we made),
+    /// the changes from `head` to `tip` (made by others) are copied in.
+    fn advance(&self, head: &str, tip: &str, new: &str) -> Result<(), GitError> {
+        if tip != head {
+            let output = run(self.repo.git().args([
+                "diff",
+                "--name-only",
+                "-z",
+                "--no-renames",
+                head,
+                tip,
+                "--",
+            ]))?;
+            let output = String::from_utf8_lossy(&output.stdout);
+            for path in output.split('\0').filter(|path| !path.is_empty()) {
+                self.refresh(new, path)?;
+            }
+        }
+        self.set_head(new);
+        Ok(())
+    }
+
+    /// Make the file `path` in the snapshot the same as in `rev`.
+    fn refresh(&self, rev: &str, path: &str) -> Result<(), GitError> {
+        let file = self.root.join(path);
+        let result = self
+            .repo
+            .git()
+            .args(["cat-file", "blob", &format!("{rev}:{path}")])
+            .output()?;
+        if result.status.success() {
+            if let Some(dir) = file.parent() {
+                std::fs::create_dir_all(dir)?;
+            }
+            std::fs::write(&file, &result.stdout)?;
+            return Ok(());
+        }
+        match std::fs::remove_file(&file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => (),
+        }
+        // Directories are only in git because of their files.
+        let mut dir = file.parent();
+        while let Some(d) = dir
+            && d != self.root
+            && std::fs::remove_dir(d).is_ok()
+        {
+            dir = d.parent();
+        }
+        Ok(())
+    }
+}
+
+/// Add the files below `path` in the snapshot at `root` to `files`.
+fn list_files(
+    root: &std::path::Path,
+    path: &str,
+    files: &mut std::collections::BTreeSet<String>,
+) -> Result<(), GitError> {
+    let full_path = match path {
+        "" => std::path::PathBuf::from(root),
+        path => root.join(path),
+    };
+    let metadata = match std::fs::symlink_metadata(&full_path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+        Ok(metadata) => metadata,
+    };
+    if metadata.is_file() {
+        files.insert(String::from(path));
+    } else if metadata.is_dir() {
+        for entry in std::fs::read_dir(&full_path)? {
+            let name = entry?.file_name();
+            let name = name.to_string_lossy();
+            let child = match path {
+                "" => String::from(name),
+                path => format!("{path}/{name}"),
+            };
+            list_files(root, &child, files)?;
+        }
+    }
+    Ok(())
+}
+
+/// The tree `tree` (None for an empty one) with `changes` made to it,
+/// None if nothing's left in it.  The paths of the changes are relative
+/// to the tree.
+pub(crate) fn apply(
+    repo: &crate::Repo,
+    tree: Option<&str>,
+    changes: &std::collections::BTreeMap<&str, Option<&plumbing::TreeEntry>>,
+) -> Result<Option<String>, GitError> {
+    let mut entries = std::collections::BTreeMap::<String, plumbing::TreeEntry>::new();
+    if let Some(tree) = tree {
+        for entry in plumbing::ls_tree(repo, tree)? {
+            entries.insert(entry.name.clone(), entry);
+        }
+    }
+
+    let mut subtrees = std::collections::BTreeMap::<
+        &str,
+        std::collections::BTreeMap<&str, Option<&plumbing::TreeEntry>>,
+    >::new();
+    for (path, change) in changes {
+        match (path.split_once('/'), change) {
+            (Some((dir, rest)), _) => {
+                subtrees.entry(dir).or_default().insert(rest, *change);
+            }
+            (None, Some(entry)) => {
+                entries.insert(
+                    String::from(*path),
+                    plumbing::TreeEntry {
+                        name: String::from(*path),
+                        ..(*entry).clone()
+                    },
+                );
+            }
+            (None, None) => {
+                entries.remove(*path);
+            }
+        }
+    }
+    for (dir, changes) in subtrees {
+        let subtree = entries
+            .get(dir)
+            .filter(|entry| entry.kind == "tree")
+            .map(|entry| entry.hash.clone());
+        match apply(repo, subtree.as_deref(), &changes)? {
+            Some(hash) => entries.insert(String::from(dir), plumbing::TreeEntry::tree(&hash, dir)),
+            None => entries.remove(dir),
+        };
+    }
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    let entries: Vec<plumbing::TreeEntry> = entries.into_values().collect();
+    Ok(Some(plumbing::mktree(repo, &entries)?))
+}
@@ -6,7 +6,10 @@ use std::str::FromStr;
 use log::debug;
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq, serde::Deserialize)]
-/// These are the states an issue can be in.
+/// These are the states an issue can be in.  The built-in states are
+/// always available, other states can be declared in the workflow
+/// section of the issue database's `config.toml` (see
+/// `crate::workflow::Workflow`).
 pub enum State {
     New,
     Backlog,
@@ -14,10 +17,33 @@ pub enum State {
     InProgress,
     Done,
     WontDo,
+    /// A state declared in the workflow configuration.  The name is
+    /// stored in lower case, like the names of the built-in states.
+    Custom(String),
 }
 
 pub type IssueHandle = String;
 
+/// The description that replaces the original description of a
+/// redacted Issue or Comment.
+pub const REDACTED_DESCRIPTION: &str = "[redacted]\n";
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+/// These are the types of links an issue can have to another issue.
+pub enum LinkType {
+    DuplicateOf,
+    RelatesTo,
+    Supersedes,
+    CausedBy,
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+/// A typed link from one issue to another.
+pub struct Link {
+    pub link_type: LinkType,
+    pub target: IssueHandle,
+}
+
 #[derive(Debug, ignorable::PartialEq)]
 pub struct Issue {
     pub id: String,
@@ -26,8 +52,25 @@ pub struct Issue {
     pub done_time: Option<chrono::DateTime<chrono::Local>>,
     pub tags: Vec<String>,
     pub state: State,
+    /// If the Issue was automatically moved to the Blocked state because
+    /// of unresolved dependencies, this is the state it was in before,
+    /// and will return to when its dependencies are resolved.
+    pub pre_blocked_state: Option<State>,
     pub dependencies: Option<Vec<IssueHandle>>,
+    pub parent: Option<IssueHandle>,
+    pub links: Vec<Link>,
     pub assignee: Option<String>,
+    /// The custom fields of the Issue (see `crate::field`), by name.
+    /// Fields declared in the database configuration have the declared
+    /// type, other fields are read as strings.
+    pub fields: std::collections::BTreeMap<String, crate::field::FieldValue>,
+    pub attachments: Vec<crate::attachment::Attachment>,
+    /// How long the work on the Issue is expected to take.
+    pub estimate: Option<chrono::TimeDelta>,
+    /// Time spent working on the Issue, oldest first.
+    pub worklog: Vec<crate::worklog::WorklogEntry>,
+    /// The name of the milestone the Issue belongs to.
+    pub milestone: Option<String>,
     pub description: String,
     pub comments: Vec<crate::comment::Comment>,
 
@@ -73,6 +116,24 @@ pub enum IssueError {
     DepExists,
     #[error("Self-dependency not allowed")]
     DepSelf,
+    #[error("Issue cannot be its own parent")]
+    ParentSelf,
+    #[error("Failed to parse link type")]
+    LinkTypeParseError,
+    #[error("Link not found")]
+    LinkNotFound,
+    #[error("Link already exists")]
+    LinkExists,
+    #[error("Self-link not allowed")]
+    LinkSelf,
+    #[error(transparent)]
+    FieldError(#[from] crate::field::FieldError),
+    #[error("comment {0} not found")]
+    CommentNotFound(String),
+    #[error(transparent)]
+    AttachmentError(#[from] crate::attachment::AttachmentError),
+    #[error(transparent)]
+    WorklogError(#[from] crate::worklog::WorklogError),
 }
 
 impl FromStr for State {
@@ -97,6 +158,34 @@ impl FromStr for State {
     }
 }
 
+impl State {
+    /// Parse a state name: one of the built-in states, or one of the
+    /// custom states declared in the workflow.
+    pub fn parse(s: &str, workflow: &crate::workflow::Workflow) -> Result<Self, IssueError> {
+        let state = Self::from_name(s)?;
+        if matches!(state, State::Custom(_)) && !workflow.has_state(&state) {
+            return Err(IssueError::StateParseError);
+        }
+        Ok(state)
+    }
+
+    /// Parse the name of a built-in state or of any custom state,
+    /// without checking that the custom state is declared anywhere.
+    pub(crate) fn from_name(s: &str) -> Result<Self, IssueError> {
+        if let Ok(state) = Self::from_str(s) {
+            return Ok(state);
+        }
+        if !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            Ok(State::Custom(s.to_lowercase()))
+        } else {
+            Err(IssueError::StateParseError)
+        }
+    }
+}
+
 impl fmt::Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let fmt_str = match self {
@@ -106,21 +195,85 @@ impl fmt::Display for State {
             State::InProgress => "inprogress",
             State::Done => "done",
             State::WontDo => "wontdo",
+            State::Custom(name) => name,
         };
         write!(f, "{fmt_str}")
     }
 }
 
+impl FromStr for LinkType {
+    type Err = IssueError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        if s == "duplicate-of" {
+            Ok(LinkType::DuplicateOf)
+        } else if s == "relates-to" {
+            Ok(LinkType::RelatesTo)
+        } else if s == "supersedes" {
+            Ok(LinkType::Supersedes)
+        } else if s == "caused-by" {
+            Ok(LinkType::CausedBy)
+        } else {
+            Err(IssueError::LinkTypeParseError)
+        }
+    }
+}
+
+impl fmt::Display for LinkType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fmt_str = match self {
+            LinkType::DuplicateOf => "duplicate-of",
+            LinkType::RelatesTo => "relates-to",
+            LinkType::Supersedes => "supersedes",
+            LinkType::CausedBy => "caused-by",
+        };
+        write!(f, "{fmt_str}")
+    }
+}
+
+impl LinkType {
+    /// The name of this link type as seen from the target of the
+    /// link.  If A is a "duplicate-of" B, then B is "duplicated-by" A.
+    pub fn reverse_name(&self) -> &'static str {
+        match self {
+            LinkType::DuplicateOf => "duplicated-by",
+            LinkType::RelatesTo => "relates-to",
+            LinkType::Supersedes => "superseded-by",
+            LinkType::CausedBy => "causes",
+        }
+    }
+}
+
 // This is the public API of Issue.
 impl Issue {
+    /// Read an Issue from its directory.  Only the built-in states are
+    /// accepted, use `new_from_dir_with_workflow()` for issues that may
+    /// be in custom states.
     pub fn new_from_dir(dir: &std::path::Path) -> Result<Self, IssueError> {
+        Self::new_from_dir_with_workflow(dir, &crate::workflow::Workflow::default())
+    }
+
+    /// Read an Issue from its directory, accepting the custom states
+    /// declared in the workflow.
+    pub fn new_from_dir_with_workflow(
+        dir: &std::path::Path,
+        workflow: &crate::workflow::Workflow,
+    ) -> Result<Self, IssueError> {
         let mut author: Option<String> = None;
         let mut creation_time: Option<chrono::DateTime<chrono::Local>> = None;
         let mut description: Option<String> = None;
         let mut state = State::New; // default state, if not specified in the issue
+        let mut pre_blocked_state: Option<State> = None;
         let mut dependencies: Option<Vec<String>> = None;
+        let mut parent: Option<IssueHandle> = None;
+        let mut links = Vec::<Link>::new();
         let mut comments = Vec::<crate::comment::Comment>::new();
         let mut assignee: Option<String> = None;
+        let mut fields = std::collections::BTreeMap::<String, crate::field::FieldValue>::new();
+        let mut attachments = Vec::<crate::attachment::Attachment>::new();
+        let mut estimate: Option<chrono::TimeDelta> = None;
+        let mut worklog = Vec::<crate::worklog::WorklogEntry>::new();
+        let mut milestone: Option<String> = None;
         let mut tags = Vec::<String>::new();
         let mut done_time: Option<chrono::DateTime<chrono::Local>> = None;
 
@@ -137,7 +290,10 @@ impl Issue {
                 description = Some(std::fs::read_to_string(direntry.path())?);
             } else if file_name == "state" {
                 let state_string = std::fs::read_to_string(direntry.path())?;
-                state = State::from_str(state_string.trim())?;
+                state = State::parse(state_string.trim(), workflow)?;
+            } else if file_name == "pre_blocked_state" {
+                let state_string = std::fs::read_to_string(direntry.path())?;
+                pre_blocked_state = Some(State::parse(state_string.trim(), workflow)?);
             } else if file_name == "assignee" {
                 assignee = Some(String::from(
                     std::fs::read_to_string(direntry.path())?.trim(),
@@ -149,6 +305,26 @@ impl Issue {
                 done_time = Some(raw_done_time.into());
             } else if file_name == "dependencies" && direntry.metadata()?.is_dir() {
                 dependencies = Self::read_dependencies(&direntry.path())?;
+            } else if file_name == "parent" {
+                parent = Some(String::from(
+                    std::fs::read_to_string(direntry.path())?.trim(),
+                ));
+            } else if file_name == "links" && direntry.metadata()?.is_dir() {
+                links = Self::read_links(&direntry.path())?;
+            } else if file_name == "fields" && direntry.metadata()?.is_dir() {
+                fields = Self::read_fields(&direntry.path())?;
+            } else if file_name == "milestone" {
+                milestone = Some(String::from(
+                    std::fs::read_to_string(direntry.path())?.trim(),
+                ));
+            } else if file_name == "estimate" {
+                estimate = Some(crate::worklog::parse_duration(&std::fs::read_to_string(
+                    direntry.path(),
+                )?)?);
+            } else if file_name == "worklog" && direntry.metadata()?.is_dir() {
+                worklog = crate::worklog::read_worklog(&direntry.path())?;
+            } else if file_name == "attachments" && direntry.metadata()?.is_dir() {
+                attachments = crate::attachment::read_attachments(&direntry.path())?;
             } else if file_name == "tags" {
                 tags = Self::read_tags(&direntry)?;
             } else if file_name == "comments" && direntry.metadata()?.is_dir() {
@@ -196,8 +372,16 @@ impl Issue {
             done_time,
             tags,
             state,
+            pre_blocked_state,
             dependencies,
+            parent,
+            links,
             assignee,
+            fields,
+            attachments,
+            estimate,
+            worklog,
+            milestone,
             description,
             comments,
             dir: std::path::PathBuf::from(dir),
@@ -218,6 +402,35 @@ impl Issue {
         Ok(comment)
     }
 
+    /// Add a reply to a Comment on this Issue.  Commits.
+    pub fn add_reply(
+        &mut self,
+        in_reply_to: &str,
+        description: &Option<String>,
+    ) -> Result<crate::comment::Comment, IssueError> {
+        if self.get_comment(in_reply_to).is_none() {
+            return Err(IssueError::CommentNotFound(String::from(in_reply_to)));
+        }
+        let comment = crate::comment::Comment::new_reply(self, description, Some(in_reply_to))?;
+        Ok(comment)
+    }
+
+    /// Return the replies to a Comment on this Issue, or the top-level
+    /// comments (comments that are not replies to a comment on this
+    /// Issue) if `comment_uuid` is None.  Sorted by creation time.
+    pub fn replies(&self, comment_uuid: Option<&str>) -> Vec<&crate::comment::Comment> {
+        self.comments
+            .iter()
+            .filter(|comment| match comment_uuid {
+                Some(uuid) => comment.in_reply_to.as_deref() == Some(uuid),
+                None => match &comment.in_reply_to {
+                    None => true,
+                    Some(parent) => self.get_comment(parent).is_none(),
+                },
+            })
+            .collect()
+    }
+
     /// Create a new Issue in an Issues database specified by a directory.
     /// The new Issue will live in a new subdirectory, named by a unique
     /// Issue identifier.
@@ -231,50 +444,40 @@ impl Issue {
     /// On success, the new Issue with its valid description is committed
     /// to the Issues database.
     pub fn new(dir: &std::path::Path, description: &Option<String>) -> Result<Self, IssueError> {
-        let mut issue_dir = std::path::PathBuf::from(dir);
-        let rnd: u128 = rand::random();
-        let issue_id = format!("{rnd:032x}");
-        issue_dir.push(&issue_id);
-        std::fs::create_dir(&issue_dir)?;
-
-        let mut issue = Self {
-            id: String::from(&issue_id),
-            author: crate::git::get_user_name_email(&issue_dir)?,
-            creation_time: chrono::Local::now(),
-            done_time: None,
-            tags: Vec::<String>::new(),
-            state: State::New,
-            dependencies: None,
-            assignee: None,
-            description: String::from(""), // FIXME: kind of bogus to use the empty string as None
-            comments: Vec::<crate::comment::Comment>::new(),
-            dir: issue_dir.clone(),
-        };
-
-        match description {
-            Some(description) => {
-                if description.is_empty() {
-                    return Err(IssueError::EmptyDescription);
-                }
-                issue.description = String::from(description);
-                let description_filename = issue.description_filename();
-                let mut description_file = std::fs::File::create(&description_filename)?;
-                write!(description_file, "{description}")?;
-            }
-            None => issue.edit_description_file()?,
-        };
-
-        let author_filename = issue.author_filename();
-        let mut author_file = std::fs::File::create(&author_filename)?;
-        write!(author_file, "{}", &issue.author)?;
-
-        let creation_time_filename = issue.creation_time_filename();
-        let mut creation_time_file = std::fs::File::create(&creation_time_filename)?;
-        write!(creation_time_file, "{}", issue.creation_time.to_rfc3339())?;
+        Self::new_from_template(dir, description, &crate::template::Template::default())
+    }
 
-        issue.commit(&format!("create new issue {issue_id}"))?;
+    /// Create a new Issue from a Template.  If no description is
+    /// supplied, the user edits the description starting from the
+    /// template's description.  The tags, state, assignee and custom
+    /// fields of the template are set on the new Issue.  Commits.
+    ///
+    /// This does not check the template against the database
+    /// configuration, use `IssuesMut::new_issue()` for that.
+    pub fn new_from_template(
+        dir: &std::path::Path,
+        description: &Option<String>,
+        template: &crate::template::Template,
+    ) -> Result<Self, IssueError> {
+        Self::create(
+            dir,
+            description,
+            template.description.as_deref(),
+            &crate::front_matter::Metadata::from(template),
+        )
+    }
 
-        Ok(issue)
+    /// Create a new Issue with the specified description and metadata.
+    /// Commits.
+    ///
+    /// This does not check the metadata against the issues database,
+    /// use `IssuesMut::new_issue_full()` for that.
+    pub fn new_from_metadata(
+        dir: &std::path::Path,
+        description: &str,
+        metadata: &crate::front_matter::Metadata,
+    ) -> Result<Self, IssueError> {
+        Self::create(dir, &Some(String::from(description)), None, metadata)
     }
 
     /// Interactively edit the description of an existing Issue.
@@ -303,12 +506,16 @@ impl Issue {
 
     /// Change the State of the Issue.  If the new state is `Done`,
     /// set the Issue `done_time`.  Commits.
+    ///
+    /// Setting the state by hand overrides automatic blocking, so this
+    /// forgets any `pre_blocked_state`.
+    ///
+    /// This doesn't check the state or the transition against the
+    /// database workflow, use `IssuesMut::set_state()` for that.
     pub fn set_state(&mut self, new_state: State) -> Result<(), IssueError> {
         let old_state = self.state.clone();
-        let mut state_filename = std::path::PathBuf::from(&self.dir);
-        state_filename.push("state");
-        let mut state_file = std::fs::File::create(&state_filename)?;
-        write!(state_file, "{new_state}")?;
+        self.write_state(&new_state)?;
+        self.write_pre_blocked_state(None)?;
         self.commit(&format!(
             "change state of issue {}, {} -> {}",
             self.dir
@@ -318,12 +525,52 @@ impl Issue {
             old_state,
             new_state,
         ))?;
+        self.state = new_state.clone();
+        self.pre_blocked_state = None;
         if new_state == State::Done {
             self.set_done_time(chrono::Local::now())?;
         }
         Ok(())
     }
 
+    /// Move the Issue to the Blocked state because of the specified
+    /// unresolved dependencies, remembering the current state so
+    /// `unblock()` can restore it.  Commits.
+    pub(crate) fn block(&mut self, blockers: &[&IssueHandle]) -> Result<(), IssueError> {
+        let old_state = self.state.clone();
+        self.write_state(&State::Blocked)?;
+        self.write_pre_blocked_state(Some(&old_state))?;
+        let blockers: Vec<&str> = blockers.iter().map(|b| b.as_str()).collect();
+        self.commit(&format!(
+            "issue {} blocked by unresolved dependencies {}, {} -> {}",
+            self.id,
+            blockers.join(", "),
+            old_state,
+            State::Blocked,
+        ))?;
+        self.state = State::Blocked;
+        self.pre_blocked_state = Some(old_state);
+        Ok(())
+    }
+
+    /// Move an automatically blocked Issue back to the state it was in
+    /// before it was blocked.  Does nothing if the Issue was not blocked
+    /// by `block()`.  Commits.
+    pub(crate) fn unblock(&mut self) -> Result<(), IssueError> {
+        let Some(new_state) = self.pre_blocked_state.clone() else {
+            return Ok(());
+        };
+        self.write_state(&new_state)?;
+        self.write_pre_blocked_state(None)?;
+        self.commit(&format!(
+            "issue {} unblocked, all dependencies resolved, {} -> {}",
+            self.id, self.state, new_state,
+        ))?;
+        self.state = new_state;
+        self.pre_blocked_state = None;
+        Ok(())
+    }
+
     /// Set the `done_time` of the Issue.  Commits.
     pub fn set_done_time(
         &mut self,
@@ -367,6 +614,76 @@ impl Issue {
         Ok(())
     }
 
+    /// Set or clear the milestone of the Issue.  Commits.
+    ///
+    /// This does not check that the milestone exists, use
+    /// `IssuesMut::set_milestone()` for that.
+    pub fn set_milestone(&mut self, milestone: Option<&str>) -> Result<(), IssueError> {
+        if self.milestone.as_deref() == milestone {
+            return Ok(());
+        }
+        let old_milestone = self.milestone.take();
+        self.write_optional_file("milestone", milestone)?;
+        self.milestone = milestone.map(String::from);
+        self.commit(&format!(
+            "change milestone of issue {}, {} -> {}",
+            self.id,
+            old_milestone.as_deref().unwrap_or("None"),
+            milestone.unwrap_or("None"),
+        ))?;
+        Ok(())
+    }
+
+    /// Set or clear the estimate of the Issue.  Commits.
+    pub fn set_estimate(&mut self, estimate: Option<chrono::TimeDelta>) -> Result<(), IssueError> {
+        let old_estimate = match &self.estimate {
+            Some(estimate) => crate::worklog::format_duration(estimate),
+            None => String::from("None"),
+        };
+        let new_estimate = estimate.as_ref().map(crate::worklog::format_duration);
+        self.write_optional_file("estimate", new_estimate.as_deref())?;
+        self.estimate = estimate;
+        self.commit(&format!(
+            "change estimate of issue {}, {} -> {}",
+            self.id,
+            old_estimate,
+            new_estimate.as_deref().unwrap_or("None"),
+        ))?;
+        Ok(())
+    }
+
+    /// Add an entry to the worklog of the Issue.  Commits.
+    pub fn log_work(
+        &mut self,
+        user: &str,
+        start: chrono::DateTime<chrono::Local>,
+        duration: chrono::TimeDelta,
+        note: Option<&str>,
+    ) -> Result<(), IssueError> {
+        let rnd: u128 = rand::random();
+        let entry = crate::worklog::WorklogEntry {
+            uuid: format!("{rnd:032x}"),
+            user: String::from(user),
+            start,
+            duration,
+            note: note.map(String::from),
+        };
+        entry.write(&self.dir)?;
+        self.commit(&format!(
+            "log {} of work on issue {}",
+            crate::worklog::format_duration(&duration),
+            self.id
+        ))?;
+        self.worklog.push(entry);
+        self.worklog.sort_by_key(|entry| entry.start);
+        Ok(())
+    }
+
+    /// The total time logged on the Issue.
+    pub fn logged_time(&self) -> chrono::TimeDelta {
+        self.worklog.iter().map(|entry| entry.duration).sum()
+    }
+
     /// Add a new Tag to the Issue.  Commits.
     pub fn add_tag(&mut self, tag: &str) -> Result<(), IssueError> {
         let tag_string = String::from(tag);
@@ -457,10 +774,427 @@ impl Issue {
         self.commit(&format!("remove dep {} from issue {}", dep, self.id))?;
         Ok(())
     }
+
+    /// Set the parent of the Issue, making this Issue a child of the
+    /// parent.  Commits.
+    ///
+    /// This does not check for cycles in the issue hierarchy, use
+    /// `IssuesMut::set_parent()` for that.
+    pub fn set_parent(&mut self, parent: &IssueHandle) -> Result<(), IssueError> {
+        if &self.id == parent {
+            return Err(IssueError::ParentSelf);
+        }
+        let old_parent = match &self.parent {
+            Some(old_parent) => old_parent.clone(),
+            None => String::from("None"),
+        };
+        let mut parent_filename = std::path::PathBuf::from(&self.dir);
+        parent_filename.push("parent");
+        let mut parent_file = std::fs::File::create(&parent_filename)?;
+        write!(parent_file, "{parent}")?;
+        self.parent = Some(parent.clone());
+        self.commit(&format!(
+            "change parent of issue {}, {} -> {}",
+            self.id, old_parent, parent,
+        ))?;
+        Ok(())
+    }
+
+    /// Add a typed link from this Issue to another Issue.  Commits.
+    pub fn add_link(
+        &mut self,
+        link_type: LinkType,
+        target: &IssueHandle,
+    ) -> Result<(), IssueError> {
+        if &self.id == target {
+            return Err(IssueError::LinkSelf);
+        }
+        let link = Link {
+            link_type,
+            target: target.clone(),
+        };
+        if self.links.contains(&link) {
+            return Err(IssueError::LinkExists);
+        }
+
+        let mut link_filename = std::path::PathBuf::from(&self.dir);
+        link_filename.push("links");
+        link_filename.push(link_type.to_string());
+        std::fs::create_dir_all(&link_filename)?;
+        link_filename.push(target);
+        std::fs::File::create(&link_filename)?;
+
+        self.links.push(link);
+        self.links.sort();
+        self.commit(&format!(
+            "issue {} add link {} {}",
+            self.id, link_type, target
+        ))?;
+        Ok(())
+    }
+
+    /// Remove a typed link from this Issue to another Issue.  Commits.
+    pub fn remove_link(
+        &mut self,
+        link_type: LinkType,
+        target: &IssueHandle,
+    ) -> Result<(), IssueError> {
+        let Some(index) = self
+            .links
+            .iter()
+            .position(|l| l.link_type == link_type && &l.target == target)
+        else {
+            return Err(IssueError::LinkNotFound);
+        };
+        self.links.remove(index);
+
+        let mut link_filename = std::path::PathBuf::from(&self.dir);
+        link_filename.push("links");
+        link_filename.push(link_type.to_string());
+        link_filename.push(target);
+        std::fs::remove_file(&link_filename)?;
+
+        self.commit(&format!(
+            "issue {} remove link {} {}",
+            self.id, link_type, target
+        ))?;
+        Ok(())
+    }
+
+    /// Set a custom field of the Issue, or remove it if `value` is None.
+    /// Commits.
+    ///
+    /// This does not check the value against the field declarations in
+    /// the database configuration, use `IssuesMut::set_field()` for that.
+    pub fn set_field(
+        &mut self,
+        name: &str,
+        value: Option<crate::field::FieldValue>,
+    ) -> Result<(), IssueError> {
+        crate::field::validate_name(name)?;
+        let old_value = match self.fields.get(name) {
+            Some(old_value) => old_value.to_string(),
+            None => String::from("None"),
+        };
+        let mut field_filename = std::path::PathBuf::from(&self.dir);
+        field_filename.push("fields");
+        field_filename.push(name);
+        let new_value = match value {
+            Some(value) => {
+                std::fs::create_dir_all(field_filename.parent().unwrap())?;
+                let mut field_file = std::fs::File::create(&field_filename)?;
+                write!(field_file, "{value}")?;
+                let new_value = value.to_string();
+                self.fields.insert(String::from(name), value);
+                new_value
+            }
+            None => {
+                match std::fs::remove_file(&field_filename) {
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                    Err(e) => return Err(e.into()),
+                    Ok(_) => (),
+                }
+                self.fields.remove(name);
+                String::from("None")
+            }
+        };
+        self.commit(&format!(
+            "change field {} of issue {}, {} -> {}",
+            name, self.id, old_value, new_value,
+        ))?;
+        Ok(())
+    }
+
+    /// Return the metadata of the Issue, as shown in the front matter
+    /// of `ent edit --full`.
+    pub fn metadata(&self) -> crate::front_matter::Metadata {
+        crate::front_matter::Metadata {
+            state: Some(self.state.to_string()),
+            assignee: self.assignee.clone(),
+            tags: self.tags.clone(),
+            dependencies: self.dependencies.clone().unwrap_or_default(),
+            parent: self.parent.clone(),
+            milestone: self.milestone.clone(),
+            done_time: self.done_time.map(|done_time| done_time.to_rfc3339()),
+            links: self
+                .links
+                .iter()
+                .fold(std::collections::BTreeMap::new(), |mut links, link| {
+                    links
+                        .entry(link.link_type.to_string())
+                        .or_insert_with(Vec::new)
+                        .push(link.target.clone());
+                    links
+                }),
+            fields: self
+                .fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Replace the description and all the metadata of the Issue.
+    /// Commits all the changes together.
+    ///
+    /// This does not check the metadata against the issues database,
+    /// use `IssuesMut::edit_issue_full()` for that.
+    pub fn edit_full(
+        &mut self,
+        description: &str,
+        metadata: &crate::front_matter::Metadata,
+    ) -> Result<(), IssueError> {
+        if description.is_empty() {
+            return Err(IssueError::EmptyDescription);
+        }
+        self.write_metadata(metadata)?;
+        std::fs::write(self.description_filename(), description)?;
+        self.description = String::from(description);
+        self.commit(&format!("edit issue {}", self.id))?;
+        Ok(())
+    }
+
+    /// Delete the Issue, with all its comments.  Commits.
+    pub fn remove(self) -> Result<(), IssueError> {
+        std::fs::remove_dir_all(&self.dir)?;
+        crate::git::add(&self.dir)?;
+        let Some(db_dir) = self.dir.parent() else {
+            return Err(IssueError::IdError);
+        };
+        crate::git::commit(db_dir, &format!("remove issue {}", self.id))?;
+        Ok(())
+    }
+
+    /// Replace the description of the Issue with a tombstone, keeping
+    /// all its metadata.  Commits.
+    ///
+    /// The original description is still in the git history.
+    pub fn redact(&mut self) -> Result<(), IssueError> {
+        std::fs::write(self.description_filename(), REDACTED_DESCRIPTION)?;
+        self.description = String::from(REDACTED_DESCRIPTION);
+        self.commit(&format!("redact issue {}", self.id))?;
+        Ok(())
+    }
+
+    /// Delete a Comment from the Issue.  Replies to the Comment are
+    /// kept.  Commits.
+    pub fn remove_comment(&mut self, comment_uuid: &str) -> Result<(), IssueError> {
+        let Some(index) = self.comments.iter().position(|c| c.uuid == comment_uuid) else {
+            return Err(IssueError::CommentNotFound(String::from(comment_uuid)));
+        };
+        let comment = self.comments.remove(index);
+        std::fs::remove_dir_all(&comment.dir)?;
+        crate::git::add(&comment.dir)?;
+        crate::git::commit(
+            &self.dir,
+            &format!("remove comment {} from issue {}", comment_uuid, self.id),
+        )?;
+        Ok(())
+    }
+
+    /// Move a Comment from this Issue to another Issue.  Commits.
+    pub fn move_comment(
+        &mut self,
+        comment_uuid: &str,
+        target: &mut Issue,
+    ) -> Result<(), IssueError> {
+        let Some(index) = self.comments.iter().position(|c| c.uuid == comment_uuid) else {
+            return Err(IssueError::CommentNotFound(String::from(comment_uuid)));
+        };
+        let mut comment = self.comments.remove(index);
+        comment.move_to(&target.dir)?;
+        target.comments.push(comment);
+        target.comments.sort_by_key(|c| c.creation_time);
+        self.commit_with(
+            target,
+            &format!(
+                "move comment {} from issue {} to issue {}",
+                comment_uuid, self.id, target.id
+            ),
+        )
+    }
+
+    /// Fold another Issue into this one: move all its comments here,
+    /// and add its tags and the specified dependencies to this Issue.
+    /// The other Issue is left otherwise unchanged.  Commits.
+    ///
+    /// The caller chooses which of the other Issue's dependencies to
+    /// add, use `IssuesMut::merge_issues()` to avoid dependency cycles.
+    pub fn merge_from(
+        &mut self,
+        other: &mut Issue,
+        dependencies: &[IssueHandle],
+    ) -> Result<(), IssueError> {
+        for mut comment in other.comments.drain(..) {
+            comment.move_to(&self.dir)?;
+            self.comments.push(comment);
+        }
+        self.comments.sort_by_key(|c| c.creation_time);
+
+        for tag in &other.tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+        self.tags.sort();
+        self.write_tags()?;
+
+        for dep in dependencies {
+            if dep == &self.id {
+                continue;
+            }
+            let mut dep_filename = self.dir.clone();
+            dep_filename.push("dependencies");
+            std::fs::create_dir_all(&dep_filename)?;
+            dep_filename.push(dep);
+            std::fs::File::create(&dep_filename)?;
+            let deps = self.dependencies.get_or_insert_with(Vec::new);
+            if !deps.contains(dep) {
+                deps.push(dep.clone());
+                deps.sort();
+            }
+        }
+
+        self.commit_with(
+            other,
+            &format!("merge issue {} into issue {}", other.id, self.id),
+        )
+    }
+
+    /// Attach a file to the Issue.  If `use_blobs` is true the contents
+    /// go in the database's blob store (see `crate::attachment`).
+    /// Commits.
+    pub fn add_attachment(
+        &mut self,
+        file: &std::path::Path,
+        name: &str,
+        use_blobs: bool,
+    ) -> Result<crate::attachment::Attachment, IssueError> {
+        let Some(db_dir) = self.dir.parent() else {
+            return Err(IssueError::IdError);
+        };
+        let attachment =
+            crate::attachment::add_attachment(&self.dir, db_dir, file, name, use_blobs)?;
+        self.attachments.push(attachment.clone());
+        self.attachments.sort();
+        crate::git::add(&self.dir)?;
+        if use_blobs {
+            let mut blobs_dir = std::path::PathBuf::from(db_dir);
+            blobs_dir.push("blobs");
+            crate::git::add(&blobs_dir)?;
+        }
+        self.commit(&format!("add attachment {} to issue {}", name, self.id))?;
+        Ok(attachment)
+    }
+
+    /// Read the contents of an attachment of the Issue.
+    pub fn read_attachment(&self, name: &str) -> Result<Vec<u8>, IssueError> {
+        let Some(attachment) = self.attachments.iter().find(|a| a.name == name) else {
+            return Err(crate::attachment::AttachmentError::NotFound(String::from(name)).into());
+        };
+        let Some(db_dir) = self.dir.parent() else {
+            return Err(IssueError::IdError);
+        };
+        Ok(crate::attachment::read_contents(
+            &self.dir, db_dir, attachment,
+        )?)
+    }
+
+    /// Remove the parent of the Issue, making it a top-level issue.
+    /// Commits.
+    pub fn clear_parent(&mut self) -> Result<(), IssueError> {
+        let Some(old_parent) = self.parent.take() else {
+            return Ok(());
+        };
+        let mut parent_filename = std::path::PathBuf::from(&self.dir);
+        parent_filename.push("parent");
+        match std::fs::remove_file(&parent_filename) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e.into()),
+            Ok(_) => (),
+        }
+        self.commit(&format!(
+            "remove parent {} from issue {}",
+            old_parent, self.id
+        ))?;
+        Ok(())
+    }
 }
 
 // This is the internal/private API of Issue.
 impl Issue {
+    /// Create a new Issue.  If no description is supplied, the user
+    /// edits the description, starting from `initial_description`.
+    /// Commits.
+    fn create(
+        dir: &std::path::Path,
+        description: &Option<String>,
+        initial_description: Option<&str>,
+        metadata: &crate::front_matter::Metadata,
+    ) -> Result<Self, IssueError> {
+        let mut issue_dir = std::path::PathBuf::from(dir);
+        let rnd: u128 = rand::random();
+        let issue_id = format!("{rnd:032x}");
+        issue_dir.push(&issue_id);
+        std::fs::create_dir(&issue_dir)?;
+
+        let mut issue = Self {
+            id: String::from(&issue_id),
+            author: crate::git::get_user_name_email(&issue_dir)?,
+            creation_time: chrono::Local::now(),
+            done_time: None,
+            tags: Vec::<String>::new(),
+            state: State::New,
+            pre_blocked_state: None,
+            dependencies: None,
+            parent: None,
+            links: Vec::<Link>::new(),
+            assignee: None,
+            fields: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            estimate: None,
+            worklog: Vec::new(),
+            milestone: None,
+            description: String::from(""), // FIXME: kind of bogus to use the empty string as None
+            comments: Vec::<crate::comment::Comment>::new(),
+            dir: issue_dir.clone(),
+        };
+
+        match description {
+            Some(description) => {
+                if description.is_empty() {
+                    return Err(IssueError::EmptyDescription);
+                }
+                issue.description = String::from(description);
+                let description_filename = issue.description_filename();
+                let mut description_file = std::fs::File::create(&description_filename)?;
+                write!(description_file, "{description}")?;
+            }
+            None => {
+                if let Some(initial_description) = initial_description {
+                    let description_filename = issue.description_filename();
+                    let mut description_file = std::fs::File::create(&description_filename)?;
+                    write!(description_file, "{initial_description}")?;
+                }
+                issue.edit_description_file()?
+            }
+        };
+
+        issue.write_metadata(metadata)?;
+
+        let author_filename = issue.author_filename();
+        let mut author_file = std::fs::File::create(&author_filename)?;
+        write!(author_file, "{}", &issue.author)?;
+
+        let creation_time_filename = issue.creation_time_filename();
+        let mut creation_time_file = std::fs::File::create(&creation_time_filename)?;
+        write!(creation_time_file, "{}", issue.creation_time.to_rfc3339())?;
+
+        issue.commit(&format!("create new issue {issue_id}"))?;
+
+        Ok(issue)
+    }
+
     fn description_filename(&self) -> std::path::PathBuf {
         let mut description_filename = std::path::PathBuf::from(&self.dir);
         description_filename.push("description");
@@ -479,6 +1213,33 @@ impl Issue {
         creation_time_filename
     }
 
+    fn write_state(&self, state: &State) -> Result<(), IssueError> {
+        let mut state_filename = std::path::PathBuf::from(&self.dir);
+        state_filename.push("state");
+        let mut state_file = std::fs::File::create(&state_filename)?;
+        write!(state_file, "{state}")?;
+        Ok(())
+    }
+
+    /// Write the `pre_blocked_state` file, or remove it if `state` is None.
+    fn write_pre_blocked_state(&self, state: Option<&State>) -> Result<(), IssueError> {
+        let mut pre_blocked_state_filename = std::path::PathBuf::from(&self.dir);
+        pre_blocked_state_filename.push("pre_blocked_state");
+        match state {
+            Some(state) => {
+                let mut pre_blocked_state_file =
+                    std::fs::File::create(&pre_blocked_state_filename)?;
+                write!(pre_blocked_state_file, "{state}")?;
+            }
+            None => match std::fs::remove_file(&pre_blocked_state_filename) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e.into()),
+                Ok(_) => (),
+            },
+        }
+        Ok(())
+    }
+
     /// Read the Issue's description file into the internal Issue representation.
     fn read_description(&mut self) -> Result<(), IssueError> {
         let description_filename = self.description_filename();
@@ -498,7 +1259,9 @@ impl Issue {
         }
 
         let description_filename = self.description_filename();
-        let exists = description_filename.exists();
+        // A new Issue may have a description file pre-filled from a
+        // template, but there's no committed description to go back to.
+        let exists = description_filename.exists() && !self.description.is_empty();
         let editor = match std::env::var("EDITOR") {
             Ok(editor) => editor,
             Err(std::env::VarError::NotPresent) => String::from("vi"),
@@ -509,8 +1272,6 @@ impl Issue {
             .spawn()?
             .wait_with_output()?;
         if !result.status.success() {
-            println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
-            println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
             return Err(IssueError::EditorError);
         }
         if !description_filename.exists() || description_filename.metadata()?.len() == 0 {
@@ -530,7 +1291,8 @@ impl Issue {
 
     fn read_tags(tags_direntry: &std::fs::DirEntry) -> Result<Vec<String>, IssueError> {
         if !tags_direntry.metadata()?.is_dir() {
-            eprintln!("issue has old-style tags file");
+            #[cfg(feature = "log")]
+            debug!("issue has old-style tags file");
             return Err(IssueError::IssueParseError);
         }
         let mut tags = Vec::<String>::new();
@@ -545,7 +1307,7 @@ impl Issue {
     /// Perform un-escape on a filename to make it into a tag:
     /// ",0" => ","
     /// ",1" => "/"
-    fn tag_from_filename(filename: &str) -> Result<String, IssueError> {
+    pub(crate) fn tag_from_filename(filename: &str) -> Result<String, IssueError> {
         let mut tag = String::new();
         let mut token_iter = filename.split(',');
         let Some(start) = token_iter.next() else {
@@ -583,7 +1345,7 @@ impl Issue {
     // Perform escape on a tag to make it into a filename:
     // "," => ",0"
     // "/" => ",1"
-    fn tag_to_filename(tag: &str) -> String {
+    pub(crate) fn tag_to_filename(tag: &str) -> String {
         let mut filename = tag.replace(",", ",0");
         filename = filename.replace("/", ",1");
         filename
@@ -597,7 +1359,7 @@ impl Issue {
             let comment = crate::comment::Comment::new_from_dir(&direntry.path())?;
             comments.push(comment);
         }
-        comments.sort_by(|a, b| a.creation_time.cmp(&b.creation_time));
+        comments.sort_by_key(|c| c.creation_time);
         Ok(())
     }
 
@@ -619,29 +1381,220 @@ impl Issue {
         Ok(dependencies)
     }
 
-    fn commit_tags(&self, commit_message: &str) -> Result<(), IssueError> {
-        let mut tags_dir_name = self.dir.clone();
-        tags_dir_name.push("tags");
-        match std::fs::remove_dir_all(&tags_dir_name) {
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
-            Err(e) => return Err(e.into()),
-            Ok(_) => (),
+    fn read_links(dir: &std::path::Path) -> Result<Vec<Link>, IssueError> {
+        let mut links = Vec::<Link>::new();
+        for type_direntry in (dir.read_dir()?).flatten() {
+            // Link types we don't know (from a newer entomologist) are
+            // left alone.
+            let Ok(link_type) = LinkType::from_str(&type_direntry.file_name().to_string_lossy())
+            else {
+                continue;
+            };
+            for direntry in (type_direntry.path().read_dir()?).flatten() {
+                links.push(Link {
+                    link_type,
+                    target: direntry.file_name().to_string_lossy().into_owned(),
+                });
+            }
+        }
+        links.sort();
+        Ok(links)
+    }
+
+    fn read_fields(
+        dir: &std::path::Path,
+    ) -> Result<std::collections::BTreeMap<String, crate::field::FieldValue>, IssueError> {
+        let mut fields = std::collections::BTreeMap::<String, crate::field::FieldValue>::new();
+        for direntry in (dir.read_dir()?).flatten() {
+            let name = direntry.file_name().to_string_lossy().into_owned();
+            let value = String::from(std::fs::read_to_string(direntry.path())?.trim());
+            fields.insert(name, crate::field::FieldValue::String(value));
         }
-        std::fs::create_dir(&tags_dir_name)?;
+        Ok(fields)
+    }
+
+    fn commit_tags(&self, commit_message: &str) -> Result<(), IssueError> {
+        self.write_tags()?;
+        self.commit(commit_message)?;
+        Ok(())
+    }
+
+    fn write_tags(&self) -> Result<(), IssueError> {
+        let tags_dir_name = self.clear_subdir("tags")?;
         for tag in &self.tags {
             let mut tag_filename = tags_dir_name.clone();
             tag_filename.push(Issue::tag_to_filename(tag));
             std::fs::File::create(&tag_filename)?;
         }
-        self.commit(commit_message)?;
+        Ok(())
+    }
+
+    /// Remove a subdirectory of the Issue directory and everything in
+    /// it, and make a new empty one.  Returns the path of the new
+    /// subdirectory.
+    fn clear_subdir(&self, name: &str) -> Result<std::path::PathBuf, IssueError> {
+        let mut subdir_name = self.dir.clone();
+        subdir_name.push(name);
+        match std::fs::remove_dir_all(&subdir_name) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e.into()),
+            Ok(_) => (),
+        }
+        std::fs::create_dir(&subdir_name)?;
+        Ok(subdir_name)
+    }
+
+    /// Write a file in the Issue directory, or remove it if `contents`
+    /// is None.
+    fn write_optional_file(&self, name: &str, contents: Option<&str>) -> Result<(), IssueError> {
+        let mut filename = self.dir.clone();
+        filename.push(name);
+        match contents {
+            Some(contents) => std::fs::write(&filename, contents)?,
+            None => match std::fs::remove_file(&filename) {
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => return Err(e.into()),
+                Ok(_) => (),
+            },
+        }
+        Ok(())
+    }
+
+    /// Write all the metadata of the Issue to its directory, without
+    /// committing.  A missing state leaves the state unchanged.
+    fn write_metadata(
+        &mut self,
+        metadata: &crate::front_matter::Metadata,
+    ) -> Result<(), IssueError> {
+        // Check everything before writing anything, so a bad document
+        // doesn't leave the issue half rewritten.
+        let state = match &metadata.state {
+            Some(state) => Some(State::from_name(state)?),
+            None => None,
+        };
+        if metadata.parent.as_ref() == Some(&self.id) {
+            return Err(IssueError::ParentSelf);
+        }
+        let done_time = match &metadata.done_time {
+            Some(done_time) => {
+                Some(chrono::DateTime::parse_from_rfc3339(done_time)?.with_timezone(&chrono::Local))
+            }
+            None => None,
+        };
+        if metadata.dependencies.contains(&self.id) {
+            return Err(IssueError::DepSelf);
+        }
+        let mut new_links = Vec::<Link>::new();
+        for (link_type, targets) in &metadata.links {
+            let link_type = LinkType::from_str(link_type)?;
+            for target in targets {
+                if target == &self.id {
+                    return Err(IssueError::LinkSelf);
+                }
+                new_links.push(Link {
+                    link_type,
+                    target: target.clone(),
+                });
+            }
+        }
+        for name in metadata.fields.keys() {
+            crate::field::validate_name(name)?;
+        }
+
+        if let Some(state) = state
+            && state != self.state
+        {
+            self.write_state(&state)?;
+            self.write_pre_blocked_state(None)?;
+            self.pre_blocked_state = None;
+            self.state = state;
+        }
+
+        self.write_optional_file("assignee", metadata.assignee.as_deref())?;
+        self.assignee = metadata.assignee.clone();
+
+        self.write_optional_file("parent", metadata.parent.as_deref())?;
+        self.parent = metadata.parent.clone();
+
+        self.write_optional_file("milestone", metadata.milestone.as_deref())?;
+        self.milestone = metadata.milestone.clone();
+
+        self.done_time = done_time;
+        self.write_optional_file(
+            "done_time",
+            self.done_time
+                .map(|done_time| done_time.to_rfc3339())
+                .as_deref(),
+        )?;
+
+        let mut tags = metadata.tags.clone();
+        tags.sort();
+        tags.dedup();
+        self.tags = tags;
+        self.write_tags()?;
+
+        let mut dependencies = metadata.dependencies.clone();
+        dependencies.sort();
+        dependencies.dedup();
+        let dependencies_dir_name = self.clear_subdir("dependencies")?;
+        for dep in &dependencies {
+            let mut dep_filename = dependencies_dir_name.clone();
+            dep_filename.push(dep);
+            std::fs::File::create(&dep_filename)?;
+        }
+        self.dependencies = if dependencies.is_empty() {
+            None
+        } else {
+            Some(dependencies)
+        };
+
+        // Only the link types we know are replaced, others are kept.
+        let mut links_dir_name = self.dir.clone();
+        links_dir_name.push("links");
+        std::fs::create_dir_all(&links_dir_name)?;
+        for type_direntry in (links_dir_name.read_dir()?).flatten() {
+            if LinkType::from_str(&type_direntry.file_name().to_string_lossy()).is_ok() {
+                std::fs::remove_dir_all(type_direntry.path())?;
+            }
+        }
+        for link in &new_links {
+            let mut link_filename = links_dir_name.clone();
+            link_filename.push(link.link_type.to_string());
+            std::fs::create_dir_all(&link_filename)?;
+            link_filename.push(&link.target);
+            std::fs::File::create(&link_filename)?;
+        }
+        new_links.sort();
+        new_links.dedup();
+        self.links = new_links;
+
+        let mut fields = std::collections::BTreeMap::<String, crate::field::FieldValue>::new();
+        let fields_dir_name = self.clear_subdir("fields")?;
+        for (name, value) in &metadata.fields {
+            crate::field::validate_name(name)?;
+            let mut field_filename = fields_dir_name.clone();
+            field_filename.push(name);
+            std::fs::write(&field_filename, value)?;
+            fields.insert(
+                name.clone(),
+                crate::field::FieldValue::String(value.clone()),
+            );
+        }
+        self.fields = fields;
+
+        Ok(())
+    }
+
+    /// Commit changes to this Issue and another Issue together.
+    fn commit_with(&self, other: &Issue, commit_message: &str) -> Result<(), IssueError> {
+        crate::git::add(&self.dir)?;
+        crate::git::add(&other.dir)?;
+        crate::git::commit(&self.dir, commit_message)?;
         Ok(())
     }
 
     fn commit(&self, commit_message: &str) -> Result<(), IssueError> {
         crate::git::add(&self.dir)?;
-        if !crate::git::worktree_is_dirty(&self.dir.to_string_lossy())? {
-            return Ok(());
-        }
         crate::git::commit(&self.dir, commit_message)?;
         Ok(())
     }
@@ -746,10 +1699,151 @@ mod tests {
         assert_eq!(Issue::tag_to_filename(tag), ",00,00,01,01");
     }
 
+    #[test]
+    fn link_type_round_trip() {
+        for link_type in [
+            LinkType::DuplicateOf,
+            LinkType::RelatesTo,
+            LinkType::Supersedes,
+            LinkType::CausedBy,
+        ] {
+            assert_eq!(
+                LinkType::from_str(&link_type.to_string()).unwrap(),
+                link_type
+            );
+        }
+        assert!(LinkType::from_str("blocks").is_err());
+    }
+
+    #[test]
+    fn read_unknown_link_types() {
+        let dir = tempfile::tempdir().unwrap();
+        for (link_type, target) in [("relates-to", "a"), ("blocks", "b")] {
+            let mut link_filename = std::path::PathBuf::from(dir.path());
+            link_filename.push(link_type);
+            std::fs::create_dir_all(&link_filename).unwrap();
+            link_filename.push(target);
+            std::fs::File::create(&link_filename).unwrap();
+        }
+        assert_eq!(
+            Issue::read_links(dir.path()).unwrap(),
+            vec![Link {
+                link_type: LinkType::RelatesTo,
+                target: String::from("a"),
+            }]
+        );
+    }
+
+    #[test]
+    fn invalid_metadata_changes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let dep_filename = dir.path().join("dependencies").join("dep");
+        std::fs::create_dir_all(dep_filename.parent().unwrap()).unwrap();
+        std::fs::File::create(&dep_filename).unwrap();
+        let link_filename = dir.path().join("links").join("relates-to").join("other");
+        std::fs::create_dir_all(link_filename.parent().unwrap()).unwrap();
+        std::fs::File::create(&link_filename).unwrap();
+
+        let mut issue = Issue {
+            id: String::from("me"),
+            author: String::from("A Person <foo@example.org>"),
+            creation_time: chrono::Local::now(),
+            done_time: None,
+            tags: Vec::new(),
+            state: State::New,
+            pre_blocked_state: None,
+            dependencies: Some(vec![String::from("dep")]),
+            parent: None,
+            links: vec![Link {
+                link_type: LinkType::RelatesTo,
+                target: String::from("other"),
+            }],
+            assignee: None,
+            fields: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            estimate: None,
+            worklog: Vec::new(),
+            milestone: None,
+            description: String::from("me"),
+            comments: Vec::new(),
+            dir: std::path::PathBuf::from(dir.path()),
+        };
+
+        let mut metadata = issue.metadata();
+        metadata
+            .links
+            .insert(String::from("relates-to"), vec![String::from("me")]);
+        assert!(matches!(
+            issue.write_metadata(&metadata),
+            Err(IssueError::LinkSelf)
+        ));
+
+        let mut metadata = issue.metadata();
+        metadata.dependencies.push(String::from("me"));
+        assert!(matches!(
+            issue.write_metadata(&metadata),
+            Err(IssueError::DepSelf)
+        ));
+
+        assert!(dep_filename.exists());
+        assert!(link_filename.exists());
+        assert_eq!(issue.links.len(), 1);
+    }
+
+    #[test]
+    fn comment_replies() {
+        let comment = |uuid: &str, in_reply_to: Option<&str>| crate::comment::Comment {
+            uuid: String::from(uuid),
+            author: String::from("A Person <foo@example.org>"),
+            creation_time: chrono::Local::now(),
+            description: String::from(uuid),
+            in_reply_to: in_reply_to.map(String::from),
+            reactions: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            dir: std::path::PathBuf::from(uuid),
+        };
+        let issue = Issue {
+            id: String::from("issue"),
+            author: String::from("A Person <foo@example.org>"),
+            creation_time: chrono::Local::now(),
+            done_time: None,
+            tags: Vec::<String>::new(),
+            state: State::New,
+            pre_blocked_state: None,
+            dependencies: None,
+            parent: None,
+            links: Vec::<Link>::new(),
+            assignee: None,
+            fields: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            estimate: None,
+            worklog: Vec::new(),
+            milestone: None,
+            description: String::from("issue"),
+            comments: vec![
+                comment("question", None),
+                comment("answer", Some("question")),
+                comment("orphan", Some("deleted")),
+                comment("thanks", Some("answer")),
+            ],
+            dir: std::path::PathBuf::from("issue"),
+        };
+
+        let uuids = |comments: Vec<&crate::comment::Comment>| -> Vec<String> {
+            comments.iter().map(|c| c.uuid.clone()).collect()
+        };
+        assert_eq!(uuids(issue.replies(None)), vec!["question", "orphan"]);
+        assert_eq!(uuids(issue.replies(Some("question"))), vec!["answer"]);
+        assert_eq!(uuids(issue.replies(Some("answer"))), vec!["thanks"]);
+        assert!(issue.replies(Some("thanks")).is_empty());
+    }
+
     #[test]
     fn read_issue_0() {
         // This has to be an IssuesMut because we want a long-lived worktree to read Issue from.
-        let issues_mut = crate::IssuesMut::new_from_git("entomologist-data-test-0000").unwrap();
+        let issues_mut =
+            crate::IssuesMut::new_from_git(&crate::Repo::current(), "entomologist-data-test-0000")
+                .unwrap();
         let mut issue_dir = std::path::PathBuf::from(issues_mut.path());
         let uuid = "3943fc5c173fdf41c0a22251593cd476";
         issue_dir.push(&uuid);
@@ -772,8 +1866,16 @@ mod tests {
                 String::from("tag1"),
             ]),
             state: State::New,
+            pre_blocked_state: None,
             dependencies: None,
+            parent: None,
+            links: Vec::<Link>::new(),
             assignee: None,
+            fields: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            estimate: None,
+            worklog: Vec::new(),
+            milestone: None,
             description: String::from(
                 "this is the title of my issue\n\nThis is the description of my issue.\nIt is multiple lines.\n* Arbitrary contents\n* But let's use markdown by convention\n",
             ),
@@ -786,7 +1888,9 @@ mod tests {
     #[test]
     fn read_issue_1() {
         // This has to be an IssuesMut because we want a long-lived worktree to read Issue from.
-        let issues_mut = crate::IssuesMut::new_from_git("entomologist-data-test-0000").unwrap();
+        let issues_mut =
+            crate::IssuesMut::new_from_git(&crate::Repo::current(), "entomologist-data-test-0000")
+                .unwrap();
         let mut issue_dir = std::path::PathBuf::from(issues_mut.path());
         let uuid = "7792b063eef6d33e7da5dc1856750c14";
         issue_dir.push(&uuid);
@@ -801,8 +1905,16 @@ mod tests {
             done_time: None,
             tags: Vec::<String>::new(),
             state: State::InProgress,
+            pre_blocked_state: None,
             dependencies: None,
+            parent: None,
+            links: Vec::<Link>::new(),
             assignee: Some(String::from("beep boop")),
+            fields: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            estimate: None,
+            worklog: Vec::new(),
+            milestone: None,
             description: String::from("minimal"),
             comments: Vec::<crate::comment::Comment>::new(),
             dir: std::path::PathBuf::from(issue_dir),
@@ -812,7 +1924,7 @@ mod tests {
 
     #[test]
     fn invalid_issue_0() {
-        let r = crate::Issues::new_from_git("entomologist-data-test-0003");
+        let r = crate::Issues::new_from_git(&crate::Repo::current(), "entomologist-data-test-0003");
         let Err(crate::issues::Error::IssueError(IssueError::ChronoParseError(
             _chrono_parse_error,
         ))) = r
@@ -838,7 +1950,7 @@ mod tests {
 
     #[test]
     fn invalid_issue_1() {
-        let r = crate::Issues::new_from_git("entomologist-data-test-0004");
+        let r = crate::Issues::new_from_git(&crate::Repo::current(), "entomologist-data-test-0004");
         let Err(crate::issues::Error::IssueError(IssueError::ChronoParseError(chrono_parse_error))) =
             r
         else {
@@ -852,7 +1964,7 @@ mod tests {
 
     #[test]
     fn invalid_issue_2() {
-        let r = crate::Issues::new_from_git("entomologist-data-test-0005");
+        let r = crate::Issues::new_from_git(&crate::Repo::current(), "entomologist-data-test-0005");
         let Err(crate::issues::Error::IssueError(IssueError::StdIoError(os_error))) = r else {
             panic!("unexpected result: {r:#?}");
         };
@@ -1,9 +1,66 @@
 #[cfg(feature = "log")]
 use log::debug;
 
-// Just a placeholder for now, get rid of this if we don't need it.
+use crate::issue::{IssueHandle, State};
+
+/// The newest format of the issues database this version of ent
+/// understands.  Format 1 is just issue directories and `config.toml`,
+/// format 2 adds the `templates`, `milestones` and `blobs` directories,
+/// which ent versions without format checking would take for issues.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Configuration of an issues database, read from the `config.toml`
+/// file at the top of the GitDb branch.
 #[derive(Debug, Default, PartialEq, serde::Deserialize)]
-pub struct Config {}
+pub struct Config {
+    /// The format of the issues database, see `FORMAT_VERSION`.
+    /// Databases from before formats were recorded have none.
+    #[serde(default)]
+    pub format: Option<u32>,
+
+    /// If true, open issues with unresolved dependencies are
+    /// automatically treated as Blocked, and return to their previous
+    /// state when all their dependencies are closed.
+    #[serde(default)]
+    pub auto_blocked: bool,
+
+    /// The states issues can be in, and the transitions between them.
+    #[serde(default)]
+    pub workflow: crate::workflow::Workflow,
+
+    /// The custom fields issues can have, by name.
+    #[serde(default)]
+    pub fields: std::collections::BTreeMap<String, crate::field::FieldType>,
+
+    /// If true, new attachments are stored in the content-addressed
+    /// blob store instead of in the issue (see `crate::attachment`).
+    #[serde(default)]
+    pub blob_attachments: bool,
+
+    /// How `ent changelog` sorts issues into sections by their tags.
+    #[serde(default)]
+    pub changelog: crate::changelog::ChangelogConfig,
+}
+
+impl Config {
+    /// The format of the issues database, 1 if none is recorded.
+    pub fn format(&self) -> u32 {
+        self.format.unwrap_or(1)
+    }
+
+    /// Give the custom fields of an Issue the types declared in the
+    /// config.  Values that don't match the declared type (for example
+    /// because the declaration changed) are kept as strings.
+    pub(crate) fn type_fields(&self, issue: &mut crate::Issue) {
+        for (name, value) in issue.fields.iter_mut() {
+            if let Some(field_type) = self.fields.get(name)
+                && let Ok(typed_value) = field_type.parse(name, &value.to_string())
+            {
+                *value = typed_value;
+            }
+        }
+    }
+}
 
 /// `Issues` is a deserialization of the GitDb, using a short-lived,
 /// ephemeral worktree. The worktree is made from the detached head of the
@@ -12,6 +69,8 @@ pub struct Config {}
 pub struct Issues {
     issues: std::collections::HashMap<String, crate::Issue>,
     config: Config,
+    templates: std::collections::BTreeMap<String, crate::template::Template>,
+    milestones: std::collections::BTreeMap<String, crate::milestone::Milestone>,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -30,6 +89,17 @@ pub enum Error {
 
     #[error(transparent)]
     GitDB(#[from] crate::gitdb::Error),
+
+    #[error("invalid config.toml: {0}")]
+    InvalidConfig(String),
+
+    #[error(
+        "the issues database has format {found}, this version of ent only understands format {supported} and older, please upgrade ent"
+    )]
+    UnsupportedFormat { found: u32, supported: u32 },
+
+    #[error(transparent)]
+    MilestoneError(#[from] crate::milestone::MilestoneError),
 }
 
 /// Public API of Issues.
@@ -39,12 +109,12 @@ impl Issues {
     }
 
     /// Read Issues from a git ref (typically the `entomologist-data`
-    /// branch). The resulting Issues struct provides a static, read-only
-    /// view of the issues recorded in the git ref.
+    /// branch) of a repo. The resulting Issues struct provides a static,
+    /// read-only view of the issues recorded in the git ref.
     ///
     /// For a mutable view use IssuesMut instead.
-    pub fn new_from_git(git_ref: &str) -> Result<Self, Error> {
-        let gitdb = crate::gitdb::GitDb::get(git_ref)?;
+    pub fn new_from_git(repo: &crate::Repo, git_ref: &str) -> Result<Self, Error> {
+        let gitdb = crate::gitdb::GitDb::get(repo, git_ref)?;
         let issues = Self::new_from_dir(&gitdb.path())?;
         // Drop the GitDb, this destroys the underlying worktree.
         Ok(issues)
@@ -59,6 +129,251 @@ impl Issues {
     pub fn iter(&self) -> std::collections::hash_map::Iter<'_, String, crate::Issue> {
         self.issues.iter()
     }
+
+    /// Get the configuration of the issues database.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Look up an issue Template by name.
+    pub fn get_template(&self, name: &str) -> Option<&crate::template::Template> {
+        self.templates.get(name)
+    }
+
+    /// Iterate over the issue Templates, sorted by name.
+    pub fn templates(
+        &self,
+    ) -> std::collections::btree_map::Iter<'_, String, crate::template::Template> {
+        self.templates.iter()
+    }
+
+    /// Look up a Milestone by name.
+    pub fn get_milestone(&self, name: &str) -> Option<&crate::milestone::Milestone> {
+        self.milestones.get(name)
+    }
+
+    /// Iterate over the Milestones, sorted by name.
+    pub fn milestones(
+        &self,
+    ) -> std::collections::btree_map::Iter<'_, String, crate::milestone::Milestone> {
+        self.milestones.iter()
+    }
+
+    /// Return the Issues in a Milestone, sorted by creation time.
+    pub fn milestone_issues(&self, name: &str) -> Vec<&crate::Issue> {
+        let mut issues: Vec<&crate::Issue> = self
+            .issues
+            .values()
+            .filter(|issue| issue.milestone.as_deref() == Some(name))
+            .collect();
+        issues.sort_by_key(|issue| issue.creation_time);
+        issues
+    }
+
+    /// Return the progress of a Milestone as `(closed, total)`, where
+    /// `total` is the number of Issues in the Milestone and `closed` is
+    /// the number of those Issues that are closed.
+    pub fn milestone_progress(&self, name: &str) -> (usize, usize) {
+        let issues = self.milestone_issues(name);
+        let closed = issues
+            .iter()
+            .filter(|issue| self.config.workflow.is_closed(&issue.state))
+            .count();
+        (closed, issues.len())
+    }
+
+    /// Return the open Issues in a Milestone that are blocked, either
+    /// because they're in the Blocked state or because they have
+    /// unresolved dependencies.  Sorted by creation time.
+    pub fn milestone_blockers(&self, name: &str) -> Vec<&crate::Issue> {
+        self.milestone_issues(name)
+            .into_iter()
+            .filter(|issue| !self.config.workflow.is_closed(&issue.state))
+            .filter(|issue| {
+                self.effective_state(issue) == State::Blocked
+                    || !self.blocking_dependencies(&issue.id).is_empty()
+            })
+            .collect()
+    }
+
+    /// Return the state an Issue should be shown as.  This is the
+    /// Issue's state, except when the `auto_blocked` policy is enabled
+    /// and the Issue is open with unresolved dependencies, in which case
+    /// it is Blocked (or the other way around, the Issue was blocked
+    /// automatically and all its dependencies have since been resolved).
+    pub fn effective_state(&self, issue: &crate::Issue) -> State {
+        match self.auto_blocked_state(issue) {
+            Some(state) => state,
+            None => issue.state.clone(),
+        }
+    }
+
+    /// Returns true if an Issue is open and blocked, that is it has
+    /// unresolved dependencies or its effective state is Blocked.
+    pub fn is_blocked(&self, issue: &crate::Issue) -> bool {
+        !self.config.workflow.is_closed(&issue.state)
+            && (self.effective_state(issue) == State::Blocked
+                || !self.blocking_dependencies(&issue.id).is_empty())
+    }
+
+    /// Return the state the `auto_blocked` policy moves an Issue to, if
+    /// any: Blocked if it's open with unresolved dependencies, or back
+    /// to the state it was in before if it was blocked automatically and
+    /// its dependencies are all resolved.  Transitions the workflow
+    /// doesn't allow are not made.
+    pub(crate) fn auto_blocked_state(&self, issue: &crate::Issue) -> Option<State> {
+        let workflow = &self.config.workflow;
+        if !self.config.auto_blocked || workflow.is_closed(&issue.state) {
+            return None;
+        }
+        let new_state = if !self.blocking_dependencies(&issue.id).is_empty() {
+            State::Blocked
+        } else {
+            issue.pre_blocked_state.clone()?
+        };
+        if new_state == issue.state
+            || !workflow.has_state(&new_state)
+            || !workflow.allows_transition(&issue.state, &new_state)
+        {
+            return None;
+        }
+        Some(new_state)
+    }
+
+    /// Return the children of an Issue (the Issues whose parent is
+    /// the specified Issue), sorted by creation time.
+    pub fn children(&self, issue_id: &str) -> Vec<&crate::Issue> {
+        let mut children: Vec<&crate::Issue> = self
+            .issues
+            .values()
+            .filter(|issue| issue.parent.as_deref() == Some(issue_id))
+            .collect();
+        children.sort_by_key(|issue| issue.creation_time);
+        children
+    }
+
+    /// Return the progress of an Issue as `(closed, total)`, where
+    /// `total` is the number of children of the Issue and `closed`
+    /// is the number of those children that are closed.
+    /// Returns None if the Issue has no children.
+    pub fn progress(&self, issue_id: &str) -> Option<(usize, usize)> {
+        let children = self.children(issue_id);
+        if children.is_empty() {
+            return None;
+        }
+        let closed = children
+            .iter()
+            .filter(|child| self.config.workflow.is_closed(&child.state))
+            .count();
+        Some((closed, children.len()))
+    }
+
+    /// Return the Issues that depend on the specified Issue (the Issues
+    /// it blocks), sorted by creation time.
+    pub fn dependents(&self, issue_id: &str) -> Vec<&crate::Issue> {
+        let mut dependents: Vec<&crate::Issue> = self
+            .issues
+            .values()
+            .filter(|issue| match &issue.dependencies {
+                Some(dependencies) => dependencies.iter().any(|d| d == issue_id),
+                None => false,
+            })
+            .collect();
+        dependents.sort_by_key(|issue| issue.creation_time);
+        dependents
+    }
+
+    /// Return the dependencies of an Issue that are not yet resolved
+    /// (not in a closed state).  Dependencies on issues that don't exist
+    /// are ignored.
+    pub fn blocking_dependencies(&self, issue_id: &str) -> Vec<&IssueHandle> {
+        let Some(issue) = self.get_issue(issue_id) else {
+            return Vec::new();
+        };
+        let Some(dependencies) = &issue.dependencies else {
+            return Vec::new();
+        };
+        dependencies
+            .iter()
+            .filter(|dep_id| match self.get_issue(dep_id) {
+                Some(dep) => !self.config.workflow.is_closed(&dep.state),
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Return the open Issues that depend on the specified Issue and
+    /// have no other unresolved dependencies.  Once the specified Issue
+    /// is resolved, these Issues are no longer blocked.
+    pub fn unblocked_by(&self, issue_id: &str) -> Vec<&crate::Issue> {
+        self.dependents(issue_id)
+            .into_iter()
+            .filter(|dependent| !self.config.workflow.is_closed(&dependent.state))
+            .filter(|dependent| {
+                self.blocking_dependencies(&dependent.id)
+                    .iter()
+                    .all(|dep_id| *dep_id == issue_id)
+            })
+            .collect()
+    }
+
+    /// Return the links from other Issues to the specified Issue, as
+    /// `(link type, id of the linking issue)` pairs.
+    pub fn links_to(&self, issue_id: &str) -> Vec<(crate::issue::LinkType, &IssueHandle)> {
+        let mut links: Vec<(crate::issue::LinkType, &IssueHandle)> = self
+            .issues
+            .values()
+            .flat_map(|issue| {
+                issue
+                    .links
+                    .iter()
+                    .filter(|link| link.target == issue_id)
+                    .map(|link| (link.link_type, &issue.id))
+            })
+            .collect();
+        links.sort();
+        links
+    }
+
+    /// Returns true if making `parent_id` the parent of `issue_id`
+    /// would create a cycle in the issue hierarchy.
+    pub fn parent_creates_cycle(&self, issue_id: &str, parent_id: &str) -> bool {
+        let mut visited = std::collections::HashSet::<&str>::new();
+        let mut ancestor = Some(parent_id);
+        while let Some(id) = ancestor {
+            if id == issue_id {
+                return true;
+            }
+            if !visited.insert(id) {
+                // There's already a cycle above us, but it doesn't
+                // include `issue_id`.
+                return false;
+            }
+            ancestor = self.get_issue(id).and_then(|issue| issue.parent.as_deref());
+        }
+        false
+    }
+
+    /// Returns true if making `issue_id` depend on `dep_id` would
+    /// create a cycle in the dependency graph.
+    pub fn dependency_creates_cycle(&self, issue_id: &str, dep_id: &str) -> bool {
+        let mut visited = std::collections::HashSet::<&str>::new();
+        let mut to_visit = vec![dep_id];
+        while let Some(id) = to_visit.pop() {
+            if id == issue_id {
+                return true;
+            }
+            if !visited.insert(id) {
+                continue;
+            }
+            if let Some(issue) = self.get_issue(id)
+                && let Some(dependencies) = &issue.dependencies
+            {
+                to_visit.extend(dependencies.iter().map(|d| d.as_str()));
+            }
+        }
+        false
+    }
 }
 
 /// pub(crate) API of Issues.
@@ -68,14 +383,33 @@ impl Issues {
     pub(crate) fn new_from_dir(dir: &std::path::Path) -> Result<Issues, Error> {
         // Read Issues from DB.
         let mut issues = std::collections::HashMap::<String, crate::Issue>::new();
-        let mut config = Config::default();
+        let mut templates = std::collections::BTreeMap::<String, crate::template::Template>::new();
+        let mut milestones =
+            std::collections::BTreeMap::<String, crate::milestone::Milestone>::new();
+
+        // Read the config first, the issues are parsed with its workflow.
+        let config_path = dir.join("config.toml");
+        let config = if config_path.is_file() {
+            Issues::parse_config(&config_path)?
+        } else {
+            Config::default()
+        };
 
         for direntry in dir.read_dir()?.flatten() {
-            if direntry.metadata()?.is_dir() {
-                let issue = crate::Issue::new_from_dir(direntry.path().as_path())?;
+            if direntry.file_name() == "templates" && direntry.metadata()?.is_dir() {
+                templates = Issues::read_templates(&direntry.path())?;
+            } else if direntry.file_name() == "milestones" && direntry.metadata()?.is_dir() {
+                milestones = Issues::read_milestones(&direntry.path())?;
+            } else if direntry.file_name() == "blobs" {
+                // Attachment contents, read on demand.
+            } else if direntry.metadata()?.is_dir() {
+                let issue = crate::Issue::new_from_dir_with_workflow(
+                    direntry.path().as_path(),
+                    &config.workflow,
+                )?;
                 issues.insert(issue.id.clone(), issue);
             } else if direntry.file_name() == "config.toml" {
-                config = Issues::parse_config(direntry.path().as_path())?;
+                // Already read.
             } else {
                 #[cfg(feature = "log")]
                 debug!(
@@ -85,22 +419,61 @@ impl Issues {
             }
         }
 
-        Ok(Self { issues, config })
+        // Now that we have the config, give the custom fields of the
+        // issues their declared types.
+        for issue in issues.values_mut() {
+            config.type_fields(issue);
+        }
+
+        Ok(Self {
+            issues,
+            config,
+            templates,
+            milestones,
+        })
+    }
+
+    /// Record the format of the issues database in the in-memory config.
+    pub(crate) fn set_format(&mut self, format: Option<u32>) {
+        self.config.format = format;
     }
 
     /// Insert an Issue.
-    ///
-    /// Note: this is currently only used for testing.
-    #[allow(dead_code)]
     pub(crate) fn add_issue(&mut self, issue: crate::Issue) {
         self.issues.insert(issue.id.clone(), issue);
     }
 
+    /// Remove an Issue, returning it.
+    pub(crate) fn remove_issue(&mut self, issue_id: &str) -> Option<crate::Issue> {
+        self.issues.remove(issue_id)
+    }
+
     /// Get a mutable ref to an Issue.
     pub(crate) fn get_issue_mut(&mut self, issue_id: &str) -> Option<&mut crate::Issue> {
         self.issues.get_mut(issue_id)
     }
 
+    /// Give the custom fields of an Issue the types declared in the
+    /// config.
+    pub(crate) fn type_fields(&mut self, issue_id: &str) {
+        if let Some(issue) = self.issues.get_mut(issue_id) {
+            self.config.type_fields(issue);
+        }
+    }
+
+    /// Insert a Milestone.
+    pub(crate) fn add_milestone(&mut self, milestone: crate::milestone::Milestone) {
+        self.milestones.insert(milestone.name.clone(), milestone);
+    }
+
+    /// Get a mutable ref to a Milestone.
+    pub(crate) fn get_milestone_mut(
+        &mut self,
+        name: &str,
+    ) -> Option<&mut crate::milestone::Milestone> {
+        self.milestones.get_mut(name)
+    }
+
     /// Get a mutable iterator over all Issue objects.
     pub(crate) fn iter_mut(
         &mut self,
@@ -111,13 +484,103 @@ impl Issues {
 
 /// Private/internal API of Issues.
 impl Issues {
+    fn read_templates(
+        dir: &std::path::Path,
+    ) -> Result<std::collections::BTreeMap<String, crate::template::Template>, Error> {
+        let mut templates = std::collections::BTreeMap::<String, crate::template::Template>::new();
+        for direntry in dir.read_dir()?.flatten() {
+            let path = direntry.path();
+            if path.extension().is_none_or(|extension| extension != "toml") {
+                #[cfg(feature = "log")]
+                debug!("ignoring unknown file in templates directory: {:?}", path);
+                continue;
+            }
+            let Some(name) = path.file_stem() else {
+                return Err(Error::FilenameError(direntry.file_name()));
+            };
+            let name = name.to_string_lossy().into_owned();
+            let template: crate::template::Template =
+                toml::from_str(&std::fs::read_to_string(&path)?)?;
+            templates.insert(name, template);
+        }
+        Ok(templates)
+    }
+
+    fn read_milestones(
+        dir: &std::path::Path,
+    ) -> Result<std::collections::BTreeMap<String, crate::milestone::Milestone>, Error> {
+        let mut milestones =
+            std::collections::BTreeMap::<String, crate::milestone::Milestone>::new();
+        for direntry in dir.read_dir()?.flatten() {
+            if !direntry.metadata()?.is_dir() {
+                #[cfg(feature = "log")]
+                debug!(
+                    "ignoring unknown file in milestones directory: {:?}",
+                    direntry.file_name()
+                );
+                continue;
+            }
+            let milestone = crate::milestone::Milestone::new_from_dir(&direntry.path())?;
+            milestones.insert(milestone.name.clone(), milestone);
+        }
+        Ok(milestones)
+    }
+
     fn parse_config(config_path: &std::path::Path) -> Result<Config, Error> {
         let config_contents = std::fs::read_to_string(config_path)?;
         let config: Config = toml::from_str(&config_contents)?;
+        if config.format() > FORMAT_VERSION {
+            return Err(Error::UnsupportedFormat {
+                found: config.format(),
+                supported: FORMAT_VERSION,
+            });
+        }
+        if config.auto_blocked
+            && (!config.workflow.has_state(&State::Blocked)
+                || config.workflow.is_closed(&State::Blocked))
+        {
+            return Err(Error::InvalidConfig(String::from(
+                "auto_blocked needs the Blocked state in the workflow, and open",
+            )));
+        }
+        for name in config.fields.keys() {
+            if crate::field::validate_name(name).is_err() {
+                return Err(Error::InvalidConfig(format!("invalid field name {name:?}")));
+            }
+        }
         Ok(config)
     }
 }
 
+/// Set the `format` key of the config file contents `config`, keeping
+/// everything else as it is.
+pub(crate) fn set_config_format(config: &str, format: u32) -> String {
+    let format_line = format!("format = {format}");
+    let mut lines = Vec::new();
+    let mut found = false;
+    let mut top_level = true;
+    for line in config.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            top_level = false;
+        }
+        let key = trimmed.split('=').next().unwrap_or("").trim();
+        if top_level && !found && key == "format" {
+            lines.push(format_line.clone());
+            found = true;
+        } else {
+            lines.push(String::from(line));
+        }
+    }
+    if !found {
+        // Top-level keys go before the first table.
+        lines.insert(0, format_line);
+    }
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+    contents
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,7 +588,8 @@ mod tests {
 
     #[test]
     fn read_issues_0000() {
-        let issues = Issues::new_from_git("entomologist-data-test-0000").unwrap();
+        let issues =
+            Issues::new_from_git(&crate::Repo::current(), "entomologist-data-test-0000").unwrap();
 
         let mut expected = Issues::new();
 
@@ -140,8 +604,16 @@ mod tests {
             done_time: None,
             tags: Vec::<String>::new(),
             state: crate::issue::State::InProgress,
+            pre_blocked_state: None,
             dependencies: None,
+            parent: None,
+            links: Vec::<crate::issue::Link>::new(),
             assignee: Some(String::from("beep boop")),
+            fields: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            estimate: None,
+            worklog: Vec::new(),
+            milestone: None,
             description: String::from("minimal"),
             comments: Vec::<crate::comment::Comment>::new(),
             dir,
@@ -168,8 +640,16 @@ mod tests {
                     String::from("tag1"),
                 ]),
                 state: crate::issue::State::New,
+                pre_blocked_state: None,
                 dependencies: None,
+                parent: None,
+                links: Vec::<crate::issue::Link>::new(),
                 assignee: None,
+                fields: std::collections::BTreeMap::new(),
+                attachments: Vec::new(),
+                estimate: None,
+                worklog: Vec::new(),
+                milestone: None,
                 description: String::from("this is the title of my issue\n\nThis is the description of my issue.\nIt is multiple lines.\n* Arbitrary contents\n* But let's use markdown by convention\n"),
                 comments: Vec::<crate::comment::Comment>::new(),
                 dir,
@@ -180,7 +660,8 @@ mod tests {
 
     #[test]
     fn read_issues_0001() {
-        let issues = Issues::new_from_git("entomologist-data-test-0001").unwrap();
+        let issues =
+            Issues::new_from_git(&crate::Repo::current(), "entomologist-data-test-0001").unwrap();
 
         let mut expected = Issues::new();
 
@@ -199,8 +680,16 @@ mod tests {
             ),
             tags: Vec::<String>::new(),
             state: crate::issue::State::Done,
+            pre_blocked_state: None,
             dependencies: None,
+            parent: None,
+            links: Vec::<crate::issue::Link>::new(),
             assignee: None,
+            fields: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            estimate: None,
+            worklog: Vec::new(),
+            milestone: None,
             description: String::from("oh yeah we got titles"),
             comments: Vec::<crate::comment::Comment>::new(),
             dir,
@@ -219,6 +708,9 @@ mod tests {
                 author: String::from("Sebastian Kuzminsky <seb@highlab.com>"),
                 creation_time: chrono::DateTime::parse_from_rfc3339("2025-07-24T10:08:38-06:00").unwrap().with_timezone(&chrono::Local),
                 description: String::from("This is a comment on issue dd79c8cfb8beeacd0460429944b4ecbe\n\nIt has multiple lines\n"),
+                in_reply_to: None,
+                reactions: std::collections::BTreeMap::new(),
+                attachments: Vec::new(),
                 dir: std::path::PathBuf::from(comment_dir),
             }
         );
@@ -232,8 +724,16 @@ mod tests {
                 done_time: None,
                 tags: Vec::<String>::new(),
                 state: crate::issue::State::WontDo,
+                pre_blocked_state: None,
                 dependencies: None,
+                parent: None,
+                links: Vec::<crate::issue::Link>::new(),
                 assignee: None,
+                fields: std::collections::BTreeMap::new(),
+                attachments: Vec::new(),
+                estimate: None,
+                worklog: Vec::new(),
+                milestone: None,
                 description: String::from("issues out the wazoo\n\nLots of words\nthat don't say much\nbecause this is just\na test\n"),
                 comments: expected_comments,
                 dir,
@@ -244,7 +744,8 @@ mod tests {
 
     #[test]
     fn read_issues_0002() {
-        let issues = Issues::new_from_git("entomologist-data-test-0002").unwrap();
+        let issues =
+            Issues::new_from_git(&crate::Repo::current(), "entomologist-data-test-0002").unwrap();
 
         let mut expected = Issues::new();
 
@@ -259,8 +760,16 @@ mod tests {
             done_time: None,
             tags: Vec::<String>::new(),
             state: crate::issue::State::Done,
+            pre_blocked_state: None,
             dependencies: None,
+            parent: None,
+            links: Vec::<crate::issue::Link>::new(),
             assignee: None,
+            fields: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            estimate: None,
+            worklog: Vec::new(),
+            milestone: None,
             description: String::from("oh yeah we got titles\n"),
             comments: Vec::<crate::comment::Comment>::new(),
             dir,
@@ -278,8 +787,16 @@ mod tests {
                 done_time: None,
                 tags: Vec::<String>::new(),
                 state: crate::issue::State::WontDo,
+                pre_blocked_state: None,
                 dependencies: None,
+                parent: None,
+                links: Vec::<crate::issue::Link>::new(),
                 assignee: None,
+                fields: std::collections::BTreeMap::new(),
+                attachments: Vec::new(),
+                estimate: None,
+                worklog: Vec::new(),
+                milestone: None,
                 description: String::from("issues out the wazoo\n\nLots of words\nthat don't say much\nbecause this is just\na test\n"),
                 comments: Vec::<crate::comment::Comment>::new(),
                 dir,
@@ -298,11 +815,19 @@ mod tests {
                 done_time: None,
                 tags: Vec::<String>::new(),
                 state: crate::issue::State::WontDo,
+                pre_blocked_state: None,
                 dependencies: Some(vec![
                     crate::issue::IssueHandle::from("3fa5bfd93317ad25772680071d5ac325"),
                     crate::issue::IssueHandle::from("dd79c8cfb8beeacd0460429944b4ecbe"),
                 ]),
+                parent: None,
+                links: Vec::<crate::issue::Link>::new(),
                 assignee: None,
+                fields: std::collections::BTreeMap::new(),
+                attachments: Vec::new(),
+                estimate: None,
+                worklog: Vec::new(),
+                milestone: None,
                 description: String::from("issue with dependencies\n\na test has begun\nfor dependencies we seek\nintertwining life"),
                 comments: Vec::<crate::comment::Comment>::new(),
                 dir,
@@ -310,4 +835,249 @@ mod tests {
         );
         assert_eq!(issues, expected);
     }
+
+    fn make_issue(id: &str, state: State, parent: Option<&str>, deps: &[&str]) -> crate::Issue {
+        crate::Issue {
+            id: String::from(id),
+            author: String::from("A Person <foo@example.org>"),
+            creation_time: chrono::Local::now(),
+            done_time: None,
+            tags: Vec::<String>::new(),
+            state,
+            pre_blocked_state: None,
+            dependencies: match deps.len() {
+                0 => None,
+                _ => Some(deps.iter().map(|d| String::from(*d)).collect()),
+            },
+            parent: parent.map(String::from),
+            links: Vec::<crate::issue::Link>::new(),
+            assignee: None,
+            fields: std::collections::BTreeMap::new(),
+            attachments: Vec::new(),
+            estimate: None,
+            worklog: Vec::new(),
+            milestone: None,
+            description: String::from(id),
+            comments: Vec::<crate::comment::Comment>::new(),
+            dir: std::path::PathBuf::from(id),
+        }
+    }
+
+    #[test]
+    fn issue_hierarchy() {
+        let mut issues = Issues::new();
+        issues.add_issue(make_issue("epic", State::InProgress, None, &[]));
+        issues.add_issue(make_issue("task1", State::Done, Some("epic"), &[]));
+        issues.add_issue(make_issue("task2", State::New, Some("epic"), &[]));
+        issues.add_issue(make_issue("task3", State::WontDo, Some("epic"), &[]));
+        issues.add_issue(make_issue("subtask", State::New, Some("task2"), &[]));
+
+        assert_eq!(issues.children("epic").len(), 3);
+        assert_eq!(issues.progress("epic"), Some((2, 3)));
+        assert_eq!(issues.progress("task2"), Some((0, 1)));
+        assert_eq!(issues.progress("task1"), None);
+
+        assert!(issues.parent_creates_cycle("epic", "subtask"));
+        assert!(issues.parent_creates_cycle("epic", "epic"));
+        assert!(!issues.parent_creates_cycle("subtask", "task1"));
+    }
+
+    #[test]
+    fn reverse_links() {
+        use crate::issue::{Link, LinkType};
+        let mut issues = Issues::new();
+        let mut dup = make_issue("dup", State::WontDo, None, &[]);
+        dup.links.push(Link {
+            link_type: LinkType::DuplicateOf,
+            target: String::from("orig"),
+        });
+        let mut other = make_issue("other", State::New, None, &[]);
+        other.links.push(Link {
+            link_type: LinkType::RelatesTo,
+            target: String::from("orig"),
+        });
+        issues.add_issue(dup);
+        issues.add_issue(other);
+        issues.add_issue(make_issue("orig", State::New, None, &[]));
+
+        let dup_id = String::from("dup");
+        let other_id = String::from("other");
+        assert_eq!(
+            issues.links_to("orig"),
+            vec![
+                (LinkType::DuplicateOf, &dup_id),
+                (LinkType::RelatesTo, &other_id)
+            ]
+        );
+        assert!(issues.links_to("dup").is_empty());
+    }
+
+    #[test]
+    fn reverse_dependencies() {
+        let mut issues = Issues::new();
+        issues.add_issue(make_issue("a", State::Done, None, &[]));
+        issues.add_issue(make_issue("b", State::InProgress, None, &[]));
+        issues.add_issue(make_issue("c", State::New, None, &["a"]));
+        issues.add_issue(make_issue("d", State::New, None, &["a", "b"]));
+        issues.add_issue(make_issue("e", State::Done, None, &["a"]));
+
+        let dependents: Vec<&str> = issues
+            .dependents("a")
+            .iter()
+            .map(|i| i.id.as_str())
+            .collect();
+        assert_eq!(dependents.len(), 3);
+        assert!(dependents.contains(&"c"));
+        assert!(dependents.contains(&"d"));
+        assert!(dependents.contains(&"e"));
+
+        assert_eq!(issues.blocking_dependencies("d"), vec![&String::from("b")]);
+
+        // "d" is still blocked by "b", and "e" is already Done.
+        let unblocked: Vec<&str> = issues
+            .unblocked_by("a")
+            .iter()
+            .map(|i| i.id.as_str())
+            .collect();
+        assert_eq!(unblocked, vec!["c"]);
+        let unblocked: Vec<&str> = issues
+            .unblocked_by("b")
+            .iter()
+            .map(|i| i.id.as_str())
+            .collect();
+        assert_eq!(unblocked, vec!["d"]);
+
+        // "c" only depends on "a", which is Done.
+        assert!(!issues.is_blocked(issues.get_issue("c").unwrap()));
+        assert!(issues.is_blocked(issues.get_issue("d").unwrap()));
+        // Closed issues are never blocked.
+        assert!(!issues.is_blocked(issues.get_issue("e").unwrap()));
+    }
+
+    #[test]
+    fn milestone_progress() {
+        let mut issues = Issues::new();
+        let states = [
+            ("done", State::Done, &[][..]),
+            ("waiting", State::Backlog, &["outside"][..]),
+            ("stuck", State::Blocked, &[][..]),
+            ("ready", State::New, &["done"][..]),
+        ];
+        for (id, state, deps) in states {
+            let mut issue = make_issue(id, state, None, deps);
+            issue.milestone = Some(String::from("v1.2"));
+            issues.add_issue(issue);
+        }
+        issues.add_issue(make_issue("outside", State::InProgress, None, &[]));
+
+        assert_eq!(issues.milestone_issues("v1.2").len(), 4);
+        assert_eq!(issues.milestone_progress("v1.2"), (1, 4));
+        assert_eq!(issues.milestone_progress("v2.0"), (0, 0));
+        let mut blockers: Vec<&str> = issues
+            .milestone_blockers("v1.2")
+            .iter()
+            .map(|issue| issue.id.as_str())
+            .collect();
+        blockers.sort();
+        assert_eq!(blockers, vec!["stuck", "waiting"]);
+    }
+
+    #[test]
+    fn auto_blocked_effective_state() {
+        let mut issues = Issues::new();
+        issues.add_issue(make_issue("dep", State::InProgress, None, &[]));
+        issues.add_issue(make_issue("waiting", State::Backlog, None, &["dep"]));
+        let mut unblocked = make_issue("unblocked", State::Blocked, None, &[]);
+        unblocked.pre_blocked_state = Some(State::InProgress);
+        issues.add_issue(unblocked);
+
+        // Policy disabled: the recorded states are shown.
+        let waiting = issues.get_issue("waiting").unwrap();
+        assert_eq!(issues.effective_state(waiting), State::Backlog);
+        let unblocked = issues.get_issue("unblocked").unwrap();
+        assert_eq!(issues.effective_state(unblocked), State::Blocked);
+
+        issues.config = toml::from_str("auto_blocked = true").unwrap();
+        let waiting = issues.get_issue("waiting").unwrap();
+        assert_eq!(issues.effective_state(waiting), State::Blocked);
+        let unblocked = issues.get_issue("unblocked").unwrap();
+        assert_eq!(issues.effective_state(unblocked), State::InProgress);
+        let dep = issues.get_issue("dep").unwrap();
+        assert_eq!(issues.effective_state(dep), State::InProgress);
+
+        // Transitions the workflow doesn't allow are not made.
+        issues.config = toml::from_str(
+            "auto_blocked = true\n[workflow.transitions]\nBacklog = [\"InProgress\"]\nBlocked = [\"Backlog\"]\n",
+        )
+        .unwrap();
+        let waiting = issues.get_issue("waiting").unwrap();
+        assert_eq!(issues.effective_state(waiting), State::Backlog);
+        let unblocked = issues.get_issue("unblocked").unwrap();
+        assert_eq!(issues.effective_state(unblocked), State::Blocked);
+    }
+
+    #[test]
+    fn dependency_cycles() {
+        let mut issues = Issues::new();
+        issues.add_issue(make_issue("a", State::New, None, &["b"]));
+        issues.add_issue(make_issue("b", State::New, None, &["c"]));
+        issues.add_issue(make_issue("c", State::New, None, &[]));
+
+        assert!(issues.dependency_creates_cycle("c", "a"));
+        assert!(issues.dependency_creates_cycle("c", "b"));
+        assert!(!issues.dependency_creates_cycle("a", "c"));
+    }
+
+    #[test]
+    fn set_config_format() {
+        assert_eq!(super::set_config_format("", 2), "format = 2\n");
+        assert_eq!(
+            super::set_config_format("auto_blocked = true\n\n[fields]\nformat = \"string\"\n", 2),
+            "format = 2\nauto_blocked = true\n\n[fields]\nformat = \"string\"\n"
+        );
+        assert_eq!(
+            super::set_config_format("format = 1\nauto_blocked = true\n", 2),
+            "format = 2\nauto_blocked = true\n"
+        );
+    }
+
+    #[test]
+    fn auto_blocked_needs_open_blocked_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        std::fs::write(
+            &config,
+            "auto_blocked = true\n[workflow]\nstates = [\"New\", \"Done\"]\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            Issues::new_from_dir(dir.path()),
+            Err(Error::InvalidConfig(_))
+        ));
+
+        std::fs::write(
+            &config,
+            "auto_blocked = true\n[workflow]\nstates = [\"New\", \"Blocked\", \"Done\"]\nclosed = [\"Blocked\", \"Done\"]\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            Issues::new_from_dir(dir.path()),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn unsupported_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        std::fs::write(&config, format!("format = {}\n", FORMAT_VERSION + 1)).unwrap();
+        assert!(matches!(
+            Issues::new_from_dir(dir.path()),
+            Err(Error::UnsupportedFormat { .. })
+        ));
+
+        std::fs::write(&config, format!("format = {FORMAT_VERSION}\n")).unwrap();
+        let issues = Issues::new_from_dir(dir.path()).unwrap();
+        assert_eq!(issues.config().format(), FORMAT_VERSION);
+    }
 }
@@ -1,9 +1,13 @@
 #[cfg(feature = "log")]
 use log::debug;
 
+use std::str::FromStr;
+
+use crate::issue::State;
+
 /// `IssuesMut` is a deserialization of the GitDB, using a long-lived
-/// ephemeral worktree. The worktree is made from the named branch,
-/// and is not dropped until the IssuesMut object is dropped.
+/// ephemeral snapshot of the named branch (see `crate::gitdb::GitDbMut`),
+/// which is not dropped until the IssuesMut object is dropped.
 ///
 /// This means you can make changes to the IssuesMut object and the
 /// changes will be incorporated into the GitDb ref as commits.
@@ -20,27 +24,69 @@ pub enum Error {
 
     #[error(transparent)]
     GitDB(#[from] crate::gitdb::Error),
+
+    #[error(transparent)]
+    Issue(#[from] crate::issue::IssueError),
+
+    #[error("issue {0} not found")]
+    IssueNotFound(String),
+
+    #[error("making {parent} the parent of {issue} would create a cycle")]
+    ParentCycle { issue: String, parent: String },
+
+    #[error("making {issue} depend on {dependency} would create a cycle")]
+    DependencyCycle { issue: String, dependency: String },
+
+    #[error("state {0} is not one of the workflow states")]
+    UnknownState(State),
+
+    #[error("the workflow does not allow changing state from {from} to {to}")]
+    TransitionNotAllowed { from: State, to: State },
+
+    #[error("field {0} is not declared in config.toml")]
+    UnknownField(String),
+
+    #[error(transparent)]
+    Field(#[from] crate::field::FieldError),
+
+    #[error("template {0} not found")]
+    TemplateNotFound(String),
+
+    #[error(transparent)]
+    FrontMatter(#[from] crate::front_matter::FrontMatterError),
+
+    #[error(transparent)]
+    ChronoParseError(#[from] chrono::format::ParseError),
+
+    #[error("milestone {0} not found")]
+    MilestoneNotFound(String),
+
+    #[error(transparent)]
+    Milestone(#[from] crate::milestone::MilestoneError),
+
+    #[error("invalid repo name {0:?}")]
+    InvalidRepoName(String),
 }
 
 /// Public API of Issues.
 impl IssuesMut {
     /// Read Issues from a git ref (typically the `entomologist-data`
-    /// branch). The resulting Issues struct provides a mutable, read-write
-    /// view of the issues recorded in the git ref. The IssuesMut includes
-    /// a git worktree with a checkout of the specified git ref, which
+    /// branch) of a repo. The resulting Issues struct provides a mutable,
+    /// read-write view of the issues recorded in the git ref. The IssuesMut includes
+    /// a snapshot of the files of the specified git ref, which
     /// enables adding/modifying/removing Issue objects and committing
     /// to the git ref.
     ///
     /// For an immutable read-only view use Issues instead.
-    pub fn new_from_git(git_ref: &str) -> Result<Self, Error> {
-        let gitdb_mut = crate::gitdb::GitDbMut::get(git_ref)?;
+    pub fn new_from_git(repo: &crate::Repo, git_ref: &str) -> Result<Self, Error> {
+        let gitdb_mut = crate::gitdb::GitDbMut::get(repo, git_ref)?;
         let issues = crate::Issues::new_from_dir(&gitdb_mut.path())?;
-        // The GitDbMut goes in the IssuesMut, so the underlying worktree survives as long as the
+        // The GitDbMut goes in the IssuesMut, so the underlying snapshot survives as long as the
         // IssuesMut survives.
         Ok(Self { gitdb_mut, issues })
     }
 
-    /// Get the path of the git worktree used as the backing store.
+    /// Get the path of the snapshot used as the backing store.
     pub fn path(&self) -> std::path::PathBuf {
         self.gitdb_mut.path()
     }
@@ -52,6 +98,47 @@ impl IssuesMut {
     //     self.issues.add_issue(issue);
     // }
 
+    /// Create a new Issue, optionally from the named Template.  The
+    /// template's state and custom fields are checked against the
+    /// database configuration before the Issue is created.  Commits.
+    pub fn new_issue(
+        &mut self,
+        description: &Option<String>,
+        template_name: Option<&str>,
+    ) -> Result<&crate::Issue, Error> {
+        let template = match template_name {
+            None => crate::template::Template::default(),
+            Some(name) => match self.issues.get_template(name) {
+                Some(template) => template.clone(),
+                None => return Err(Error::TemplateNotFound(String::from(name))),
+            },
+        };
+
+        let config = self.issues.config();
+        if let Some(state) = &template.state {
+            let state = State::from_name(state)?;
+            if !config.workflow.has_state(&state) {
+                return Err(Error::UnknownState(state));
+            }
+        }
+        for (name, value) in &template.fields {
+            let Some(field_type) = config.fields.get(name) else {
+                return Err(Error::UnknownField(name.clone()));
+            };
+            field_type.parse(name, value)?;
+        }
+
+        let mut issue =
+            crate::Issue::new_from_template(&self.gitdb_mut.path(), description, &template)?;
+        config.type_fields(&mut issue);
+        let issue_id = issue.id.clone();
+        self.issues.add_issue(issue);
+        match self.issues.get_issue(&issue_id) {
+            Some(issue) => Ok(issue),
+            None => Err(Error::IssueNotFound(issue_id)),
+        }
+    }
+
     /// Look up an Issue by its id.
     pub fn get_issue(&self, issue_id: &str) -> Option<&crate::Issue> {
         self.issues.get_issue(issue_id)
@@ -72,9 +159,857 @@ impl IssuesMut {
         self.issues.iter_mut()
     }
 
+    /// Get the configuration of the issues database.
+    pub fn config(&self) -> &crate::issues::Config {
+        self.issues.config()
+    }
+
+    /// Change the State of an Issue, enforcing the workflow from the
+    /// database configuration.  If the new state is closed, the Issue's
+    /// `done_time` is set.  Commits.
+    pub fn set_state(&mut self, issue_id: &str, new_state: State) -> Result<(), Error> {
+        let workflow = &self.issues.config().workflow;
+        if !workflow.has_state(&new_state) {
+            return Err(Error::UnknownState(new_state));
+        }
+        let closed = workflow.is_closed(&new_state);
+        let Some(issue) = self.issues.get_issue(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        if !workflow.allows_transition(&issue.state, &new_state) {
+            return Err(Error::TransitionNotAllowed {
+                from: issue.state.clone(),
+                to: new_state,
+            });
+        }
+
+        let Some(issue) = self.issues.get_issue_mut(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        issue.set_state(new_state.clone())?;
+        // Issue::set_state() already sets the done_time of Done issues.
+        if closed && new_state != State::Done {
+            issue.set_done_time(chrono::Local::now())?;
+        }
+        Ok(())
+    }
+
+    /// Create a new Issue by editing a document with the description
+    /// and all the metadata of the Issue (see `crate::front_matter`),
+    /// optionally starting from the named Template.  Invalid documents
+    /// are re-opened in the editor with the errors added.  Commits.
+    ///
+    /// Returns None if the user gave up by emptying the document.
+    pub fn new_issue_full(
+        &mut self,
+        template_name: Option<&str>,
+    ) -> Result<Option<&crate::Issue>, Error> {
+        let template = match template_name {
+            None => crate::template::Template::default(),
+            Some(name) => match self.issues.get_template(name) {
+                Some(template) => template.clone(),
+                None => return Err(Error::TemplateNotFound(String::from(name))),
+            },
+        };
+        let mut metadata = crate::front_matter::Metadata::from(&template);
+        if metadata.state.is_none() {
+            metadata.state = Some(self.issues.config().workflow.label(&State::New));
+        }
+        let document = crate::front_matter::render(
+            &metadata,
+            template.description.as_deref().unwrap_or_default(),
+        )?;
+
+        let Some((description, metadata)) = crate::front_matter::edit(&document, |document| {
+            self.check_document(None, document)
+                .map_err(|e| e.to_string())
+        })?
+        else {
+            return Ok(None);
+        };
+
+        let mut issue =
+            crate::Issue::new_from_metadata(&self.gitdb_mut.path(), &description, &metadata)?;
+        self.issues.config().type_fields(&mut issue);
+        let issue_id = issue.id.clone();
+        self.issues.add_issue(issue);
+        match self.issues.get_issue(&issue_id) {
+            Some(issue) => Ok(Some(issue)),
+            None => Err(Error::IssueNotFound(issue_id)),
+        }
+    }
+
+    /// Edit the description and all the metadata of an Issue as a
+    /// single document (see `crate::front_matter`).  Invalid documents
+    /// are re-opened in the editor with the errors added.  All the
+    /// changes are committed together.
+    ///
+    /// Returns false if the user gave up by emptying the document.
+    pub fn edit_issue_full(&mut self, issue_id: &str) -> Result<bool, Error> {
+        self.edit_issue_full_with(issue_id, |document, accept| {
+            crate::front_matter::edit(document, accept)
+        })
+    }
+
+    /// `edit_issue_full()`, with `edit` in place of
+    /// `crate::front_matter::edit()`.
+    pub(crate) fn edit_issue_full_with(
+        &mut self,
+        issue_id: &str,
+        edit: impl FnOnce(
+            &str,
+            &mut dyn FnMut(&str) -> Result<(String, crate::front_matter::Metadata), String>,
+        ) -> Result<
+            Option<(String, crate::front_matter::Metadata)>,
+            crate::front_matter::FrontMatterError,
+        >,
+    ) -> Result<bool, Error> {
+        let Some(issue) = self.issues.get_issue(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        let workflow = &self.issues.config().workflow;
+        let mut metadata = issue.metadata();
+        metadata.state = Some(workflow.label(&issue.state));
+        let document = crate::front_matter::render(&metadata, &issue.description)?;
+        let old_state = issue.state.clone();
+        let old_done_time = metadata.done_time.clone();
+
+        let Some((description, mut metadata)) = edit(&document, &mut |document| {
+            self.check_document(Some(issue_id), document)
+                .map_err(|e| e.to_string())
+        })?
+        else {
+            return Ok(false);
+        };
+
+        // Like set_state(), moving to a closed state sets the done_time,
+        // unless the user set it by hand.
+        let workflow = &self.issues.config().workflow;
+        if let Some(new_state) = &metadata.state {
+            let new_state = State::from_name(new_state)?;
+            if workflow.is_closed(&new_state)
+                && !workflow.is_closed(&old_state)
+                && metadata.done_time == old_done_time
+            {
+                metadata.done_time = Some(chrono::Local::now().to_rfc3339());
+            }
+        }
+
+        let Some(issue) = self.issues.get_issue_mut(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        issue.edit_full(&description, &metadata)?;
+        self.issues.type_fields(issue_id);
+        Ok(true)
+    }
+
+    /// Delete an Issue.  Other Issues that refer to it (as a dependency,
+    /// parent or link target) are left alone, references to missing
+    /// Issues are ignored.  Commits.
+    pub fn remove_issue(&mut self, issue_id: &str) -> Result<(), Error> {
+        let Some(issue) = self.issues.remove_issue(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        issue.remove()?;
+        Ok(())
+    }
+
+    /// Find the Issue that has the Comment with the specified uuid.
+    pub fn issue_of_comment(&self, comment_uuid: &str) -> Option<&crate::Issue> {
+        self.issues
+            .iter()
+            .map(|(_, issue)| issue)
+            .find(|issue| issue.get_comment(comment_uuid).is_some())
+    }
+
+    /// Move a Comment to another Issue.  Commits.
+    pub fn move_comment(&mut self, comment_uuid: &str, target_id: &str) -> Result<(), Error> {
+        let Some(source) = self.issue_of_comment(comment_uuid) else {
+            return Err(
+                crate::issue::IssueError::CommentNotFound(String::from(comment_uuid)).into(),
+            );
+        };
+        let source_id = source.id.clone();
+        if source_id == target_id {
+            return Ok(());
+        }
+        let Some(mut target) = self.issues.remove_issue(target_id) else {
+            return Err(Error::IssueNotFound(String::from(target_id)));
+        };
+        let result = match self.issues.get_issue_mut(&source_id) {
+            Some(source) => source.move_comment(comment_uuid, &mut target),
+            None => Err(crate::issue::IssueError::CommentNotFound(String::from(
+                comment_uuid,
+            ))),
+        };
+        self.issues.add_issue(target);
+        result?;
+        Ok(())
+    }
+
+    /// Merge Issue `from_id` into Issue `into_id`: move all its comments
+    /// to `into_id`, add its tags and dependencies to `into_id`, and mark
+    /// it as a duplicate of `into_id`.  Dependencies that would create a
+    /// cycle are not added, they are returned instead.  The whole merge
+    /// is a single commit, if any of it fails nothing is committed and
+    /// both Issues are left as they were.
+    pub fn merge_issues(
+        &mut self,
+        from_id: &str,
+        into_id: &str,
+    ) -> Result<Vec<crate::issue::IssueHandle>, Error> {
+        if from_id == into_id {
+            return Err(crate::issue::IssueError::LinkSelf.into());
+        }
+        let Some(from) = self.issues.get_issue(from_id) else {
+            return Err(Error::IssueNotFound(String::from(from_id)));
+        };
+        if self.issues.get_issue(into_id).is_none() {
+            return Err(Error::IssueNotFound(String::from(into_id)));
+        }
+        let (dependencies, skipped): (
+            Vec<crate::issue::IssueHandle>,
+            Vec<crate::issue::IssueHandle>,
+        ) = from
+            .dependencies
+            .iter()
+            .flatten()
+            .filter(|dep| dep.as_str() != into_id)
+            .cloned()
+            .partition(|dep| !self.issues.dependency_creates_cycle(into_id, dep));
+
+        self.batch(
+            &[from_id, into_id],
+            &format!("merge issue {from_id} into issue {into_id}"),
+            |issues| {
+                let Some(mut from) = issues.issues.remove_issue(from_id) else {
+                    return Err(Error::IssueNotFound(String::from(from_id)));
+                };
+                let result = match issues.issues.get_issue_mut(into_id) {
+                    Some(into) => into.merge_from(&mut from, &dependencies),
+                    None => Err(crate::issue::IssueError::IdError),
+                };
+                issues.issues.add_issue(from);
+                result?;
+                issues.mark_duplicate(from_id, into_id)
+            },
+        )?;
+        Ok(skipped)
+    }
+
+    /// Create a new Issue from a Comment.  The description of the new
+    /// Issue is the Comment's description, the new Issue links to the
+    /// Issue the Comment is on, and a comment saying so is added to the
+    /// old Issue.  The Comment itself stays where it is.  Commits.
+    ///
+    /// Returns the id of the new Issue.
+    pub fn split_comment(
+        &mut self,
+        comment_uuid: &str,
+    ) -> Result<crate::issue::IssueHandle, Error> {
+        let Some(source) = self.issue_of_comment(comment_uuid) else {
+            return Err(
+                crate::issue::IssueError::CommentNotFound(String::from(comment_uuid)).into(),
+            );
+        };
+        let source_id = source.id.clone();
+        let description = match source.get_comment(comment_uuid) {
+            Some(comment) => comment.description.clone(),
+            None => {
+                return Err(
+                    crate::issue::IssueError::CommentNotFound(String::from(comment_uuid)).into(),
+                );
+            }
+        };
+
+        let new_id = self.new_issue(&Some(description), None)?.id.clone();
+        self.add_link(&new_id, crate::issue::LinkType::RelatesTo, &source_id)?;
+        let Some(source) = self.issues.get_issue_mut(&source_id) else {
+            return Err(Error::IssueNotFound(source_id));
+        };
+        let comment = source.add_comment(&Some(format!(
+            "Comment {comment_uuid} split into issue {new_id}\n"
+        )))?;
+        source.comments.push(comment);
+        Ok(new_id)
+    }
+
+    /// Attach a file to the Issue or Comment with the specified uuid.
+    /// The attachment is named `name`, or after the file if `name` is
+    /// None.  Commits.
+    pub fn attach(
+        &mut self,
+        uuid: &str,
+        file: &std::path::Path,
+        name: Option<&str>,
+    ) -> Result<crate::attachment::Attachment, Error> {
+        let name = match name {
+            Some(name) => String::from(name),
+            None => match file.file_name() {
+                Some(file_name) => file_name.to_string_lossy().into_owned(),
+                None => {
+                    return Err(crate::issue::IssueError::from(
+                        crate::attachment::AttachmentError::InvalidName(
+                            file.to_string_lossy().into_owned(),
+                        ),
+                    )
+                    .into());
+                }
+            },
+        };
+        let use_blobs = self.issues.config().blob_attachments;
+        if use_blobs {
+            // The blob store is a format 2 directory.
+            return self.with_format(2, |issues| {
+                issues.add_attachment_inner(uuid, file, &name, use_blobs)
+            });
+        }
+        self.add_attachment_inner(uuid, file, &name, use_blobs)
+    }
+
+    fn add_attachment_inner(
+        &mut self,
+        uuid: &str,
+        file: &std::path::Path,
+        name: &str,
+        use_blobs: bool,
+    ) -> Result<crate::attachment::Attachment, Error> {
+        if let Some(issue) = self.issues.get_issue_mut(uuid) {
+            return Ok(issue.add_attachment(file, name, use_blobs)?);
+        }
+        let Some(issue_id) = self.issue_of_comment(uuid).map(|issue| issue.id.clone()) else {
+            return Err(Error::IssueNotFound(String::from(uuid)));
+        };
+        let Some(comment) = self
+            .issues
+            .get_issue_mut(&issue_id)
+            .and_then(|issue| issue.comments.iter_mut().find(|c| c.uuid == uuid))
+        else {
+            return Err(Error::IssueNotFound(String::from(uuid)));
+        };
+        comment
+            .add_attachment(file, name, use_blobs)
+            .map_err(|e| crate::issue::IssueError::from(e).into())
+    }
+
+    /// Read the contents of an attachment of the Issue or Comment with
+    /// the specified uuid.
+    pub fn read_attachment(&self, uuid: &str, name: &str) -> Result<Vec<u8>, Error> {
+        if let Some(issue) = self.issues.get_issue(uuid) {
+            return Ok(issue.read_attachment(name)?);
+        }
+        let Some(comment) = self
+            .issue_of_comment(uuid)
+            .and_then(|issue| issue.get_comment(uuid))
+        else {
+            return Err(Error::IssueNotFound(String::from(uuid)));
+        };
+        comment
+            .read_attachment(name)
+            .map_err(|e| crate::issue::IssueError::from(e).into())
+    }
+
+    /// Create a new, open Milestone.  Commits.
+    pub fn new_milestone(
+        &mut self,
+        name: &str,
+        description: &str,
+        target_date: Option<chrono::NaiveDate>,
+    ) -> Result<&crate::milestone::Milestone, Error> {
+        // The milestones directory is a format 2 directory.
+        let milestone = self.with_format(2, |issues| {
+            Ok(crate::milestone::Milestone::new(
+                &issues.path(),
+                name,
+                description,
+                target_date,
+            )?)
+        })?;
+        self.issues.add_milestone(milestone);
+        match self.issues.get_milestone(name) {
+            Some(milestone) => Ok(milestone),
+            None => Err(Error::MilestoneNotFound(String::from(name))),
+        }
+    }
+
+    /// Close or reopen a Milestone.  Commits.
+    pub fn set_milestone_closed(&mut self, name: &str, closed: bool) -> Result<(), Error> {
+        let Some(milestone) = self.issues.get_milestone_mut(name) else {
+            return Err(Error::MilestoneNotFound(String::from(name)));
+        };
+        milestone.set_closed(closed)?;
+        Ok(())
+    }
+
+    /// Add an Issue to a Milestone, or remove it from its Milestone if
+    /// `milestone` is None.  Commits.
+    pub fn set_milestone(&mut self, issue_id: &str, milestone: Option<&str>) -> Result<(), Error> {
+        if let Some(milestone) = milestone
+            && self.issues.get_milestone(milestone).is_none()
+        {
+            return Err(Error::MilestoneNotFound(String::from(milestone)));
+        }
+        let Some(issue) = self.issues.get_issue_mut(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        issue.set_milestone(milestone)?;
+        Ok(())
+    }
+
+    /// Set a custom field of an Issue.  The field must be declared in
+    /// the database configuration, and `value` must be valid for the
+    /// type of the field.  An empty `value` removes the field from the
+    /// Issue.  Commits.
+    pub fn set_field(&mut self, issue_id: &str, name: &str, value: &str) -> Result<(), Error> {
+        let Some(field_type) = self.issues.config().fields.get(name) else {
+            return Err(Error::UnknownField(String::from(name)));
+        };
+        let value = if value.is_empty() {
+            None
+        } else {
+            Some(field_type.parse(name, value)?)
+        };
+        let Some(issue) = self.issues.get_issue_mut(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        issue.set_field(name, value)?;
+        Ok(())
+    }
+
+    /// Apply the `auto_blocked` policy from the database configuration:
+    /// open Issues with unresolved dependencies are moved to Blocked, and
+    /// automatically blocked Issues whose dependencies are all resolved
+    /// are moved back to the state they were in before.  Each change is
+    /// a separate commit.  Issues are only moved if the workflow allows
+    /// the transition.
+    ///
+    /// Returns the changes made, as `(issue id, old state, new state)`.
+    /// Does nothing if the policy is not enabled.
+    pub fn update_blocked_states(
+        &mut self,
+    ) -> Result<Vec<(crate::issue::IssueHandle, State, State)>, Error> {
+        let mut changes = Vec::new();
+        if !self.issues.config().auto_blocked {
+            return Ok(changes);
+        }
+
+        let mut issue_ids: Vec<crate::issue::IssueHandle> =
+            self.issues.iter().map(|(id, _)| id.clone()).collect();
+        issue_ids.sort();
+        for issue_id in issue_ids {
+            let blockers: Vec<crate::issue::IssueHandle> = self
+                .issues
+                .blocking_dependencies(&issue_id)
+                .into_iter()
+                .cloned()
+                .collect();
+            let Some(new_state) = self
+                .issues
+                .get_issue(&issue_id)
+                .and_then(|issue| self.issues.auto_blocked_state(issue))
+            else {
+                continue;
+            };
+            let Some(issue) = self.issues.get_issue_mut(&issue_id) else {
+                continue;
+            };
+            let old_state = issue.state.clone();
+            if new_state == State::Blocked {
+                let blockers: Vec<&crate::issue::IssueHandle> = blockers.iter().collect();
+                issue.block(&blockers)?;
+            } else {
+                issue.unblock()?;
+            }
+            changes.push((issue_id, old_state, issue.state.clone()));
+        }
+        Ok(changes)
+    }
+
+    /// Return the Issues that depend on the specified Issue.
+    pub fn dependents(&self, issue_id: &str) -> Vec<&crate::Issue> {
+        self.issues.dependents(issue_id)
+    }
+
+    /// Return the children of an Issue.
+    pub fn children(&self, issue_id: &str) -> Vec<&crate::Issue> {
+        self.issues.children(issue_id)
+    }
+
+    /// Return the links from other Issues to the specified Issue, as
+    /// `(link type, id of the linking issue)` pairs.
+    pub fn links_to(
+        &self,
+        issue_id: &str,
+    ) -> Vec<(crate::issue::LinkType, &crate::issue::IssueHandle)> {
+        self.issues.links_to(issue_id)
+    }
+
+    /// Returns true if an Issue is open and blocked, see
+    /// `Issues::is_blocked()`.
+    pub fn is_blocked(&self, issue: &crate::Issue) -> bool {
+        self.issues.is_blocked(issue)
+    }
+
+    /// Return the open Issues that are blocked only by the specified
+    /// Issue.
+    pub fn unblocked_by(&self, issue_id: &str) -> Vec<&crate::Issue> {
+        self.issues.unblocked_by(issue_id)
+    }
+
+    /// Set the parent of an Issue.  Fails if either Issue does not
+    /// exist, or if the new parent would create a cycle in the issue
+    /// hierarchy.  Commits.
+    pub fn set_parent(&mut self, issue_id: &str, parent_id: &str) -> Result<(), Error> {
+        if self.issues.get_issue(parent_id).is_none() {
+            return Err(Error::IssueNotFound(String::from(parent_id)));
+        }
+        if self.issues.parent_creates_cycle(issue_id, parent_id) {
+            return Err(Error::ParentCycle {
+                issue: String::from(issue_id),
+                parent: String::from(parent_id),
+            });
+        }
+        let Some(issue) = self.issues.get_issue_mut(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        issue.set_parent(&String::from(parent_id))?;
+        Ok(())
+    }
+
+    /// Add a dependency to an Issue.  Fails if either Issue does not
+    /// exist, or if the new dependency would create a cycle in the
+    /// dependency graph.  A dependency on an Issue in another repo
+    /// (`REPO:ID`, see `crate::multi`) can't be checked from here and
+    /// is added as is.  Commits.
+    pub fn add_dependency(&mut self, issue_id: &str, dep_id: &str) -> Result<(), Error> {
+        if let (Some(repo_name), _) = crate::multi::split_issue_ref(dep_id) {
+            if crate::multi::validate_repo_name(repo_name).is_err() {
+                return Err(Error::InvalidRepoName(String::from(repo_name)));
+            }
+        } else if self.issues.get_issue(dep_id).is_none() {
+            return Err(Error::IssueNotFound(String::from(dep_id)));
+        }
+        if self.issues.dependency_creates_cycle(issue_id, dep_id) {
+            return Err(Error::DependencyCycle {
+                issue: String::from(issue_id),
+                dependency: String::from(dep_id),
+            });
+        }
+        let Some(issue) = self.issues.get_issue_mut(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        issue.add_dependency(String::from(dep_id))?;
+        Ok(())
+    }
+
+    /// Add a typed link from one Issue to another.  Fails if either
+    /// Issue does not exist.  Commits.
+    pub fn add_link(
+        &mut self,
+        issue_id: &str,
+        link_type: crate::issue::LinkType,
+        target_id: &str,
+    ) -> Result<(), Error> {
+        if self.issues.get_issue(target_id).is_none() {
+            return Err(Error::IssueNotFound(String::from(target_id)));
+        }
+        let Some(issue) = self.issues.get_issue_mut(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        issue.add_link(link_type, &String::from(target_id))?;
+        Ok(())
+    }
+
+    /// Mark an Issue as a duplicate of another Issue: link the duplicate
+    /// to the original, add a comment saying so, and change the state of
+    /// the duplicate to WontDo.  Fails without changing anything if the
+    /// workflow doesn't let the duplicate move to WontDo.  The whole
+    /// change is a single commit.
+    pub fn mark_duplicate(&mut self, duplicate_id: &str, original_id: &str) -> Result<(), Error> {
+        let Some(duplicate) = self.issues.get_issue(duplicate_id) else {
+            return Err(Error::IssueNotFound(String::from(duplicate_id)));
+        };
+        let workflow = &self.issues.config().workflow;
+        if !workflow.has_state(&State::WontDo) {
+            return Err(Error::UnknownState(State::WontDo));
+        }
+        if !workflow.allows_transition(&duplicate.state, &State::WontDo) {
+            return Err(Error::TransitionNotAllowed {
+                from: duplicate.state.clone(),
+                to: State::WontDo,
+            });
+        }
+
+        self.batch(
+            &[duplicate_id],
+            &format!("mark issue {duplicate_id} as a duplicate of issue {original_id}"),
+            |issues| {
+                issues.add_link(
+                    duplicate_id,
+                    crate::issue::LinkType::DuplicateOf,
+                    original_id,
+                )?;
+                let Some(issue) = issues.issues.get_issue_mut(duplicate_id) else {
+                    return Err(Error::IssueNotFound(String::from(duplicate_id)));
+                };
+                let comment =
+                    issue.add_comment(&Some(format!("Duplicate of issue {original_id}\n")))?;
+                issue.comments.push(comment);
+                issues.set_state(duplicate_id, State::WontDo)
+            },
+        )
+    }
+
+    /// Make all the changes `f` makes a single commit, with the message
+    /// `msg`.  If any of it fails nothing is committed, the files are
+    /// put back the way they were, and the Issues `issue_ids` are read
+    /// again so they don't keep the changes that were undone.
+    fn batch<T>(
+        &mut self,
+        issue_ids: &[&str],
+        msg: &str,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let batch = crate::gitdb::writer::batch(&self.path());
+        let result = match f(self) {
+            Ok(value) => match batch {
+                Some(batch) => match batch.commit(msg) {
+                    Ok(()) => Ok(value),
+                    Err(e) => Err(crate::issue::IssueError::from(e).into()),
+                },
+                None => Ok(value),
+            },
+            Err(e) => {
+                // Dropping the batch puts the files back.
+                drop(batch);
+                Err(e)
+            }
+        };
+        if result.is_err() {
+            for issue_id in issue_ids {
+                // The original error is the one to report.
+                let _ = self.reload_issue(issue_id);
+            }
+        }
+        result
+    }
+
+    /// Run `f` with `config.toml` recording at least format `format`,
+    /// so ent versions that don't understand it refuse the database
+    /// instead of misreading it.  The config change is staged to go in
+    /// the commit made by `f`, and put back if `f` fails.
+    fn with_format<T>(
+        &mut self,
+        format: u32,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let old_format = self.issues.config().format;
+        if self.issues.config().format() >= format {
+            return f(self);
+        }
+        let config_path = self.path().join("config.toml");
+        let contents = match std::fs::read_to_string(&config_path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(crate::issues::Error::from(e).into()),
+        };
+        std::fs::write(
+            &config_path,
+            crate::issues::set_config_format(&contents, format),
+        )
+        .map_err(crate::issues::Error::from)?;
+        crate::git::add(&config_path).map_err(crate::gitdb::Error::from)?;
+        self.issues.set_format(Some(format));
+
+        let result = f(self);
+        if result.is_err() {
+            crate::git::restore_file(&config_path).map_err(crate::gitdb::Error::from)?;
+            self.issues.set_format(old_format);
+        }
+        result
+    }
+
+    /// Read an Issue again from its directory.
+    fn reload_issue(&mut self, issue_id: &str) -> Result<(), Error> {
+        let Some(issue) = self.issues.get_issue(issue_id) else {
+            return Err(Error::IssueNotFound(String::from(issue_id)));
+        };
+        let issue =
+            crate::Issue::new_from_dir_with_workflow(&issue.dir, &self.issues.config().workflow)?;
+        self.issues.add_issue(issue);
+        self.issues.type_fields(issue_id);
+        Ok(())
+    }
+
+    /// Parse a document from `ent edit --full` or `ent new --full` and
+    /// check its metadata against the issues database.  `issue_id` is
+    /// the Issue being edited, or None for a new Issue.  Empty values
+    /// are removed from the metadata.
+    fn check_document(
+        &self,
+        issue_id: Option<&str>,
+        document: &str,
+    ) -> Result<(String, crate::front_matter::Metadata), Error> {
+        let (description, mut metadata) = match crate::front_matter::parse(document) {
+            Ok((metadata, description)) => (description, metadata),
+            Err(e) => return Err(e.into()),
+        };
+        if description.trim().is_empty() {
+            return Err(crate::issue::IssueError::EmptyDescription.into());
+        }
+
+        let config = self.issues.config();
+        if let Some(state) = &metadata.state {
+            let state = State::from_name(state)?;
+            if !config.workflow.has_state(&state) {
+                return Err(Error::UnknownState(state));
+            }
+            if let Some(issue) = issue_id.and_then(|id| self.issues.get_issue(id))
+                && !config.workflow.allows_transition(&issue.state, &state)
+            {
+                return Err(Error::TransitionNotAllowed {
+                    from: issue.state.clone(),
+                    to: state,
+                });
+            }
+        }
+
+        metadata.assignee = metadata.assignee.filter(|assignee| !assignee.is_empty());
+        metadata.parent = metadata.parent.filter(|parent| !parent.is_empty());
+        metadata.milestone = metadata.milestone.filter(|milestone| !milestone.is_empty());
+        metadata.done_time = metadata.done_time.filter(|done_time| !done_time.is_empty());
+        metadata.tags.retain(|tag| !tag.is_empty());
+        metadata.fields.retain(|_, value| !value.is_empty());
+
+        if let Some(done_time) = &metadata.done_time {
+            chrono::DateTime::parse_from_rfc3339(done_time)?;
+        }
+
+        if let Some(parent_id) = &metadata.parent {
+            if self.issues.get_issue(parent_id).is_none() {
+                return Err(Error::IssueNotFound(parent_id.clone()));
+            }
+            if let Some(issue_id) = issue_id
+                && self.issues.parent_creates_cycle(issue_id, parent_id)
+            {
+                return Err(Error::ParentCycle {
+                    issue: String::from(issue_id),
+                    parent: parent_id.clone(),
+                });
+            }
+        }
+
+        if let Some(milestone) = &metadata.milestone
+            && self.issues.get_milestone(milestone).is_none()
+        {
+            return Err(Error::MilestoneNotFound(milestone.clone()));
+        }
+
+        for dep_id in &metadata.dependencies {
+            // Like add_dependency(), dependencies on issues in other
+            // repos can't be checked from here.
+            if let (Some(repo_name), _) = crate::multi::split_issue_ref(dep_id) {
+                if crate::multi::validate_repo_name(repo_name).is_err() {
+                    return Err(Error::InvalidRepoName(String::from(repo_name)));
+                }
+                continue;
+            }
+            if self.issues.get_issue(dep_id).is_none() {
+                return Err(Error::IssueNotFound(dep_id.clone()));
+            }
+            if let Some(issue_id) = issue_id
+                && self.issues.dependency_creates_cycle(issue_id, dep_id)
+            {
+                return Err(Error::DependencyCycle {
+                    issue: String::from(issue_id),
+                    dependency: dep_id.clone(),
+                });
+            }
+        }
+
+        for (link_type, targets) in &metadata.links {
+            crate::issue::LinkType::from_str(link_type)?;
+            for target_id in targets {
+                if issue_id == Some(target_id.as_str()) {
+                    return Err(crate::issue::IssueError::LinkSelf.into());
+                }
+                if self.issues.get_issue(target_id).is_none() {
+                    return Err(Error::IssueNotFound(target_id.clone()));
+                }
+            }
+        }
+
+        for (name, value) in &metadata.fields {
+            let Some(field_type) = config.fields.get(name) else {
+                return Err(Error::UnknownField(name.clone()));
+            };
+            field_type.parse(name, value)?;
+        }
+
+        Ok((description, metadata))
+    }
+
     /// This converts an IssuesMut into an Issues, which drops the
-    /// long-lived named-branch worktree of the IssuesMut.
+    /// long-lived named-branch snapshot of the IssuesMut.
     pub fn drop_mut(self) -> crate::Issues {
         self.issues
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::run;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn edit_cross_repo_dependency() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = std::process::Command::new("git")
+            .args(["init", "--bare", &dir.path().to_string_lossy()])
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        let repo = crate::Repo::new(dir.path());
+        run(repo.git().args(["config", "user.name", "Test"])).unwrap();
+        run(repo
+            .git()
+            .args(["config", "user.email", "test@example.com"]))
+        .unwrap();
+
+        let mut issues = IssuesMut::new_from_git(&repo, "entomologist-data").unwrap();
+        let issue_id = issues
+            .new_issue(&Some(String::from("depends on the firmware")), None)
+            .unwrap()
+            .id
+            .clone();
+        issues
+            .add_dependency(&issue_id, "firmware:1234abcd")
+            .unwrap();
+
+        // Save the document as it is, the way an editor would.
+        let edited = issues
+            .edit_issue_full_with(&issue_id, |document, accept| {
+                Ok(Some(accept(document).unwrap()))
+            })
+            .unwrap();
+        assert!(edited);
+        assert_eq!(
+            issues.get_issue(&issue_id).unwrap().dependencies,
+            Some(vec![String::from("firmware:1234abcd")])
+        );
+
+        let mut document = String::new();
+        issues
+            .edit_issue_full_with(&issue_id, |rendered, _| {
+                document = String::from(rendered);
+                Ok(None)
+            })
+            .unwrap();
+        let bad_repo_name = document.replace("firmware:", "firm/ware:");
+        assert!(matches!(
+            issues.check_document(Some(&issue_id), &bad_repo_name),
+            Err(Error::InvalidRepoName(_))
+        ));
+    }
+}
@@ -6,22 +6,39 @@ use std::str::FromStr;
 
 pub mod gitdb;
 
+pub mod attachment;
+pub mod changelog;
+pub mod changes;
+pub mod cleanup;
 pub mod comment;
+pub mod field;
+pub mod front_matter;
 pub mod git;
 pub mod issue;
 pub mod issues;
 pub mod issues_mut;
+pub mod lock;
+pub mod milestone;
+pub mod multi;
+pub mod proposal;
+pub mod repo;
+pub mod template;
+pub mod workflow;
+pub mod worklog;
 
 use crate::issue::State;
 
 pub use crate::issue::Issue;
 pub use crate::issues::Issues;
 pub use crate::issues_mut::IssuesMut;
+pub use crate::repo::Repo;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ParseFilterError {
     #[error("Failed to parse filter")]
     ParseError,
+    #[error("unknown filter {0:?}")]
+    UnknownFilter(String),
     #[error(transparent)]
     IssueParseError(#[from] crate::issue::IssueError),
     #[error(transparent)]
@@ -37,8 +54,14 @@ pub struct Filter<'a> {
     pub include_assignees: std::collections::HashSet<&'a str>,
     pub include_tags: std::collections::HashSet<&'a str>,
     pub exclude_tags: std::collections::HashSet<&'a str>,
+    /// Values to include for each custom field, by field name.  The
+    /// empty string matches issues that don't have the field set.
+    pub include_fields: std::collections::HashMap<&'a str, std::collections::HashSet<&'a str>>,
     pub start_done_time: Option<chrono::DateTime<chrono::Local>>,
     pub end_done_time: Option<chrono::DateTime<chrono::Local>>,
+    /// The workflow whose custom states may appear in `state=` filters.
+    /// Without a workflow only the built-in states are accepted.
+    pub workflow: Option<&'a crate::workflow::Workflow>,
 }
 
 impl<'a> Default for Filter<'a> {
@@ -48,6 +71,14 @@ impl<'a> Default for Filter<'a> {
 }
 
 impl<'a> Filter<'a> {
+    /// Make a Filter that includes the open states of the workflow.
+    pub fn new_for_workflow(workflow: &'a crate::workflow::Workflow) -> Filter<'a> {
+        let mut filter = Self::new();
+        filter.include_states = workflow.open_states();
+        filter.workflow = Some(workflow);
+        filter
+    }
+
     pub fn new() -> Filter<'a> {
         Self {
             include_states: std::collections::HashSet::<crate::issue::State>::from([
@@ -59,8 +90,10 @@ impl<'a> Filter<'a> {
             include_assignees: std::collections::HashSet::<&'a str>::new(),
             include_tags: std::collections::HashSet::<&'a str>::new(),
             exclude_tags: std::collections::HashSet::<&'a str>::new(),
+            include_fields: std::collections::HashMap::new(),
             start_done_time: None,
             end_done_time: None,
+            workflow: None,
         }
     }
 
@@ -74,8 +107,11 @@ impl<'a> Filter<'a> {
             "state" => {
                 self.include_states.clear();
                 for s in tokens[1].split(",") {
-                    self.include_states
-                        .insert(crate::issue::State::from_str(s)?);
+                    let state = match self.workflow {
+                        Some(workflow) => crate::issue::State::parse(s, workflow)?,
+                        None => crate::issue::State::from_str(s)?,
+                    };
+                    self.include_states.insert(state);
                 }
             }
 
@@ -122,12 +158,66 @@ impl<'a> Filter<'a> {
                 }
             }
 
+            name if name.starts_with("field.") => {
+                let field = &name["field.".len()..];
+                crate::field::validate_name(field).map_err(|_| ParseFilterError::ParseError)?;
+                self.include_fields
+                    .insert(field, tokens[1].split(",").collect());
+            }
+
             _ => {
-                println!("unknown filter string {filter_str:?}");
-                return Err(ParseFilterError::ParseError);
+                return Err(ParseFilterError::UnknownFilter(String::from(filter_str)));
             }
         }
 
         Ok(())
     }
+
+    /// Return true if the Issue, shown in `state`, passes the Filter.
+    pub fn matches(&self, issue: &crate::Issue, state: &State) -> bool {
+        if !self.include_states.contains(state) {
+            return false;
+        }
+        if !self.include_assignees.is_empty() {
+            let assignee = match &issue.assignee {
+                Some(assignee) => assignee,
+                None => "",
+            };
+            if !self.include_assignees.contains(assignee) {
+                return false;
+            }
+        }
+
+        if !self.include_tags.is_empty() && !issue.has_any_tag(&self.include_tags) {
+            return false;
+        }
+        if !self.exclude_tags.is_empty() && issue.has_any_tag(&self.exclude_tags) {
+            return false;
+        }
+
+        if !self.include_fields.iter().all(|(name, values)| {
+            let value = match issue.fields.get(*name) {
+                Some(value) => value.to_string(),
+                None => String::new(),
+            };
+            values.contains(value.as_str())
+        }) {
+            return false;
+        }
+
+        if let Some(issue_done_time) = issue.done_time {
+            if let Some(start_done_time) = self.start_done_time
+                && start_done_time > issue_done_time
+            {
+                return false;
+            }
+            if let Some(end_done_time) = self.end_done_time
+                && end_done_time < issue_done_time
+            {
+                return false;
+            }
+        }
+
+        true
+    }
 }
@@ -0,0 +1,323 @@
+//! Lock files that keep `ent` processes on the same machine from
+//! stepping on each other.
+//!
+//! Locks live in the `entomologist-locks` directory of the repo's git
+//! dir.  A lock is a small TOML file saying who holds it:
+//!
+//! ```toml
+//! pid = 12345
+//! host = "workbench"
+//! time = "2025-07-24T10:08:38-06:00"
+//! ```
+//!
+//! A process that finds a lock held waits for it, telling the Repo's
+//! busy callback (if any) once, until the Repo's lock timeout runs
+//! out.  A lock held by a process that no longer exists on this host
+//! is stale, and is broken automatically.  Locks held from other hosts
+//! (when the repo is on a shared filesystem) can't be checked, use
+//! `break_lock()` for those.
+
+#[derive(Debug, thiserror::Error)]
+pub enum LockError {
+    #[error(transparent)]
+    StdIoError(#[from] std::io::Error),
+    #[error(transparent)]
+    GitError(#[from] crate::git::GitError),
+    #[error(
+        "the issue database is busy, locked by process {} on {} since {}",
+        .0.pid, .0.host, .0.time
+    )]
+    Busy(LockInfo),
+}
+
+/// Who holds a lock.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct LockInfo {
+    pub pid: u32,
+    pub host: String,
+    pub time: String,
+}
+
+impl LockInfo {
+    pub(crate) fn this_process() -> Self {
+        Self {
+            pid: std::process::id(),
+            host: hostname(),
+            time: chrono::Local::now().to_rfc3339(),
+        }
+    }
+
+    /// A lock is stale if it's held by a process on this host that
+    /// doesn't exist anymore.
+    pub fn is_stale(&self) -> bool {
+        self.host == hostname() && !process_exists(self.pid)
+    }
+}
+
+/// A held lock.  The lock is released when the Lock is dropped.
+#[derive(Debug)]
+pub struct Lock {
+    path: std::path::PathBuf,
+    registration: Option<crate::cleanup::Registration>,
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        self.registration.take();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// How long to sleep between attempts to take a held lock.
+const RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// A lock file that can't be parsed is one that's being written right
+/// now, or one whose writer died while writing it.  After this long
+/// it's the latter.
+const UNREADABLE_LOCK_AGE: std::time::Duration = std::time::Duration::from_secs(10);
+
+impl Lock {
+    /// Take the lock `name` in the repo, waiting for it as described
+    /// in the module documentation.
+    pub fn acquire(repo: &crate::Repo, name: &str) -> Result<Self, LockError> {
+        let path = lock_path(repo, name)?;
+        let start = std::time::Instant::now();
+        let mut told_busy = false;
+        loop {
+            match try_create(&path) {
+                Ok(()) => {
+                    let registration = Some(crate::cleanup::register_lock(&path));
+                    return Ok(Self { path, registration });
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => (),
+                Err(e) => return Err(e.into()),
+            }
+
+            match read_lock(&path)? {
+                None => {
+                    // Released between our attempt and now.
+                    continue;
+                }
+                Some(Ok(holder)) if holder.is_stale() => {
+                    remove_if_unchanged(&path, &holder)?;
+                    continue;
+                }
+                Some(Ok(holder)) => {
+                    if start.elapsed() >= repo.lock_timeout() {
+                        return Err(LockError::Busy(holder));
+                    }
+                    if !told_busy {
+                        if let Some(on_busy) = repo.on_busy() {
+                            on_busy(&holder);
+                        }
+                        told_busy = true;
+                    }
+                }
+                Some(Err(age)) => {
+                    if age >= UNREADABLE_LOCK_AGE {
+                        let _ = std::fs::remove_file(&path);
+                        continue;
+                    }
+                }
+            }
+            std::thread::sleep(RETRY_INTERVAL);
+        }
+    }
+
+    /// Who holds the lock `name` in the repo, if anyone.
+    pub fn holder(repo: &crate::Repo, name: &str) -> Result<Option<LockInfo>, LockError> {
+        let path = lock_path(repo, name)?;
+        Ok(match read_lock(&path)? {
+            Some(Ok(holder)) => Some(holder),
+            _ => None,
+        })
+    }
+
+    /// Remove the lock `name` in the repo, whoever holds it.  Returns
+    /// who held it.  Only for recovering from a stale lock that can't
+    /// be detected automatically.
+    pub fn break_lock(repo: &crate::Repo, name: &str) -> Result<Option<LockInfo>, LockError> {
+        let holder = Self::holder(repo, name)?;
+        match std::fs::remove_file(lock_path(repo, name)?) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+            Ok(()) => Ok(holder),
+        }
+    }
+}
+
+/// The name of the lock for writing to an issues branch.
+pub fn branch_lock_name(branch: &str) -> String {
+    format!("branch-{}", branch.replace('/', "%"))
+}
+
+fn lock_path(repo: &crate::Repo, name: &str) -> Result<std::path::PathBuf, LockError> {
+    let mut path = crate::git::git_common_dir(repo)?;
+    path.push("entomologist-locks");
+    std::fs::create_dir_all(&path)?;
+    path.push(format!("{name}.lock"));
+    Ok(path)
+}
+
+/// Atomically create the lock file with our LockInfo in it: write it
+/// to a temporary file, and hard-link that to the lock path, which
+/// fails if the lock exists.
+fn try_create(path: &std::path::Path) -> std::io::Result<()> {
+    let Some(dir) = path.parent() else {
+        return Err(std::io::Error::from(std::io::ErrorKind::NotFound));
+    };
+    let contents = toml::to_string(&LockInfo::this_process()).map_err(std::io::Error::other)?;
+    let tmp = tempfile::NamedTempFile::new_in(dir)?;
+    std::fs::write(tmp.path(), contents)?;
+    std::fs::hard_link(tmp.path(), path)
+}
+
+/// Read a lock file.  Returns None if there's no lock, and the age of
+/// the lock file if it can't be parsed.
+fn read_lock(
+    path: &std::path::Path,
+) -> Result<Option<Result<LockInfo, std::time::Duration>>, LockError> {
+    let contents = match std::fs::read_to_string(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+        Ok(contents) => contents,
+    };
+    match toml::from_str::<LockInfo>(&contents) {
+        Ok(holder) => Ok(Some(Ok(holder))),
+        Err(_) => {
+            let age = match std::fs::metadata(path)?.modified()?.elapsed() {
+                Ok(age) => age,
+                Err(_) => std::time::Duration::ZERO,
+            };
+            Ok(Some(Err(age)))
+        }
+    }
+}
+
+/// Remove a stale lock, unless someone else broke it and took the lock
+/// since we read it.
+fn remove_if_unchanged(path: &std::path::Path, stale: &LockInfo) -> Result<(), LockError> {
+    let mut stale_path = std::path::PathBuf::from(path);
+    stale_path.set_extension(format!("stale-{}", std::process::id()));
+    match std::fs::rename(path, &stale_path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+        Ok(()) => (),
+    }
+    if let Some(Ok(holder)) = read_lock(&stale_path)?
+        && &holder != stale
+    {
+        // Oops, that was a fresh lock, put it back.
+        let _ = std::fs::hard_link(&stale_path, path);
+    }
+    std::fs::remove_file(&stale_path)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: the buffer is valid for its length, and gethostname()
+    // writes at most that many bytes.
+    let result = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) };
+    if result != 0 {
+        return String::new();
+    }
+    let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
+
+#[cfg(not(unix))]
+fn hostname() -> String {
+    std::env::var("COMPUTERNAME").unwrap_or_default()
+}
+
+#[cfg(unix)]
+fn process_exists(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks whether the process exists.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // EPERM means the process exists but belongs to someone else.
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn process_exists(_pid: u32) -> bool {
+    // Can't tell, so never break locks automatically.
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// The same repo as `make_bare_repo()` in `tests/common`, keep the
+    /// two in step.
+    fn bare_repo() -> (tempfile::TempDir, crate::Repo) {
+        let dir = tempfile::tempdir().unwrap();
+        let result = std::process::Command::new("git")
+            .args(["init", "--bare", &dir.path().to_string_lossy()])
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        let repo = crate::Repo::new(dir.path());
+        (dir, repo)
+    }
+
+    #[test]
+    fn lock_busy_and_release() {
+        let (_dir, repo) = bare_repo();
+        let repo = repo.with_lock_timeout(std::time::Duration::from_millis(300));
+
+        let lock = Lock::acquire(&repo, "test").unwrap();
+        let holder = Lock::holder(&repo, "test").unwrap().unwrap();
+        assert_eq!(holder.pid, std::process::id());
+        assert!(!holder.is_stale());
+        match Lock::acquire(&repo, "test") {
+            Err(LockError::Busy(busy_holder)) => assert_eq!(busy_holder, holder),
+            other => panic!("expected a busy lock, got {other:?}"),
+        }
+
+        drop(lock);
+        assert_eq!(Lock::holder(&repo, "test").unwrap(), None);
+        let _lock = Lock::acquire(&repo, "test").unwrap();
+    }
+
+    #[test]
+    fn stale_lock() {
+        let (_dir, repo) = bare_repo();
+        let repo = repo.with_lock_timeout(std::time::Duration::ZERO);
+
+        // A lock left behind by a process that's gone.
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        let stale = LockInfo {
+            pid: dead_pid,
+            host: hostname(),
+            time: String::from("2025-07-24T10:08:38-06:00"),
+        };
+        assert!(stale.is_stale());
+        std::fs::write(
+            lock_path(&repo, "test").unwrap(),
+            toml::to_string(&stale).unwrap(),
+        )
+        .unwrap();
+
+        let _lock = Lock::acquire(&repo, "test").unwrap();
+        let holder = Lock::holder(&repo, "test").unwrap().unwrap();
+        assert_eq!(holder.pid, std::process::id());
+
+        // Locks from other hosts are never stale.
+        let remote = LockInfo {
+            host: String::from("some-other-host.invalid"),
+            ..stale
+        };
+        assert!(!remote.is_stale());
+    }
+}
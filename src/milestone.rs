@@ -0,0 +1,233 @@
+//! Milestones group issues towards a release.
+//!
+//! Milestones live in the `milestones` directory at the top of the
+//! issues database, one directory per milestone, named after the
+//! milestone:
+//!
+//! ```text
+//! milestones/v1.2/description
+//! milestones/v1.2/target_date    (YYYY-MM-DD, optional)
+//! milestones/v1.2/state          ("open" or "closed")
+//! ```
+//!
+//! An issue belongs to a milestone if its `milestone` file names it.
+
+use std::io::Write;
+
+#[cfg(feature = "log")]
+use log::debug;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MilestoneError {
+    #[error(transparent)]
+    StdIoError(#[from] std::io::Error),
+    #[error(transparent)]
+    GitError(#[from] crate::git::GitError),
+    #[error("invalid target date {0:?}, expected YYYY-MM-DD")]
+    InvalidTargetDate(String),
+    #[error("invalid milestone state {0:?}, expected open or closed")]
+    InvalidState(String),
+    #[error("invalid milestone name {0:?}")]
+    InvalidName(String),
+    #[error("milestone {0} already exists")]
+    Exists(String),
+}
+
+#[derive(Debug, ignorable::PartialEq)]
+pub struct Milestone {
+    pub name: String,
+    pub description: String,
+    pub target_date: Option<chrono::NaiveDate>,
+    pub closed: bool,
+
+    #[ignored(PartialEq)]
+    /// This is the directory that the milestone lives in.  Only used
+    /// internally by the entomologist library.
+    pub dir: std::path::PathBuf,
+}
+
+/// Milestone names are used as filenames, so they're restricted to
+/// letters, digits, '-', '_' and '.'.
+pub fn validate_name(name: &str) -> Result<(), MilestoneError> {
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(MilestoneError::InvalidName(String::from(name)));
+    }
+    Ok(())
+}
+
+impl Milestone {
+    pub fn new_from_dir(dir: &std::path::Path) -> Result<Self, MilestoneError> {
+        let mut description = String::new();
+        let mut target_date: Option<chrono::NaiveDate> = None;
+        let mut closed = false;
+
+        for direntry in (dir.read_dir()?).flatten() {
+            let file_name = direntry.file_name();
+            if file_name == "description" {
+                description = std::fs::read_to_string(direntry.path())?;
+            } else if file_name == "target_date" {
+                let raw_target_date = std::fs::read_to_string(direntry.path())?;
+                target_date = Some(parse_target_date(raw_target_date.trim())?);
+            } else if file_name == "state" {
+                closed = match std::fs::read_to_string(direntry.path())?.trim() {
+                    "open" => false,
+                    "closed" => true,
+                    state => return Err(MilestoneError::InvalidState(String::from(state))),
+                };
+            } else {
+                #[cfg(feature = "log")]
+                debug!(
+                    "ignoring unknown file in milestone directory: {:?}",
+                    file_name
+                );
+            }
+        }
+
+        let Some(name) = dir.file_name() else {
+            return Err(MilestoneError::InvalidName(
+                dir.to_string_lossy().into_owned(),
+            ));
+        };
+        Ok(Self {
+            name: name.to_string_lossy().into_owned(),
+            description,
+            target_date,
+            closed,
+            dir: std::path::PathBuf::from(dir),
+        })
+    }
+
+    /// Create a new open Milestone in the issues database in `db_dir`.
+    /// Commits.
+    pub fn new(
+        db_dir: &std::path::Path,
+        name: &str,
+        description: &str,
+        target_date: Option<chrono::NaiveDate>,
+    ) -> Result<Self, MilestoneError> {
+        validate_name(name)?;
+        let mut dir = std::path::PathBuf::from(db_dir);
+        dir.push("milestones");
+        std::fs::create_dir_all(&dir)?;
+        dir.push(name);
+        if dir.exists() {
+            return Err(MilestoneError::Exists(String::from(name)));
+        }
+        std::fs::create_dir(&dir)?;
+
+        let milestone = Self {
+            name: String::from(name),
+            description: String::from(description),
+            target_date,
+            closed: false,
+            dir,
+        };
+
+        let mut description_file = std::fs::File::create(milestone.filename("description"))?;
+        write!(description_file, "{description}")?;
+        if let Some(target_date) = &milestone.target_date {
+            let mut target_date_file = std::fs::File::create(milestone.filename("target_date"))?;
+            write!(target_date_file, "{}", target_date.format("%Y-%m-%d"))?;
+        }
+        milestone.write_state()?;
+        milestone.commit(&format!("add milestone {name}"))?;
+        Ok(milestone)
+    }
+
+    /// Close or reopen the Milestone.  Commits.
+    pub fn set_closed(&mut self, closed: bool) -> Result<(), MilestoneError> {
+        if closed == self.closed {
+            return Ok(());
+        }
+        self.closed = closed;
+        self.write_state()?;
+        self.commit(&format!(
+            "{} milestone {}",
+            if closed { "close" } else { "reopen" },
+            self.name
+        ))?;
+        Ok(())
+    }
+
+    /// An open Milestone is overdue once its target date has passed.
+    pub fn is_overdue(&self, today: chrono::NaiveDate) -> bool {
+        match self.target_date {
+            Some(target_date) => !self.closed && target_date < today,
+            None => false,
+        }
+    }
+
+    /// The first line of the description.
+    pub fn title(&self) -> &str {
+        self.description.lines().next().unwrap_or_default()
+    }
+}
+
+// This is the private, internal API.
+impl Milestone {
+    fn filename(&self, name: &str) -> std::path::PathBuf {
+        let mut filename = self.dir.clone();
+        filename.push(name);
+        filename
+    }
+
+    fn write_state(&self) -> Result<(), MilestoneError> {
+        let mut state_file = std::fs::File::create(self.filename("state"))?;
+        write!(
+            state_file,
+            "{}",
+            if self.closed { "closed" } else { "open" }
+        )?;
+        Ok(())
+    }
+
+    fn commit(&self, commit_message: &str) -> Result<(), MilestoneError> {
+        crate::git::add(&self.dir)?;
+        crate::git::commit(&self.dir, commit_message)?;
+        Ok(())
+    }
+}
+
+/// Parse a target date in the form YYYY-MM-DD.
+pub fn parse_target_date(s: &str) -> Result<chrono::NaiveDate, MilestoneError> {
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map_err(|_| MilestoneError::InvalidTargetDate(String::from(s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn milestone_names() {
+        assert!(validate_name("v1.2").is_ok());
+        assert!(validate_name("rev-c_bringup").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("..").is_err());
+        assert!(validate_name("v1/rc1").is_err());
+    }
+
+    #[test]
+    fn overdue() {
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 7, 24).unwrap();
+        let mut milestone = Milestone {
+            name: String::from("v1.2"),
+            description: String::from("First production run\n"),
+            target_date: chrono::NaiveDate::from_ymd_opt(2025, 7, 1),
+            closed: false,
+            dir: std::path::PathBuf::from("ignored"),
+        };
+        assert!(milestone.is_overdue(today));
+        milestone.closed = true;
+        assert!(!milestone.is_overdue(today));
+        milestone.closed = false;
+        milestone.target_date = None;
+        assert!(!milestone.is_overdue(today));
+    }
+}
@@ -0,0 +1,266 @@
+//! Views across the issue databases of several repositories.
+//!
+//! The repositories are listed in a workspace file, for example:
+//!
+//! ```toml
+//! [[repos]]
+//! name = "firmware"
+//! path = "../firmware"
+//!
+//! [[repos]]
+//! name = "pcb"
+//! path = "/home/seb/src/pcb"
+//! branch = "entomologist-data"
+//! ```
+//!
+//! Relative paths are relative to the directory containing the
+//! workspace file.  `branch` is optional and defaults to
+//! `entomologist-data`.
+//!
+//! Issues are referred to as `REPO:ID` across the workspace.  An issue
+//! can depend on an issue in another repository of the workspace by
+//! naming it this way.  Only workspace views resolve these
+//! dependencies, single-repository views ignore them.
+
+use crate::issue::State;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MultiError {
+    #[error(transparent)]
+    StdIoError(#[from] std::io::Error),
+    #[error(transparent)]
+    TomlDeserializeError(#[from] toml::de::Error),
+    #[error("invalid repo name {0:?}")]
+    InvalidRepoName(String),
+    #[error("repo {0} is listed more than once in the workspace")]
+    DuplicateRepo(String),
+    #[error("failed to load repo {name} ({path:?}): {source}")]
+    RepoError {
+        name: String,
+        path: std::path::PathBuf,
+        source: Box<crate::issues::Error>,
+    },
+}
+
+/// One repository in a Workspace.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct WorkspaceRepo {
+    pub name: String,
+    pub path: std::path::PathBuf,
+    #[serde(default = "default_branch")]
+    pub branch: String,
+}
+
+fn default_branch() -> String {
+    String::from("entomologist-data")
+}
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub struct Workspace {
+    #[serde(default)]
+    pub repos: Vec<WorkspaceRepo>,
+}
+
+/// Repo names are used in issue references (`REPO:ID`), so they're
+/// restricted to letters, digits, '-', '_' and '.'.
+pub fn validate_repo_name(name: &str) -> Result<(), MultiError> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(MultiError::InvalidRepoName(String::from(name)));
+    }
+    Ok(())
+}
+
+/// Split an issue reference into the repo name (if any) and the issue
+/// id: `firmware:abc123` is `(Some("firmware"), "abc123")`, and
+/// `abc123` is `(None, "abc123")`.
+pub fn split_issue_ref(issue_ref: &str) -> (Option<&str>, &str) {
+    match issue_ref.split_once(':') {
+        Some((repo_name, issue_id)) => (Some(repo_name), issue_id),
+        None => (None, issue_ref),
+    }
+}
+
+impl Workspace {
+    pub fn new_from_str(s: &str, base_dir: &std::path::Path) -> Result<Self, MultiError> {
+        let mut workspace: Workspace = toml::from_str(s)?;
+        let mut names = std::collections::HashSet::<&str>::new();
+        for repo in &mut workspace.repos {
+            validate_repo_name(&repo.name)?;
+            if repo.path.is_relative() {
+                repo.path = base_dir.join(&repo.path);
+            }
+        }
+        for repo in &workspace.repos {
+            if !names.insert(&repo.name) {
+                return Err(MultiError::DuplicateRepo(repo.name.clone()));
+            }
+        }
+        Ok(workspace)
+    }
+
+    pub fn new_from_file(file: &std::path::Path) -> Result<Self, MultiError> {
+        let base_dir = match file.parent() {
+            Some(dir) => dir,
+            None => std::path::Path::new("."),
+        };
+        Self::new_from_str(&std::fs::read_to_string(file)?, base_dir)
+    }
+}
+
+/// The Issues of all the repositories in a Workspace, in workspace
+/// order.
+#[derive(Debug)]
+pub struct MultiIssues {
+    pub repos: Vec<(String, crate::Issues)>,
+}
+
+impl MultiIssues {
+    pub fn new_from_workspace(workspace: &Workspace) -> Result<Self, MultiError> {
+        let mut repos = Vec::<(String, crate::Issues)>::new();
+        for workspace_repo in &workspace.repos {
+            let repo = crate::Repo::new(&workspace_repo.path);
+            let issues =
+                crate::Issues::new_from_git(&repo, &workspace_repo.branch).map_err(|source| {
+                    MultiError::RepoError {
+                        name: workspace_repo.name.clone(),
+                        path: workspace_repo.path.clone(),
+                        source: Box::new(source),
+                    }
+                })?;
+            repos.push((workspace_repo.name.clone(), issues));
+        }
+        Ok(Self { repos })
+    }
+
+    pub fn get_repo(&self, repo_name: &str) -> Option<&crate::Issues> {
+        self.repos
+            .iter()
+            .find(|(name, _)| name == repo_name)
+            .map(|(_, issues)| issues)
+    }
+
+    /// Look up an Issue by reference.  References without a repo name
+    /// are looked up in `repo_name`.
+    pub fn get_issue(&self, repo_name: &str, issue_ref: &str) -> Option<&crate::Issue> {
+        let (ref_repo_name, issue_id) = split_issue_ref(issue_ref);
+        self.get_repo(ref_repo_name.unwrap_or(repo_name))?
+            .get_issue(issue_id)
+    }
+
+    /// Iterate over all the Issues, as (repo name, Issues of the repo,
+    /// Issue).
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &crate::Issues, &crate::Issue)> {
+        self.repos.iter().flat_map(|(repo_name, issues)| {
+            issues
+                .iter()
+                .map(move |(_, issue)| (repo_name.as_str(), issues, issue))
+        })
+    }
+
+    /// Return the dependencies of an Issue in `repo_name` that are
+    /// still open, as `REPO:ID` references.  Dependencies in
+    /// repositories outside the workspace are left out.
+    pub fn blocking_dependencies(&self, repo_name: &str, issue: &crate::Issue) -> Vec<String> {
+        let Some(dependencies) = &issue.dependencies else {
+            return Vec::new();
+        };
+        dependencies
+            .iter()
+            .filter_map(|dep_ref| {
+                let (dep_repo_name, dep_id) = split_issue_ref(dep_ref);
+                let dep_repo_name = dep_repo_name.unwrap_or(repo_name);
+                let dep_issues = self.get_repo(dep_repo_name)?;
+                let dep = dep_issues.get_issue(dep_id)?;
+                if dep_issues.config().workflow.is_closed(&dep.state) {
+                    return None;
+                }
+                Some(format!("{dep_repo_name}:{dep_id}"))
+            })
+            .collect()
+    }
+
+    /// Return the state an Issue should be shown as, like
+    /// `Issues::effective_state()` but taking dependencies in other
+    /// repositories into account.
+    pub fn effective_state(&self, repo_name: &str, issue: &crate::Issue) -> State {
+        let Some(issues) = self.get_repo(repo_name) else {
+            return issue.state.clone();
+        };
+        let config = issues.config();
+        if !config.auto_blocked || config.workflow.is_closed(&issue.state) {
+            return issue.state.clone();
+        }
+        if !self.blocking_dependencies(repo_name, issue).is_empty() {
+            return State::Blocked;
+        }
+        match &issue.pre_blocked_state {
+            Some(pre_blocked_state) => pre_blocked_state.clone(),
+            None => issue.state.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn read_workspace() {
+        let workspace = Workspace::new_from_str(
+            r#"
+            [[repos]]
+            name = "firmware"
+            path = "../firmware"
+
+            [[repos]]
+            name = "pcb"
+            path = "/src/pcb"
+            branch = "issues"
+            "#,
+            std::path::Path::new("/home/seb/work"),
+        )
+        .unwrap();
+        assert_eq!(
+            workspace.repos,
+            vec![
+                WorkspaceRepo {
+                    name: String::from("firmware"),
+                    path: std::path::PathBuf::from("/home/seb/work/../firmware"),
+                    branch: String::from("entomologist-data"),
+                },
+                WorkspaceRepo {
+                    name: String::from("pcb"),
+                    path: std::path::PathBuf::from("/src/pcb"),
+                    branch: String::from("issues"),
+                },
+            ]
+        );
+
+        let duplicate = r#"
+            [[repos]]
+            name = "pcb"
+            path = "a"
+            [[repos]]
+            name = "pcb"
+            path = "b"
+            "#;
+        assert!(Workspace::new_from_str(duplicate, std::path::Path::new(".")).is_err());
+        let bad_name = r#"
+            [[repos]]
+            name = "my:repo"
+            path = "a"
+            "#;
+        assert!(Workspace::new_from_str(bad_name, std::path::Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn issue_refs() {
+        assert_eq!(split_issue_ref("pcb:abc123"), (Some("pcb"), "abc123"));
+        assert_eq!(split_issue_ref("abc123"), (None, "abc123"));
+    }
+}
@@ -0,0 +1,192 @@
+//! Proposals are changes to the issues made on a side branch, for
+//! someone with write access to review and merge into the issues
+//! branch, like a pull request.
+//!
+//! The proposal NAME to the issues branch BRANCH is the branch
+//! `BRANCH-proposals/NAME`, made from BRANCH.  A contributor who can't
+//! push to BRANCH makes their changes there and pushes the proposal
+//! branch somewhere the maintainers can fetch it from (their fork, for
+//! example).  Once fetched, it shows up in `list()` as a remote branch,
+//! and `accept()` merges it.
+//!
+//! Proposals on a remote that calls the issues branch something else
+//! (see `crate::git::remote_branch_name()`) are named after the
+//! remote's name for it.
+
+use crate::git::GitError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProposalError {
+    #[error(transparent)]
+    StdIoError(#[from] std::io::Error),
+    #[error(transparent)]
+    GitError(#[from] GitError),
+    #[error(transparent)]
+    GitDbError(#[from] crate::gitdb::Error),
+    #[error("invalid proposal name {0:?}")]
+    InvalidName(String),
+    #[error("proposal {0:?} not found")]
+    NotFound(String),
+    #[error("{proposal:?} is not a proposal to {branch:?}, they have no history in common")]
+    Unrelated { proposal: String, branch: String },
+    #[error(
+        "Failed to merge proposal {proposal:?} into {branch:?}, update the proposal from {branch:?} and try again:\n{error}"
+    )]
+    MergeError {
+        proposal: String,
+        branch: String,
+        error: Box<crate::git::CommandError>,
+    },
+}
+
+/// A proposal that's not accepted yet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Proposal {
+    /// The name of the proposal, without the `BRANCH-proposals/`
+    /// prefix.
+    pub name: String,
+    /// The proposal branch, as `accept()` takes it: the local branch
+    /// name, or REMOTE/BRANCH for remote branches.
+    pub branch: String,
+    /// The remote the proposal branch is from, None for local ones.
+    pub remote: Option<String>,
+    /// What accepting the proposal would change.
+    pub changes: crate::changes::Changes,
+}
+
+/// The name of the branch of the proposal `name` to `branch`.
+pub fn branch_name(branch: &str, name: &str) -> String {
+    format!("{branch}-proposals/{name}")
+}
+
+/// Make the proposal `name` to `branch`, starting from the tip of
+/// `branch`, and return the name of its branch.  If the proposal
+/// exists already it's left as it is.
+pub fn create(repo: &crate::Repo, branch: &str, name: &str) -> Result<String, ProposalError> {
+    let proposal_branch = branch_name(branch, name);
+    let refname = format!("refs/heads/{proposal_branch}");
+    let valid = repo
+        .git()
+        .args(["check-ref-format", &refname])
+        .output()?
+        .status
+        .success();
+    if name.is_empty() || !valid {
+        return Err(ProposalError::InvalidName(String::from(name)));
+    }
+    if crate::git::git_branch_exists(repo, &refname)? {
+        return Ok(proposal_branch);
+    }
+    crate::gitdb::ensure_branch_exists(repo, branch)?;
+    crate::git::run(repo.git().args(["branch", &proposal_branch, branch]))?;
+    Ok(proposal_branch)
+}
+
+/// All the local and remote (as of the last fetch) proposals to
+/// `branch` that aren't merged into it yet.
+pub fn list(repo: &crate::Repo, branch: &str) -> Result<Vec<Proposal>, ProposalError> {
+    let Some(branch_tip) = crate::git::resolve_ref(repo, &format!("refs/heads/{branch}"))? else {
+        return Ok(Vec::new());
+    };
+
+    let mut prefixes = vec![(None, format!("refs/heads/{branch}-proposals/"))];
+    for remote in crate::git::remotes(repo)? {
+        let remote_branch_name = crate::git::remote_branch_name(repo, &remote, branch)?;
+        prefixes.push((
+            Some(remote.clone()),
+            format!("refs/remotes/{remote}/{remote_branch_name}-proposals/"),
+        ));
+    }
+
+    let mut proposals = Vec::<Proposal>::new();
+    for (remote, prefix) in prefixes {
+        let refs = crate::git::run_stdout(repo.git().args([
+            "for-each-ref",
+            "--format=%(refname) %(objectname)",
+            &prefix,
+        ]))?;
+        for line in refs.lines() {
+            let Some((refname, tip)) = line.split_once(' ') else {
+                return Err(GitError::UnexpectedOutput {
+                    command: String::from("git for-each-ref"),
+                    output: refs,
+                }
+                .into());
+            };
+            let Some(base) = merge_base(repo, &branch_tip, tip)? else {
+                continue;
+            };
+            if base == tip {
+                // Accepted already.
+                continue;
+            }
+            let (Some(name), Some(short_name)) = (
+                refname.strip_prefix(&prefix),
+                refname
+                    .strip_prefix("refs/heads/")
+                    .or(refname.strip_prefix("refs/remotes/")),
+            ) else {
+                continue;
+            };
+            proposals.push(Proposal {
+                name: String::from(name),
+                branch: String::from(short_name),
+                remote: remote.clone(),
+                changes: crate::changes::Changes::between(repo, Some(&base), tip)?,
+            });
+        }
+    }
+    Ok(proposals)
+}
+
+/// Merge a proposal into `branch`, and return the changes it made.
+/// `proposal` is the proposal branch as listed by `list()`, or the name
+/// of a local proposal.
+pub fn accept(
+    repo: &crate::Repo,
+    branch: &str,
+    proposal: &str,
+) -> Result<crate::changes::Changes, ProposalError> {
+    let local_proposal = format!("refs/heads/{}", branch_name(branch, proposal));
+    let tip = match crate::git::resolve_ref(repo, &local_proposal)? {
+        Some(tip) => tip,
+        None => crate::git::resolve_ref(repo, proposal)?
+            .ok_or_else(|| ProposalError::NotFound(String::from(proposal)))?,
+    };
+
+    let gitdb = crate::gitdb::GitDbMut::get(repo, branch)?;
+    let branch_tip = crate::git::run_stdout(repo.git().args([
+        "rev-parse",
+        "--verify",
+        &format!("refs/heads/{branch}"),
+    ]))?;
+    let Some(base) = merge_base(repo, &branch_tip, &tip)? else {
+        return Err(ProposalError::Unrelated {
+            proposal: String::from(proposal),
+            branch: String::from(branch),
+        });
+    };
+    let changes = crate::changes::Changes::between(repo, Some(&base), &tip)?;
+    if base == tip {
+        return Ok(changes);
+    }
+    match crate::git::merge(
+        &gitdb.path(),
+        &tip,
+        &format!("Accept proposal {proposal} into {branch}"),
+    ) {
+        Err(GitError::Command(error)) => Err(ProposalError::MergeError {
+            proposal: String::from(proposal),
+            branch: String::from(branch),
+            error,
+        }),
+        Err(e) => Err(e.into()),
+        Ok(()) => Ok(changes),
+    }
+}
+
+/// The best common ancestor of two commits, None if they have no
+/// history in common.
+fn merge_base(repo: &crate::Repo, a: &str, b: &str) -> Result<Option<String>, GitError> {
+    crate::git::resolve(repo.git().args(["merge-base", a, b]))
+}
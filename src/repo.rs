@@ -0,0 +1,68 @@
+//! `Repo` is a handle on the git repository that holds the issues
+//! database.  Every git command the library runs on behalf of a Repo
+//! runs in the Repo's directory, not in the process's current
+//! directory, so one process can work with several repositories.
+
+#[derive(Clone, Debug)]
+pub struct Repo {
+    path: std::path::PathBuf,
+    lock_timeout: std::time::Duration,
+    on_busy: Option<fn(&crate::lock::LockInfo)>,
+}
+
+impl Default for Repo {
+    fn default() -> Self {
+        Self::current()
+    }
+}
+
+impl Repo {
+    /// The repository at `path`.  `path` can be the top of the
+    /// repository's worktree, any directory below it, or a bare
+    /// repository.
+    pub fn new<P: Into<std::path::PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            lock_timeout: std::time::Duration::from_secs(30),
+            on_busy: None,
+        }
+    }
+
+    /// How long to wait for another `ent` process to release the issue
+    /// database before giving up.  Defaults to 30 seconds.
+    pub fn with_lock_timeout(mut self, lock_timeout: std::time::Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /// A function to call (once) when the issue database is locked by
+    /// another process and we start waiting for it.
+    pub fn with_busy_callback(mut self, on_busy: fn(&crate::lock::LockInfo)) -> Self {
+        self.on_busy = Some(on_busy);
+        self
+    }
+
+    /// The repository containing the process's current directory.
+    pub fn current() -> Self {
+        Self::new(".")
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    pub fn lock_timeout(&self) -> std::time::Duration {
+        self.lock_timeout
+    }
+
+    pub fn on_busy(&self) -> Option<fn(&crate::lock::LockInfo)> {
+        self.on_busy
+    }
+
+    /// A `git` command that runs in the repository.
+    pub fn git(&self) -> std::process::Command {
+        let mut command = std::process::Command::new("git");
+        command.current_dir(&self.path);
+        command
+    }
+}
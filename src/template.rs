@@ -0,0 +1,75 @@
+//! Templates for new issues.
+//!
+//! Templates live in the `templates` directory at the top of the
+//! issues database, one `NAME.toml` file per template, for example
+//! `templates/bug.toml`:
+//!
+//! ```toml
+//! description = """
+//! Short summary of the bug
+//!
+//! Steps to reproduce:
+//!
+//! Expected behavior:
+//!
+//! Actual behavior:
+//! """
+//! tags = ["bug"]
+//! state = "Backlog"
+//!
+//! [fields]
+//! component = "firmware"
+//! ```
+//!
+//! All the settings are optional.
+//!
+//! Templates are written by hand, set `format = 2` in `config.toml`
+//! when adding the `templates` directory (see
+//! `crate::issues::FORMAT_VERSION`).
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub struct Template {
+    /// Initial contents of the description, for the user to fill in.
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub state: Option<String>,
+    pub assignee: Option<String>,
+    /// Custom fields (see `crate::field`) by name, as strings.
+    #[serde(default)]
+    pub fields: std::collections::BTreeMap<String, String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn parse_template() {
+        let template: Template = toml::from_str(
+            r#"
+            description = "Hardware defect\n\nBoard revision:\n"
+            tags = ["hardware", "defect"]
+            state = "Backlog"
+
+            [fields]
+            component = "power"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            template,
+            Template {
+                description: Some(String::from("Hardware defect\n\nBoard revision:\n")),
+                tags: vec![String::from("hardware"), String::from("defect")],
+                state: Some(String::from("Backlog")),
+                assignee: None,
+                fields: std::collections::BTreeMap::from([(
+                    String::from("component"),
+                    String::from("power")
+                )]),
+            }
+        );
+    }
+}
@@ -0,0 +1,270 @@
+//! The workflow of an issues database: the states issues can be in,
+//! the order `ent list` shows them in, which states count as closed,
+//! and which state transitions are allowed.
+//!
+//! The workflow is configured in the `[workflow]` table of the
+//! database's `config.toml`, for example:
+//!
+//! ```toml
+//! [workflow]
+//! states = ["InProgress", "InReview", "NeedsParts", "Blocked", "Backlog", "New", "Done", "WontDo"]
+//! closed = ["Done", "WontDo"]
+//!
+//! [workflow.transitions]
+//! New = ["Backlog", "InProgress", "WontDo"]
+//! InProgress = ["InReview", "NeedsParts", "Blocked", "Backlog"]
+//! InReview = ["InProgress", "Done"]
+//! ```
+//!
+//! If `states` is omitted the built-in states are used.  If `closed`
+//! is omitted, Done and WontDo are closed.  States that have no entry
+//! in `transitions` may move to any state.  New issues start in the
+//! New state, so it must be one of the states, and open.
+
+use crate::issue::State;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WorkflowError {
+    #[error(transparent)]
+    IssueError(#[from] crate::issue::IssueError),
+    #[error("state {0:?} is not one of the workflow states")]
+    UnknownState(String),
+    #[error("the New state must be one of the workflow states, and open")]
+    NoNewState,
+}
+
+/// The `[workflow]` table as written in `config.toml`.
+#[derive(Debug, Default, serde::Deserialize)]
+struct WorkflowConfig {
+    #[serde(default)]
+    states: Vec<String>,
+    #[serde(default)]
+    closed: Option<Vec<String>>,
+    #[serde(default)]
+    transitions: Option<std::collections::HashMap<String, Vec<String>>>,
+}
+
+#[derive(Debug, PartialEq, serde::Deserialize)]
+#[serde(try_from = "WorkflowConfig")]
+pub struct Workflow {
+    /// The states of the workflow in display order, with the name
+    /// used for each in `config.toml`.
+    states: Vec<(State, String)>,
+    closed: std::collections::HashSet<State>,
+    /// None means any transition is allowed.
+    transitions: Option<std::collections::HashMap<State, std::collections::HashSet<State>>>,
+}
+
+impl Default for Workflow {
+    fn default() -> Self {
+        Self {
+            states: [
+                State::InProgress,
+                State::Blocked,
+                State::Backlog,
+                State::New,
+                State::Done,
+                State::WontDo,
+            ]
+            .into_iter()
+            .map(|state| {
+                let name = format!("{state:?}");
+                (state, name)
+            })
+            .collect(),
+            closed: std::collections::HashSet::from([State::Done, State::WontDo]),
+            transitions: None,
+        }
+    }
+}
+
+impl TryFrom<WorkflowConfig> for Workflow {
+    type Error = WorkflowError;
+    fn try_from(config: WorkflowConfig) -> Result<Self, Self::Error> {
+        let mut workflow = Workflow::default();
+
+        if !config.states.is_empty() {
+            workflow.states.clear();
+            for name in config.states {
+                let state = State::from_name(&name)?;
+                workflow.states.push((state, name));
+            }
+            workflow.closed.retain(|state| {
+                workflow
+                    .states
+                    .iter()
+                    .any(|(workflow_state, _)| workflow_state == state)
+            });
+        }
+
+        if let Some(closed) = config.closed {
+            workflow.closed.clear();
+            for name in closed {
+                let state = workflow.parse_state(&name)?;
+                workflow.closed.insert(state);
+            }
+        }
+
+        if let Some(transitions) = config.transitions {
+            let mut allowed = std::collections::HashMap::new();
+            for (from, tos) in transitions {
+                let from = workflow.parse_state(&from)?;
+                let mut to_states = std::collections::HashSet::new();
+                for to in tos {
+                    to_states.insert(workflow.parse_state(&to)?);
+                }
+                allowed.insert(from, to_states);
+            }
+            workflow.transitions = Some(allowed);
+        }
+
+        // New issues are created in the New state.
+        if !workflow.has_state(&State::New) || workflow.is_closed(&State::New) {
+            return Err(WorkflowError::NoNewState);
+        }
+
+        Ok(workflow)
+    }
+}
+
+impl Workflow {
+    /// Parse a state name, and check that it's one of the states in
+    /// this workflow.
+    pub fn parse_state(&self, name: &str) -> Result<State, WorkflowError> {
+        let state = State::from_name(name)?;
+        if !self.has_state(&state) {
+            return Err(WorkflowError::UnknownState(String::from(name)));
+        }
+        Ok(state)
+    }
+
+    /// Iterate over the states of the workflow, in display order.
+    pub fn states(&self) -> impl Iterator<Item = &State> {
+        self.states.iter().map(|(state, _)| state)
+    }
+
+    pub fn has_state(&self, state: &State) -> bool {
+        self.states().any(|s| s == state)
+    }
+
+    /// The name of a state, as written in the workflow configuration.
+    pub fn label(&self, state: &State) -> String {
+        match self.states.iter().find(|(s, _)| s == state) {
+            Some((_, name)) => name.clone(),
+            None => match state {
+                State::Custom(name) => name.clone(),
+                _ => format!("{state:?}"),
+            },
+        }
+    }
+
+    /// Closed states are resolved: dependencies in a closed state no
+    /// longer block, and moving an issue to a closed state sets its
+    /// `done_time`.
+    pub fn is_closed(&self, state: &State) -> bool {
+        self.closed.contains(state)
+    }
+
+    /// The states of the workflow that are not closed.
+    pub fn open_states(&self) -> std::collections::HashSet<State> {
+        self.states()
+            .filter(|state| !self.is_closed(state))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns true if an issue may move from state `from` to state `to`.
+    pub fn allows_transition(&self, from: &State, to: &State) -> bool {
+        if from == to {
+            return true;
+        }
+        match &self.transitions {
+            None => true,
+            Some(transitions) => match transitions.get(from) {
+                None => true,
+                Some(to_states) => to_states.contains(to),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn default_workflow() {
+        let workflow = Workflow::default();
+        assert!(workflow.has_state(&State::Blocked));
+        assert!(workflow.is_closed(&State::WontDo));
+        assert!(!workflow.is_closed(&State::InProgress));
+        assert!(workflow.allows_transition(&State::New, &State::Done));
+        assert_eq!(workflow.label(&State::InProgress), "InProgress");
+    }
+
+    #[test]
+    fn custom_workflow() {
+        let workflow: Workflow = toml::from_str(
+            r#"
+            states = ["New", "InProgress", "InReview", "NeedsParts", "Done"]
+            closed = ["Done"]
+
+            [transitions]
+            New = ["InProgress"]
+            InProgress = ["InReview", "NeedsParts"]
+            "#,
+        )
+        .unwrap();
+
+        let in_review = workflow.parse_state("inreview").unwrap();
+        assert_eq!(workflow.label(&in_review), "InReview");
+        assert_eq!(
+            workflow.states().cloned().collect::<Vec<State>>(),
+            vec![
+                State::New,
+                State::InProgress,
+                in_review.clone(),
+                workflow.parse_state("NeedsParts").unwrap(),
+                State::Done,
+            ]
+        );
+        assert!(!workflow.has_state(&State::WontDo));
+        assert!(workflow.parse_state("backlog").is_err());
+        assert_eq!(State::parse("inreview", &workflow).unwrap(), in_review);
+        assert!(State::parse("inreveiw", &workflow).is_err());
+        assert!(State::parse("inreview", &Workflow::default()).is_err());
+
+        assert!(workflow.allows_transition(&State::New, &State::InProgress));
+        assert!(!workflow.allows_transition(&State::New, &State::Done));
+        assert!(workflow.allows_transition(&State::InProgress, &in_review));
+        // InReview has no transitions listed, so it can go anywhere.
+        assert!(workflow.allows_transition(&in_review, &State::Done));
+    }
+
+    #[test]
+    fn invalid_workflow() {
+        let r: Result<Workflow, _> = toml::from_str(
+            r#"
+            states = ["New", "Done"]
+            closed = ["Finished"]
+            "#,
+        );
+        assert!(r.is_err());
+
+        // Without New, new issues would start outside the workflow.
+        let r: Result<Workflow, _> = toml::from_str(
+            r#"
+            states = ["Todo", "Doing", "Done"]
+            "#,
+        );
+        assert!(r.is_err());
+        let r: Result<Workflow, _> = toml::from_str(
+            r#"
+            states = ["New", "Done"]
+            closed = ["New", "Done"]
+            "#,
+        );
+        assert!(r.is_err());
+    }
+}
@@ -0,0 +1,366 @@
+//! Time tracking: estimates, worklogs and timers.
+//!
+//! The estimate of an issue is stored in the file `estimate` in the
+//! issue's directory, as a duration like `4h` or `1h30m`.
+//!
+//! Each worklog entry is a small TOML file `worklog/UUID` in the issue's
+//! directory, so entries logged concurrently by different people merge
+//! cleanly:
+//!
+//! ```toml
+//! user = "Sebastian Kuzminsky <seb@highlab.com>"
+//! start = "2025-07-24T10:08:38-06:00"
+//! duration = "1h30m"
+//! note = "scoped the motor driver"
+//! ```
+//!
+//! Running timers are not shared, they live in the `entomologist-timers`
+//! directory of the user's git dir, one file per issue holding the
+//! time the timer was started.
+
+use std::str::FromStr;
+
+#[derive(Debug, thiserror::Error)]
+pub enum WorklogError {
+    #[error(transparent)]
+    StdIoError(#[from] std::io::Error),
+    #[error(transparent)]
+    GitError(#[from] crate::git::GitError),
+    #[error(transparent)]
+    ChronoParseError(#[from] chrono::format::ParseError),
+    #[error(transparent)]
+    TomlDeserializeError(#[from] toml::de::Error),
+    #[error(transparent)]
+    TomlSerializeError(#[from] toml::ser::Error),
+    #[error("invalid duration {0:?}, expected something like 2h, 45m or 1h30m")]
+    InvalidDuration(String),
+    #[error("invalid time {0:?}, expected today, yesterday, last-week, last-month or YYYY-MM-DD")]
+    InvalidSince(String),
+    #[error("invalid grouping {0:?}, expected assignee, user, issue or tag")]
+    InvalidGroupBy(String),
+    #[error("a timer is already running for issue {0}")]
+    TimerRunning(String),
+    #[error("no timer is running for issue {0}")]
+    TimerNotRunning(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Time a user spent working on an Issue.
+pub struct WorklogEntry {
+    pub uuid: String,
+    pub user: String,
+    pub start: chrono::DateTime<chrono::Local>,
+    pub duration: chrono::TimeDelta,
+    pub note: Option<String>,
+}
+
+/// The on-disk representation of a WorklogEntry.
+#[derive(serde::Deserialize, serde::Serialize)]
+struct WorklogFile {
+    user: String,
+    start: String,
+    duration: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+}
+
+impl WorklogEntry {
+    pub(crate) fn new_from_file(file: &std::path::Path) -> Result<Self, WorklogError> {
+        let worklog_file: WorklogFile = toml::from_str(&std::fs::read_to_string(file)?)?;
+        let start = chrono::DateTime::parse_from_rfc3339(&worklog_file.start)?;
+        Ok(Self {
+            uuid: match file.file_name() {
+                Some(file_name) => file_name.to_string_lossy().into_owned(),
+                None => String::new(),
+            },
+            user: worklog_file.user,
+            start: start.into(),
+            duration: parse_duration(&worklog_file.duration)?,
+            note: worklog_file.note,
+        })
+    }
+
+    /// Write the entry to the `worklog` directory of an Issue, without
+    /// committing.
+    pub(crate) fn write(&self, issue_dir: &std::path::Path) -> Result<(), WorklogError> {
+        let mut filename = std::path::PathBuf::from(issue_dir);
+        filename.push("worklog");
+        std::fs::create_dir_all(&filename)?;
+        filename.push(&self.uuid);
+        let worklog_file = WorklogFile {
+            user: self.user.clone(),
+            start: self.start.to_rfc3339(),
+            duration: format_duration(&self.duration),
+            note: self.note.clone(),
+        };
+        std::fs::write(&filename, toml::to_string(&worklog_file)?)?;
+        Ok(())
+    }
+}
+
+/// Read the entries in a `worklog` directory, oldest first.
+pub(crate) fn read_worklog(dir: &std::path::Path) -> Result<Vec<WorklogEntry>, WorklogError> {
+    let mut worklog = Vec::<WorklogEntry>::new();
+    for direntry in (dir.read_dir()?).flatten() {
+        worklog.push(WorklogEntry::new_from_file(&direntry.path())?);
+    }
+    worklog.sort_by(|a, b| a.start.cmp(&b.start).then(a.uuid.cmp(&b.uuid)));
+    Ok(worklog)
+}
+
+/// Parse a duration like "2h", "45m" or "1h30m".
+pub fn parse_duration(s: &str) -> Result<chrono::TimeDelta, WorklogError> {
+    let invalid = || WorklogError::InvalidDuration(String::from(s));
+    let mut minutes: i64 = 0;
+    let mut number = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let n = number.parse::<i64>().map_err(|_| invalid())?;
+        number.clear();
+        minutes += match c {
+            'h' => n * 60,
+            'm' => n,
+            _ => return Err(invalid()),
+        };
+    }
+    if !number.is_empty() || s.trim().is_empty() {
+        return Err(invalid());
+    }
+    Ok(chrono::TimeDelta::minutes(minutes))
+}
+
+/// Format a duration the way `parse_duration` reads it, to the minute.
+pub fn format_duration(duration: &chrono::TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{m}m"),
+        (h, 0) => format!("{h}h"),
+        (h, m) => format!("{h}h{m}m"),
+    }
+}
+
+/// Parse the start of a report period: "today", "yesterday",
+/// "last-week", "last-month", or a date.
+pub fn parse_since(
+    s: &str,
+    now: chrono::DateTime<chrono::Local>,
+) -> Result<chrono::DateTime<chrono::Local>, WorklogError> {
+    let today = now.date_naive();
+    let date = match s {
+        "today" => today,
+        "yesterday" => today - chrono::Days::new(1),
+        "last-week" => today - chrono::Days::new(7),
+        "last-month" => today - chrono::Months::new(1),
+        _ => chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .map_err(|_| WorklogError::InvalidSince(String::from(s)))?,
+    };
+    match date
+        .and_time(chrono::NaiveTime::MIN)
+        .and_local_timezone(chrono::Local)
+        .earliest()
+    {
+        Some(since) => Ok(since),
+        None => Err(WorklogError::InvalidSince(String::from(s))),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// How `time_report` groups the logged time.
+pub enum GroupBy {
+    Assignee,
+    User,
+    Issue,
+    Tag,
+}
+
+impl FromStr for GroupBy {
+    type Err = WorklogError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "assignee" => Ok(GroupBy::Assignee),
+            "user" => Ok(GroupBy::User),
+            "issue" => Ok(GroupBy::Issue),
+            "tag" => Ok(GroupBy::Tag),
+            _ => Err(WorklogError::InvalidGroupBy(String::from(s))),
+        }
+    }
+}
+
+/// Sum the time logged since `since`, grouped by `group_by`.  Time on
+/// issues with several tags counts towards each tag.
+pub fn time_report(
+    issues: &crate::Issues,
+    since: chrono::DateTime<chrono::Local>,
+    group_by: GroupBy,
+) -> std::collections::BTreeMap<String, chrono::TimeDelta> {
+    let mut report = std::collections::BTreeMap::<String, chrono::TimeDelta>::new();
+    for (issue_id, issue) in issues.iter() {
+        for entry in issue.worklog.iter().filter(|entry| entry.start >= since) {
+            let keys = match group_by {
+                GroupBy::Assignee => vec![match &issue.assignee {
+                    Some(assignee) => assignee.clone(),
+                    None => String::from("(unassigned)"),
+                }],
+                GroupBy::User => vec![entry.user.clone()],
+                GroupBy::Issue => vec![issue_id.clone()],
+                GroupBy::Tag if issue.tags.is_empty() => vec![String::from("(untagged)")],
+                GroupBy::Tag => issue.tags.clone(),
+            };
+            for key in keys {
+                *report.entry(key).or_default() += entry.duration;
+            }
+        }
+    }
+    report
+}
+
+fn timers_dir(repo: &crate::Repo) -> Result<std::path::PathBuf, WorklogError> {
+    let mut dir = crate::git::git_common_dir(repo)?;
+    dir.push("entomologist-timers");
+    Ok(dir)
+}
+
+/// Start a timer on an Issue.  Timers are local to this clone of the
+/// repo.
+pub fn start_timer(
+    repo: &crate::Repo,
+    issue_id: &str,
+    now: chrono::DateTime<chrono::Local>,
+) -> Result<(), WorklogError> {
+    let mut filename = timers_dir(repo)?;
+    std::fs::create_dir_all(&filename)?;
+    filename.push(issue_id);
+    if filename.exists() {
+        return Err(WorklogError::TimerRunning(String::from(issue_id)));
+    }
+    std::fs::write(&filename, now.to_rfc3339())?;
+    Ok(())
+}
+
+/// The time the timer on an Issue was started.  The timer keeps
+/// running until `clear_timer()`, which should only be called once the
+/// time is logged, so it's not lost if logging it fails.
+pub fn timer_start(
+    repo: &crate::Repo,
+    issue_id: &str,
+) -> Result<chrono::DateTime<chrono::Local>, WorklogError> {
+    let mut filename = timers_dir(repo)?;
+    filename.push(issue_id);
+    let start = match std::fs::read_to_string(&filename) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(WorklogError::TimerNotRunning(String::from(issue_id)));
+        }
+        Err(e) => return Err(e.into()),
+        Ok(start) => chrono::DateTime::parse_from_rfc3339(start.trim())?,
+    };
+    Ok(start.into())
+}
+
+/// Remove the timer on an Issue.
+pub fn clear_timer(repo: &crate::Repo, issue_id: &str) -> Result<(), WorklogError> {
+    let mut filename = timers_dir(repo)?;
+    filename.push(issue_id);
+    match std::fs::remove_file(&filename) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Err(WorklogError::TimerNotRunning(String::from(issue_id)))
+        }
+        Err(e) => Err(e.into()),
+        Ok(()) => Ok(()),
+    }
+}
+
+/// The running timers, as (issue id, start time).
+pub fn running_timers(
+    repo: &crate::Repo,
+) -> Result<Vec<(String, chrono::DateTime<chrono::Local>)>, WorklogError> {
+    let dir = timers_dir(repo)?;
+    let mut timers = Vec::<(String, chrono::DateTime<chrono::Local>)>::new();
+    if !dir.exists() {
+        return Ok(timers);
+    }
+    for direntry in (dir.read_dir()?).flatten() {
+        let start = std::fs::read_to_string(direntry.path())?;
+        let start = chrono::DateTime::parse_from_rfc3339(start.trim())?;
+        timers.push((
+            direntry.file_name().to_string_lossy().into_owned(),
+            start.into(),
+        ));
+    }
+    timers.sort_by_key(|(_, start)| *start);
+    Ok(timers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn durations() {
+        assert_eq!(
+            parse_duration("1h30m").unwrap(),
+            chrono::TimeDelta::minutes(90)
+        );
+        assert_eq!(
+            parse_duration("45m").unwrap(),
+            chrono::TimeDelta::minutes(45)
+        );
+        assert_eq!(parse_duration("2h").unwrap(), chrono::TimeDelta::hours(2));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("90").is_err());
+        assert!(parse_duration("1d").is_err());
+
+        assert_eq!(format_duration(&chrono::TimeDelta::minutes(90)), "1h30m");
+        assert_eq!(format_duration(&chrono::TimeDelta::hours(3)), "3h");
+        assert_eq!(format_duration(&chrono::TimeDelta::seconds(20)), "0m");
+    }
+
+    #[test]
+    fn since() {
+        let now = chrono::DateTime::parse_from_rfc3339("2025-07-24T10:08:38-06:00")
+            .unwrap()
+            .with_timezone(&chrono::Local);
+        let last_week = parse_since("last-week", now).unwrap();
+        assert_eq!(
+            last_week.date_naive(),
+            now.date_naive() - chrono::Days::new(7)
+        );
+        assert_eq!(last_week.time(), chrono::NaiveTime::MIN);
+        assert_eq!(
+            parse_since("2025-07-01", now).unwrap().date_naive(),
+            chrono::NaiveDate::from_ymd_opt(2025, 7, 1).unwrap()
+        );
+        assert!(parse_since("a while ago", now).is_err());
+    }
+
+    #[test]
+    fn timers() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = std::process::Command::new("git")
+            .args(["init", &dir.path().to_string_lossy()])
+            .output()
+            .unwrap();
+        assert!(result.status.success());
+        let repo = crate::Repo::new(dir.path());
+        let now = chrono::DateTime::parse_from_rfc3339("2025-07-24T10:08:38-06:00")
+            .unwrap()
+            .with_timezone(&chrono::Local);
+
+        start_timer(&repo, "abc", now).unwrap();
+        assert!(start_timer(&repo, "abc", now).is_err());
+        assert_eq!(timer_start(&repo, "abc").unwrap(), now);
+        // Reading the start time leaves the timer running.
+        assert_eq!(
+            running_timers(&repo).unwrap(),
+            vec![(String::from("abc"), now)]
+        );
+
+        clear_timer(&repo, "abc").unwrap();
+        assert!(running_timers(&repo).unwrap().is_empty());
+        assert!(timer_start(&repo, "abc").is_err());
+        assert!(clear_timer(&repo, "abc").is_err());
+    }
+}
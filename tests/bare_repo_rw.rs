@@ -0,0 +1,42 @@
+#[allow(dead_code)]
+mod common;
+
+#[test]
+/// A bare repo with no commits at all, like a freshly created central
+/// repo.  The `entomologist-data` branch is created in it, and issues
+/// are written and read back.
+fn bare_repo_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_bare_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let id = issues
+        .new_issue(&Some(String::from("issue in a bare repo")), None)
+        .unwrap()
+        .id
+        .clone();
+    drop(issues);
+
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    let issue = issues.get_issue(&id).unwrap();
+    assert_eq!(issue.title(), "issue in a bare repo");
+}
+
+#[test]
+/// A non-bare repo where HEAD has no commits yet.
+fn unborn_head_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = tempfile::tempdir().unwrap();
+    let result = std::process::Command::new("git")
+        .args(["init", &repo_dir.path().to_string_lossy()])
+        .output()
+        .unwrap();
+    assert!(result.status.success());
+    let repo = entomologist::Repo::new(repo_dir.path());
+
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    assert_eq!(issues.iter().count(), 0);
+}
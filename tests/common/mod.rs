@@ -35,11 +35,24 @@ pub fn make_test_repo() -> tempfile::TempDir {
     workdir
 }
 
-/// Create an `entomologist-data` branch in the repo that we're
-/// currently in.
+/// Create a tempdir with automatic cleanup-on-drop, and initialize a
+/// bare git repo with no commits in it, like a freshly created central
+/// repo.
 #[allow(dead_code)]
-pub fn make_entomologist_branch() {
-    let issues = entomologist::IssuesMut::new_from_git("entomologist-data").unwrap();
+pub fn make_bare_repo() -> tempfile::TempDir {
+    let workdir = tempfile::tempdir().unwrap();
+    let result = std::process::Command::new("git")
+        .args(["init", "--bare", &workdir.path().to_string_lossy()])
+        .output()
+        .unwrap();
+    assert!(result.status.success());
+    workdir
+}
+
+/// Create an `entomologist-data` branch in the repo.
+#[allow(dead_code)]
+pub fn make_entomologist_branch(repo: &entomologist::Repo) {
+    let issues = entomologist::IssuesMut::new_from_git(repo, "entomologist-data").unwrap();
     entomologist::issue::Issue::new(
         &issues.path(),
         &Some(String::from("issue created on remote")),
@@ -70,3 +83,35 @@ pub fn clone_repo(remote_repo: &std::path::Path) -> tempfile::TempDir {
 
     workdir
 }
+
+/// Count the commits on `branch` of the repo in `dir`.
+#[allow(dead_code)]
+pub fn commit_count(dir: &std::path::Path, branch: &str) -> usize {
+    let result = std::process::Command::new("git")
+        .args(["rev-list", "--count", branch])
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(result.status.success());
+    String::from_utf8_lossy(&result.stdout)
+        .trim()
+        .parse()
+        .unwrap()
+}
+
+/// Run git in `dir`, returning its trimmed stdout.  Panics, showing
+/// git's output, if it fails.
+#[allow(dead_code)]
+pub fn git(dir: &std::path::Path, args: &[&str]) -> String {
+    let result = std::process::Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    if !result.status.success() {
+        println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
+        println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
+        panic!("git {args:?} failed");
+    }
+    String::from_utf8_lossy(&result.stdout).trim().to_string()
+}
@@ -0,0 +1,156 @@
+mod common;
+
+/// Commit a file to the branch behind entomologist's back, the way a
+/// git hook or an `ent` on another machine sharing the repo might.
+fn commit_behind_our_back(repo_dir: &std::path::Path, branch: &str, file: &str, contents: &str) {
+    let worktree_dir = tempfile::tempdir().unwrap();
+    let worktree = worktree_dir.path().to_string_lossy();
+    let git = |dir: &std::path::Path, args: &[&str]| {
+        let result = std::process::Command::new("git")
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        if !result.status.success() {
+            println!("stdout: {}", &String::from_utf8_lossy(&result.stdout));
+            println!("stderr: {}", &String::from_utf8_lossy(&result.stderr));
+            panic!("git {args:?} failed");
+        }
+    };
+    git(repo_dir, &["worktree", "add", &worktree, branch]);
+    let mut filename = std::path::PathBuf::from(worktree_dir.path());
+    filename.push(file);
+    std::fs::create_dir_all(filename.parent().unwrap()).unwrap();
+    std::fs::write(&filename, contents).unwrap();
+    git(worktree_dir.path(), &["add", file]);
+    git(worktree_dir.path(), &["commit", "-m", "behind your back"]);
+    git(repo_dir, &["worktree", "remove", &worktree]);
+}
+
+#[test]
+/// Someone else commits to the branch while we're writing to it.
+/// Their change is merged with ours, nothing is lost.
+fn concurrent_writers_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    common::make_entomologist_branch(&repo);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let id = issues.iter().next().unwrap().0.clone();
+
+    commit_behind_our_back(
+        repo_dir.path(),
+        branch,
+        &format!("{id}/tags/from-elsewhere"),
+        "",
+    );
+
+    issues
+        .get_issue_mut(&id)
+        .unwrap()
+        .set_assignee("seb")
+        .unwrap();
+    drop(issues);
+
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    let issue = issues.get_issue(&id).unwrap();
+    assert_eq!(issue.assignee.as_deref(), Some("seb"));
+    assert_eq!(issue.tags, vec![String::from("from-elsewhere")]);
+}
+
+#[test]
+/// Someone else changes the same thing we do at the same time.  Our
+/// commit fails instead of overwriting theirs.
+fn conflicting_writers_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    common::make_entomologist_branch(&repo);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let id = issues.iter().next().unwrap().0.clone();
+
+    commit_behind_our_back(
+        repo_dir.path(),
+        branch,
+        &format!("{id}/assignee"),
+        "beckett",
+    );
+
+    assert!(
+        issues
+            .get_issue_mut(&id)
+            .unwrap()
+            .set_assignee("seb")
+            .is_err()
+    );
+    drop(issues);
+
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    assert_eq!(
+        issues.get_issue(&id).unwrap().assignee.as_deref(),
+        Some("beckett")
+    );
+}
+
+#[test]
+/// Writers on the same machine take turns committing: a commit waits
+/// for the lock on the branch, and gives up when the lock timeout runs
+/// out.
+fn locked_writers_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path())
+        .with_lock_timeout(std::time::Duration::from_millis(200));
+    common::make_entomologist_branch(&repo);
+
+    // Several writers can be open at once.
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let mut other_issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let id = issues.iter().next().unwrap().0.clone();
+
+    let lock =
+        entomologist::lock::Lock::acquire(&repo, &entomologist::lock::branch_lock_name(branch))
+            .unwrap();
+    match issues.get_issue_mut(&id).unwrap().set_assignee("seb") {
+        Err(entomologist::issue::IssueError::GitError(entomologist::git::GitError::Lock(
+            error,
+        ))) => match *error {
+            entomologist::lock::LockError::Busy(holder) => {
+                assert_eq!(holder.pid, std::process::id());
+            }
+            other => panic!("expected the database to be busy, got {other:?}"),
+        },
+        other => panic!("expected the database to be busy, got {other:?}"),
+    }
+
+    // Readers don't wait for writers.
+    entomologist::Issues::new_from_git(&repo, branch).unwrap();
+
+    drop(lock);
+    issues
+        .get_issue_mut(&id)
+        .unwrap()
+        .set_assignee("seb")
+        .unwrap();
+    other_issues
+        .new_issue(&Some(String::from("another issue")), None)
+        .unwrap();
+    drop(issues);
+    drop(other_issues);
+
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    assert_eq!(
+        issues.get_issue(&id).unwrap().assignee.as_deref(),
+        Some("seb")
+    );
+    assert!(
+        issues
+            .iter()
+            .any(|(_, issue)| issue.title() == "another issue")
+    );
+}
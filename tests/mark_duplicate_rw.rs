@@ -0,0 +1,59 @@
+mod common;
+
+#[test]
+/// Marking an issue as a duplicate is a single commit, or nothing at
+/// all if the workflow doesn't allow it.
+fn mark_duplicate_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    common::make_entomologist_branch(&repo);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let original_id = issues.iter().next().unwrap().0.clone();
+    let duplicate_id = issues
+        .new_issue(&Some(String::from("motor stalls again")), None)
+        .unwrap()
+        .id
+        .clone();
+    let other_id = issues
+        .new_issue(&Some(String::from("motor stalls once more")), None)
+        .unwrap()
+        .id
+        .clone();
+
+    let before = common::commit_count(repo_dir.path(), branch);
+    issues.mark_duplicate(&duplicate_id, &original_id).unwrap();
+    assert_eq!(common::commit_count(repo_dir.path(), branch), before + 1);
+
+    // New issues can't be dropped any more.
+    let config = issues.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "[workflow.transitions]\nNew = [\"Backlog\", \"InProgress\"]\n",
+    )
+    .unwrap();
+    entomologist::git::add(&config).unwrap();
+    entomologist::git::commit(&issues.path(), "forbid New -> WontDo").unwrap();
+    drop(issues);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let before = common::commit_count(repo_dir.path(), branch);
+    assert!(issues.mark_duplicate(&other_id, &original_id).is_err());
+    assert_eq!(common::commit_count(repo_dir.path(), branch), before);
+    let other = issues.get_issue(&other_id).unwrap();
+    assert!(other.links.is_empty());
+    assert!(other.comments.is_empty());
+    drop(issues);
+
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    let duplicate = issues.get_issue(&duplicate_id).unwrap();
+    assert_eq!(duplicate.state, entomologist::issue::State::WontDo);
+    assert_eq!(duplicate.links.len(), 1);
+    assert_eq!(duplicate.comments.len(), 1);
+    let other = issues.get_issue(&other_id).unwrap();
+    assert_eq!(other.state, entomologist::issue::State::New);
+    assert!(other.links.is_empty());
+    assert!(other.comments.is_empty());
+}
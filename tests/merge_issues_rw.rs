@@ -0,0 +1,84 @@
+mod common;
+
+#[test]
+/// Merging one issue into another is a single commit.
+fn merge_issues_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    common::make_entomologist_branch(&repo);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let into_id = issues.iter().next().unwrap().0.clone();
+    let from_id = issues
+        .new_issue(&Some(String::from("motor stalls again")), None)
+        .unwrap()
+        .id
+        .clone();
+    issues
+        .get_issue_mut(&from_id)
+        .unwrap()
+        .add_tag("motor")
+        .unwrap();
+
+    let before = common::commit_count(repo_dir.path(), branch);
+    issues.merge_issues(&from_id, &into_id).unwrap();
+    drop(issues);
+    assert_eq!(common::commit_count(repo_dir.path(), branch), before + 1);
+
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    let into = issues.get_issue(&into_id).unwrap();
+    assert_eq!(into.tags, vec![String::from("motor")]);
+    let from = issues.get_issue(&from_id).unwrap();
+    assert_eq!(from.state, entomologist::issue::State::WontDo);
+    assert_eq!(from.comments.len(), 1);
+}
+
+#[test]
+/// A merge that fails leaves both issues as they were.
+fn merge_issues_failed_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    common::make_entomologist_branch(&repo);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let into_id = issues.iter().next().unwrap().0.clone();
+    let from_id = issues
+        .new_issue(&Some(String::from("motor stalls again")), None)
+        .unwrap()
+        .id
+        .clone();
+    issues
+        .get_issue_mut(&from_id)
+        .unwrap()
+        .add_tag("motor")
+        .unwrap();
+    let from = issues.get_issue_mut(&from_id).unwrap();
+    let comment = from
+        .add_comment(&Some(String::from("still stalls")))
+        .unwrap();
+    from.comments.push(comment);
+
+    // The merged issue can't be dropped, so the merge fails after its
+    // tags and comments are moved.
+    let config = issues.path().join("config.toml");
+    std::fs::write(
+        &config,
+        "[workflow.transitions]\nNew = [\"Backlog\", \"InProgress\"]\n",
+    )
+    .unwrap();
+    entomologist::git::add(&config).unwrap();
+    entomologist::git::commit(&issues.path(), "forbid New -> WontDo").unwrap();
+    drop(issues);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let before = common::commit_count(repo_dir.path(), branch);
+    assert!(issues.merge_issues(&from_id, &into_id).is_err());
+    assert_eq!(common::commit_count(repo_dir.path(), branch), before);
+    assert!(issues.get_issue(&into_id).unwrap().tags.is_empty());
+    assert!(issues.get_issue(&into_id).unwrap().comments.is_empty());
+    assert_eq!(issues.get_issue(&from_id).unwrap().comments.len(), 1);
+}
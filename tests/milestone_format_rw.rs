@@ -0,0 +1,31 @@
+mod common;
+
+#[test]
+/// Creating the first milestone marks the database as format 2, in the
+/// same commit, and a failed one leaves the config alone.
+fn milestone_format_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    common::make_entomologist_branch(&repo);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    assert_eq!(issues.config().format(), 1);
+    let config = issues.path().join("config.toml");
+
+    let before = common::commit_count(repo_dir.path(), branch);
+    assert!(issues.new_milestone("bad/name", "nope", None).is_err());
+    assert_eq!(common::commit_count(repo_dir.path(), branch), before);
+    assert_eq!(issues.config().format(), 1);
+    assert!(!config.exists());
+
+    issues.new_milestone("v1.0", "first release", None).unwrap();
+    assert_eq!(common::commit_count(repo_dir.path(), branch), before + 1);
+    assert_eq!(issues.config().format(), 2);
+    drop(issues);
+
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    assert_eq!(issues.config().format(), 2);
+    assert!(issues.get_milestone("v1.0").is_some());
+}
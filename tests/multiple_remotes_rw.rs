@@ -0,0 +1,115 @@
+mod common;
+
+#[test]
+/// A repo with two remotes, one of which calls the issues branch
+/// something else.  Syncing with each pushes the issues to the right
+/// branch, and changes from one reach the other.
+fn multiple_remotes_rw() {
+    let branch = "entomologist-data";
+
+    let public_dir = tempfile::tempdir().unwrap();
+    common::git(public_dir.path(), &["init", "--bare"]);
+    let internal_dir = tempfile::tempdir().unwrap();
+    common::git(internal_dir.path(), &["init", "--bare"]);
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    let public_url = public_dir.path().to_string_lossy();
+    let internal_url = internal_dir.path().to_string_lossy();
+    common::git(repo_dir.path(), &["remote", "add", "public", &public_url]);
+    common::git(
+        repo_dir.path(),
+        &["remote", "add", "internal", &internal_url],
+    );
+    common::git(
+        repo_dir.path(),
+        &["config", "remote.internal.entomologistBranch", "issues"],
+    );
+
+    assert_eq!(
+        entomologist::git::default_sync_remotes(&repo).unwrap(),
+        vec![String::from("origin")]
+    );
+    common::git(
+        repo_dir.path(),
+        &["config", "--add", "entomologist.remote", "public"],
+    );
+    common::git(
+        repo_dir.path(),
+        &["config", "--add", "entomologist.remote", "internal"],
+    );
+    assert_eq!(
+        entomologist::git::default_sync_remotes(&repo).unwrap(),
+        vec![String::from("public"), String::from("internal")]
+    );
+    assert_eq!(
+        entomologist::git::remote_branch_name(&repo, "internal", branch).unwrap(),
+        "issues"
+    );
+    assert_eq!(
+        entomologist::git::remote_branch_name(&repo, "public", branch).unwrap(),
+        branch
+    );
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let id = issues
+        .new_issue(&Some(String::from("mirrored issue")), None)
+        .unwrap()
+        .id
+        .clone();
+    for remote in ["public", "internal"] {
+        let summary = entomologist::git::sync(&issues.path(), remote, branch).unwrap();
+        assert!(summary.incoming.is_empty());
+        assert!(summary.outgoing.issues.iter().any(|issue| issue.id == id));
+    }
+    drop(issues);
+
+    // The internal remote has the issues in its `issues` branch.
+    let local_tip = common::git(repo_dir.path(), &["rev-parse", branch]);
+    assert_eq!(
+        common::git(public_dir.path(), &["rev-parse", branch]),
+        local_tip
+    );
+    assert_eq!(
+        common::git(internal_dir.path(), &["rev-parse", "issues"]),
+        local_tip
+    );
+    let statuses = entomologist::git::remote_status(&repo, branch).unwrap();
+    assert_eq!(statuses.len(), 2);
+    for status in statuses {
+        assert!(status.has_branch);
+        assert_eq!((status.ahead, status.behind), (0, 0));
+    }
+
+    // An issue filed on the internal remote comes in from there, and
+    // goes out to the public one.
+    let internal_clone_dir = common::clone_repo(internal_dir.path());
+    common::git(
+        internal_clone_dir.path(),
+        &["config", "remote.origin.entomologistBranch", "issues"],
+    );
+    let internal_clone = entomologist::Repo::new(internal_clone_dir.path());
+    let mut issues = entomologist::IssuesMut::new_from_git(&internal_clone, branch).unwrap();
+    // The clone's issue branch comes from the remote's `issues` branch.
+    assert_eq!(
+        common::git(internal_clone_dir.path(), &["rev-parse", branch]),
+        local_tip
+    );
+    let internal_id = issues
+        .new_issue(&Some(String::from("internal issue")), None)
+        .unwrap()
+        .id
+        .clone();
+    entomologist::git::sync(&issues.path(), "origin", branch).unwrap();
+    drop(issues);
+
+    let issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let preview = entomologist::git::sync_preview(&repo, "internal", branch).unwrap();
+    assert_eq!(preview.incoming.issues.len(), 1);
+    assert_eq!(preview.incoming.issues[0].id, internal_id);
+    let summary = entomologist::git::sync(&issues.path(), "internal", branch).unwrap();
+    assert_eq!(summary.incoming, preview.incoming);
+    let summary = entomologist::git::sync(&issues.path(), "public", branch).unwrap();
+    assert_eq!(summary.outgoing.issues.len(), 1);
+    assert_eq!(summary.outgoing.issues[0].id, internal_id);
+}
@@ -4,7 +4,7 @@ mod common;
 /// No remote repo exists.
 /// No local `entomologist-data` branch exists.
 fn no_remote_no_local_entomologist_data_ro() {
-    let repo = common::make_test_repo();
-    std::env::set_current_dir(&repo).unwrap();
-    let _issues = entomologist::issues::Issues::new_from_git("entomologist_data").unwrap();
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    let _issues = entomologist::issues::Issues::new_from_git(&repo, "entomologist_data").unwrap();
 }
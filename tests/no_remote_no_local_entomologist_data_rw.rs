@@ -6,9 +6,9 @@ mod common;
 fn no_remote_no_local_entomologist_data_rw() {
     let branch = "entomologist-data";
 
-    let repo = common::make_test_repo();
-    std::env::set_current_dir(&repo).unwrap();
-    let issues = entomologist::IssuesMut::new_from_git(branch).unwrap();
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    let issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
 
     let remote = "origin";
     match entomologist::git::sync(&issues.path(), remote, branch) {
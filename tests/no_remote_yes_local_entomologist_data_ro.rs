@@ -4,8 +4,8 @@ mod common;
 /// No remote repo exists.
 /// A local `entomologist-data` branch exists.
 fn no_remote_yes_local_entomologist_data_ro() {
-    let repo = common::make_test_repo();
-    std::env::set_current_dir(&repo).unwrap();
-    common::make_entomologist_branch();
-    let _issues = entomologist::issues::Issues::new_from_git("entomologist-data").unwrap();
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    common::make_entomologist_branch(&repo);
+    let _issues = entomologist::issues::Issues::new_from_git(&repo, "entomologist-data").unwrap();
 }
@@ -0,0 +1,126 @@
+mod common;
+
+use entomologist::issue::State;
+
+#[test]
+/// A contributor without write access proposes a new issue and pushes
+/// the proposal to their fork.  The maintainer fetches it, sees what it
+/// changes, and accepts it.  A proposal that conflicts with the issues
+/// branch isn't accepted.
+fn proposals_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    common::make_entomologist_branch(&repo);
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    let id = issues.iter().next().unwrap().0.clone();
+    drop(issues);
+
+    let fork_dir = tempfile::tempdir().unwrap();
+    common::git(fork_dir.path(), &["init", "--bare"]);
+    let contributor_dir = common::clone_repo(repo_dir.path());
+    let contributor = entomologist::Repo::new(contributor_dir.path());
+    let fork_url = fork_dir.path().to_string_lossy();
+    common::git(
+        contributor_dir.path(),
+        &["remote", "add", "fork", &fork_url],
+    );
+
+    assert!(matches!(
+        entomologist::proposal::create(&contributor, branch, "bad..name"),
+        Err(entomologist::proposal::ProposalError::InvalidName(_))
+    ));
+    let proposal_branch = entomologist::proposal::create(&contributor, branch, "motor").unwrap();
+    assert_eq!(proposal_branch, "entomologist-data-proposals/motor");
+    let mut issues = entomologist::IssuesMut::new_from_git(&contributor, &proposal_branch).unwrap();
+    let new_id = issues
+        .new_issue(&Some(String::from("motor stalls")), None)
+        .unwrap()
+        .id
+        .clone();
+    issues.set_state(&id, State::InProgress).unwrap();
+    entomologist::git::sync(&issues.path(), "fork", &proposal_branch).unwrap();
+    drop(issues);
+
+    // The contributor's issues branch is untouched, and their own
+    // proposal is listed.
+    assert_eq!(
+        common::git(contributor_dir.path(), &["rev-parse", branch]),
+        common::git(repo_dir.path(), &["rev-parse", branch])
+    );
+    let proposals = entomologist::proposal::list(&contributor, branch).unwrap();
+    assert_eq!(proposals.len(), 2);
+    assert_eq!(proposals[0].branch, proposal_branch);
+    assert_eq!(proposals[0].remote, None);
+    assert_eq!(
+        proposals[1].branch,
+        "fork/entomologist-data-proposals/motor"
+    );
+
+    common::git(
+        repo_dir.path(),
+        &["remote", "add", "contributor", &fork_url],
+    );
+    common::git(repo_dir.path(), &["fetch", "contributor"]);
+    let proposals = entomologist::proposal::list(&repo, branch).unwrap();
+    assert_eq!(proposals.len(), 1);
+    let proposal = &proposals[0];
+    assert_eq!(proposal.name, "motor");
+    assert_eq!(proposal.remote.as_deref(), Some("contributor"));
+    assert_eq!(
+        proposal
+            .changes
+            .issues
+            .iter()
+            .map(|issue| (issue.id.as_str(), issue.status))
+            .collect::<Vec<_>>(),
+        {
+            let mut expected = vec![
+                (id.as_str(), entomologist::changes::IssueStatus::Modified),
+                (new_id.as_str(), entomologist::changes::IssueStatus::Created),
+            ];
+            expected.sort_by_key(|(id, _)| *id);
+            expected
+        }
+    );
+
+    let accepted = entomologist::proposal::accept(&repo, branch, &proposal.branch).unwrap();
+    assert_eq!(accepted, proposal.changes);
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    assert!(issues.get_issue(&new_id).is_some());
+    assert_eq!(issues.get_issue(&id).unwrap().state, State::InProgress);
+    drop(issues);
+    assert!(
+        entomologist::proposal::list(&repo, branch)
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        entomologist::proposal::accept(&repo, branch, &proposal.branch)
+            .unwrap()
+            .is_empty()
+    );
+
+    // A proposal changing the state of the issue differently from the
+    // issues branch conflicts.
+    let proposal_branch = entomologist::proposal::create(&repo, branch, "conflict").unwrap();
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, &proposal_branch).unwrap();
+    issues.set_state(&id, State::WontDo).unwrap();
+    drop(issues);
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    issues.set_state(&id, State::Done).unwrap();
+    drop(issues);
+    let tip = common::git(repo_dir.path(), &["rev-parse", branch]);
+    match entomologist::proposal::accept(&repo, branch, "conflict") {
+        Err(entomologist::proposal::ProposalError::MergeError { error, .. }) => {
+            assert_eq!(error.kind(), entomologist::git::FailureKind::MergeConflict)
+        }
+        result => panic!("unexpected result {result:?}"),
+    }
+    assert_eq!(common::git(repo_dir.path(), &["rev-parse", branch]), tip);
+    assert!(matches!(
+        entomologist::proposal::accept(&repo, branch, "nonexistent"),
+        Err(entomologist::proposal::ProposalError::NotFound(_))
+    ));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+#[test]
+/// Purging a comment that was moved from one issue to another removes
+/// it from the history of the issue it started in too, both for `ent
+/// rm` (the comment is removed) and `ent redact` (its description is
+/// replaced).
+fn purge_moved_comment_rw() {
+    let branch = "entomologist-data";
+
+    let repo_dir = common::make_test_repo();
+    let repo = entomologist::Repo::new(repo_dir.path());
+    common::make_entomologist_branch(&repo);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    let from_id = issues.iter().next().unwrap().0.clone();
+    let to_id = issues
+        .new_issue(&Some(String::from("motor stalls again")), None)
+        .unwrap()
+        .id
+        .clone();
+    let removed = issues
+        .get_issue_mut(&from_id)
+        .unwrap()
+        .add_comment(&Some(String::from("the password is hunter2")))
+        .unwrap()
+        .uuid;
+    let redacted = issues
+        .get_issue_mut(&from_id)
+        .unwrap()
+        .add_comment(&Some(String::from("the other password is swordfish")))
+        .unwrap()
+        .uuid;
+    drop(issues);
+
+    let mut issues = entomologist::IssuesMut::new_from_git(&repo, branch).unwrap();
+    issues.move_comment(&removed, &to_id).unwrap();
+    issues.move_comment(&redacted, &to_id).unwrap();
+
+    issues
+        .get_issue_mut(&to_id)
+        .unwrap()
+        .remove_comment(&removed)
+        .unwrap();
+    entomologist::git::purge_from_history(
+        &repo,
+        &issues.path(),
+        branch,
+        &format!("*/comments/{removed}"),
+        None,
+    )
+    .unwrap();
+
+    let issue = issues.get_issue_mut(&to_id).unwrap();
+    let comment = issue
+        .comments
+        .iter_mut()
+        .find(|comment| comment.uuid == redacted)
+        .unwrap();
+    comment.redact().unwrap();
+    entomologist::git::purge_from_history(
+        &repo,
+        &issues.path(),
+        branch,
+        &format!("*/comments/{redacted}/description"),
+        Some(entomologist::issue::REDACTED_DESCRIPTION),
+    )
+    .unwrap();
+    drop(issues);
+
+    // Commit messages name the comment, but no commit has its files.
+    let paths = common::git(
+        repo_dir.path(),
+        &["log", "--format=", "--name-only", branch],
+    );
+    assert!(!paths.contains(&removed));
+    let history = common::git(repo_dir.path(), &["log", "--format=", "-p", branch]);
+    assert!(!history.contains("hunter2"));
+    assert!(!history.contains("swordfish"));
+
+    let issues = entomologist::Issues::new_from_git(&repo, branch).unwrap();
+    let comments = &issues.get_issue(&to_id).unwrap().comments;
+    assert_eq!(comments.len(), 1);
+    assert_eq!(
+        comments[0].description,
+        entomologist::issue::REDACTED_DESCRIPTION
+    );
+}
@@ -0,0 +1,35 @@
+mod common;
+
+#[test]
+/// Two repos, neither of them the current directory.
+/// Each repo gets its own `entomologist-data` branch and issues.
+fn two_repos_rw() {
+    let branch = "entomologist-data";
+
+    let repo_a_dir = common::make_test_repo();
+    let repo_a = entomologist::Repo::new(repo_a_dir.path());
+    let repo_b_dir = common::make_test_repo();
+    let repo_b = entomologist::Repo::new(repo_b_dir.path());
+
+    let mut issues_a = entomologist::IssuesMut::new_from_git(&repo_a, branch).unwrap();
+    let id_a = issues_a
+        .new_issue(&Some(String::from("issue in repo a")), None)
+        .unwrap()
+        .id
+        .clone();
+    let mut issues_b = entomologist::IssuesMut::new_from_git(&repo_b, branch).unwrap();
+    let id_b = issues_b
+        .new_issue(&Some(String::from("issue in repo b")), None)
+        .unwrap()
+        .id
+        .clone();
+    drop(issues_a);
+    drop(issues_b);
+
+    let issues_a = entomologist::Issues::new_from_git(&repo_a, branch).unwrap();
+    assert!(issues_a.get_issue(&id_a).is_some());
+    assert!(issues_a.get_issue(&id_b).is_none());
+    let issues_b = entomologist::Issues::new_from_git(&repo_b, branch).unwrap();
+    assert!(issues_b.get_issue(&id_b).is_some());
+    assert!(issues_b.get_issue(&id_a).is_none());
+}
@@ -8,16 +8,16 @@ fn yes_remote_no_remote_entomologist_data_no_local_entomologist_data_rw() {
     let branch = "entomologist-data";
 
     // Make a temporary repo with an `entomologist-data` branch in it.
-    let remote_repo = common::make_test_repo();
-    std::env::set_current_dir(&remote_repo).unwrap();
+    let remote_repo_dir = common::make_test_repo();
+    let remote_repo = entomologist::Repo::new(remote_repo_dir.path());
 
     // Clone the "remote" repo into another temporary repo.
-    let local_repo = common::clone_repo(&remote_repo.path());
-    std::env::set_current_dir(&local_repo).unwrap();
+    let local_repo_dir = common::clone_repo(&remote_repo.path());
+    let local_repo = entomologist::Repo::new(local_repo_dir.path());
 
     // This creates a local entomologist-data branch, with no issues
     // in it.
-    let issues = entomologist::IssuesMut::new_from_git(branch).unwrap();
+    let issues = entomologist::IssuesMut::new_from_git(&local_repo, branch).unwrap();
 
     let remote = "origin";
     match entomologist::git::sync(&issues.path(), remote, branch) {
@@ -7,15 +7,15 @@ mod common;
 fn yes_remote_no_remote_entomologist_data_yes_local_entomologist_data_rw() {
     let branch = "entomologist-data";
 
-    let remote_repo = common::make_test_repo();
-    std::env::set_current_dir(&remote_repo).unwrap();
+    let remote_repo_dir = common::make_test_repo();
+    let remote_repo = entomologist::Repo::new(remote_repo_dir.path());
 
     // Clone the "remote" repo into another temporary repo.
-    let local_repo = common::clone_repo(&remote_repo.path());
-    std::env::set_current_dir(&local_repo).unwrap();
-    common::make_entomologist_branch();
+    let local_repo_dir = common::clone_repo(&remote_repo.path());
+    let local_repo = entomologist::Repo::new(local_repo_dir.path());
+    common::make_entomologist_branch(&local_repo);
 
-    let issues = entomologist::IssuesMut::new_from_git(branch).unwrap();
+    let issues = entomologist::IssuesMut::new_from_git(&local_repo, branch).unwrap();
 
     let remote = "origin";
     match entomologist::git::sync(&issues.path(), remote, branch) {
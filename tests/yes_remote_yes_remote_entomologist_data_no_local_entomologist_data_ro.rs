@@ -6,13 +6,13 @@ mod common;
 /// The remote repo has an `entomologist-data` branch.
 fn yes_remote_yes_remote_entomologist_data_no_local_entomologist_data_ro() {
     // Make a temporary repo with an `entomologist-data` branch in it.
-    let remote_repo = common::make_test_repo();
-    std::env::set_current_dir(&remote_repo).unwrap();
-    common::make_entomologist_branch();
+    let remote_repo_dir = common::make_test_repo();
+    let remote_repo = entomologist::Repo::new(remote_repo_dir.path());
+    common::make_entomologist_branch(&remote_repo);
 
     // Clone the "remote" repo into another temporary repo.
-    let local_repo = common::clone_repo(&remote_repo.path());
-    std::env::set_current_dir(&local_repo).unwrap();
+    let local_repo_dir = common::clone_repo(&remote_repo.path());
+    let local_repo = entomologist::Repo::new(local_repo_dir.path());
 
-    let _issues = entomologist::Issues::new_from_git("entomologist-data").unwrap();
+    let _issues = entomologist::Issues::new_from_git(&local_repo, "entomologist-data").unwrap();
 }
@@ -8,15 +8,15 @@ fn yes_remote_yes_remote_entomologist_data_yes_local_entomologist_data_rw() {
     let branch = "entomologist-data";
 
     // Make a temporary repo with an `entomologist-data` branch in it.
-    let remote_repo = common::make_test_repo();
-    std::env::set_current_dir(&remote_repo).unwrap();
-    common::make_entomologist_branch();
+    let remote_repo_dir = common::make_test_repo();
+    let remote_repo = entomologist::Repo::new(remote_repo_dir.path());
+    common::make_entomologist_branch(&remote_repo);
 
     // Clone the "remote" repo into another temporary repo.
-    let local_repo = common::clone_repo(&remote_repo.path());
-    std::env::set_current_dir(&local_repo).unwrap();
+    let local_repo_dir = common::clone_repo(&remote_repo.path());
+    let local_repo = entomologist::Repo::new(local_repo_dir.path());
 
-    let issues = entomologist::IssuesMut::new_from_git(branch).unwrap();
+    let issues = entomologist::IssuesMut::new_from_git(&local_repo, branch).unwrap();
 
     // Make a local issue.
     entomologist::issue::Issue::new(